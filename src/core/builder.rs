@@ -0,0 +1,197 @@
+use std::path::Path;
+
+use crate::core::config::{
+    Backend,
+    CameraPose,
+    DynamicResolution,
+    LatrConfig,
+    PowerPreference,
+    PresentMode,
+    RenderMode,
+    RenderResolution,
+    RunMode,
+    ScaleMode,
+    SceneCapacity,
+    ToneMapping,
+};
+use crate::core::error::LatrError;
+use crate::core::latr_core::LatrEngine;
+use crate::engine::background::Background;
+use crate::engine::camera::CameraController;
+use crate::engine::post::PostEffect;
+
+// End of imports
+
+// Chainable way of putting together a LatrConfig, made with LatrEngine::builder
+// Nothing is checked until build, which validates the whole config at once, see LatrConfig::validate
+#[derive(Clone, Default)]
+pub struct LatrEngineBuilder {
+    config: LatrConfig,
+}
+
+impl LatrEngineBuilder {
+    // Starts from an existing config instead of the defaults
+    pub fn from_config(config: LatrConfig) -> Self {
+        Self { config }
+    }
+
+    // Window size, and the size rays are traced at when the render resolution is fixed
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.config.resolution = (width, height);
+        self.config.num_rays = (width, height);
+        self
+    }
+
+    // Traces at this size no matter how the window is resized
+    pub fn fixed_render_resolution(mut self, width: u32, height: u32) -> Self {
+        self.config.num_rays = (width, height);
+        self.config.render_resolution = RenderResolution::Fixed;
+        self
+    }
+
+    // Traces at the window size times the scale, so 0.5 traces a quarter of the pixels
+    pub fn render_scale(mut self, scale: f32) -> Self {
+        self.config.render_resolution = RenderResolution::WindowScale(scale);
+        self
+    }
+
+    pub fn scale_mode(mut self, scale_mode: ScaleMode) -> Self {
+        self.config.scale_mode = scale_mode;
+        self
+    }
+
+    pub fn dynamic_resolution(mut self, dynamic_resolution: DynamicResolution) -> Self {
+        self.config.dynamic_resolution = Some(dynamic_resolution);
+        self
+    }
+
+    pub fn run_mode(mut self, run_mode: RunMode) -> Self {
+        self.config.run_mode = run_mode;
+        self
+    }
+
+    pub fn fps_cap(mut self, fps_cap: u32) -> Self {
+        self.config.fps_cap = fps_cap;
+        self
+    }
+
+    pub fn present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.config.present_mode = present_mode;
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.config.adapter.power_preference = power_preference;
+        self
+    }
+
+    pub fn backends(mut self, backends: &[Backend]) -> Self {
+        self.config.adapter.backends = backends.to_vec();
+        self
+    }
+
+    // Only adapters with this in their name, ignoring case
+    pub fn adapter_name(mut self, name: &str) -> Self {
+        self.config.adapter.name = Some(name.to_string());
+        self
+    }
+
+    pub fn force_fallback_adapter(mut self, force_fallback: bool) -> Self {
+        self.config.adapter.force_fallback = force_fallback;
+        self
+    }
+
+    // Room for bigger scenes than the default, SceneCapacity::for_triangles works it out from a triangle count
+    pub fn scene_capacity(mut self, capacity: SceneCapacity) -> Self {
+        self.config.scene_capacity = capacity;
+        self
+    }
+
+    // Model config file listing every model to load, see attach_models on LatrConfig
+    pub fn models<P: AsRef<Path>>(mut self, model_config: P) -> Self {
+        self.config.attach_models(model_config);
+        self
+    }
+
+    pub fn camera_position(mut self, pos: [f32; 3]) -> Self {
+        self.config.camera.pos = pos;
+        self
+    }
+
+    // Radians, see the math conventions in the README
+    pub fn camera_rotation(mut self, yaw: f32, pitch: f32) -> Self {
+        self.config.camera.yaw = yaw;
+        self.config.camera.pitch = pitch;
+        self
+    }
+
+    pub fn camera(mut self, camera: CameraPose) -> Self {
+        self.config.camera = camera;
+        self
+    }
+
+    pub fn camera_controller(mut self, controller: CameraController) -> Self {
+        self.config.camera_controller = Some(controller);
+        self
+    }
+
+    pub fn background(mut self, background: Background) -> Self {
+        self.config.background = background;
+        self
+    }
+
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.config.tone_mapping = tone_mapping;
+        self
+    }
+
+    pub fn exposure(mut self, exposure: f32) -> Self {
+        self.config.exposure = exposure;
+        self
+    }
+
+    // Adds to the end of the post effect chain, so effects run in the order they're added
+    pub fn post_effect(mut self, effect: PostEffect) -> Self {
+        self.config.post_effects.push(effect);
+        self
+    }
+
+    pub fn render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.config.render_mode = render_mode;
+        self
+    }
+
+    pub fn aovs(mut self, enabled: bool) -> Self {
+        self.config.aovs = enabled;
+        self
+    }
+
+    pub fn max_catch_up_ticks(mut self, ticks: u32) -> Self {
+        self.config.max_catch_up_ticks = ticks;
+        self
+    }
+
+    pub fn interpolate_ticks(mut self, enabled: bool) -> Self {
+        self.config.interpolate_ticks = enabled;
+        self
+    }
+
+    pub fn record<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.config.record = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // The config as it stands, for LatrEngine::replay or checking it with validate
+    pub fn config(&self) -> &LatrConfig {
+        &self.config
+    }
+
+    pub fn build(self) -> Result<LatrEngine, LatrError> {
+        LatrEngine::new(self.config)
+    }
+
+    // Same as build, with an event loop that carries user events, see LatrEngine::with_user_events
+    pub fn build_with_user_events<U: Send + 'static>(self) -> Result<LatrEngine<U>, LatrError> {
+        LatrEngine::with_user_events(self.config)
+    }
+}
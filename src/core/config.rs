@@ -1,5 +1,11 @@
 use serde::Deserialize;
 
+use crate::core::config_file::ConfigFile;
+use crate::core::error::{ConfigError, LatrError};
+use crate::engine::background::Background;
+use crate::engine::camera::{CameraController, MAX_PITCH};
+use crate::engine::post::PostEffect;
+
 // Config that specifies all settings for running
 // Has default implemented so you can just select a few things
 // Also contains all the blueprints for different models that we will use
@@ -9,11 +15,68 @@ use serde::Deserialize;
 // Normally I would use unit type for that sort of thing, but since () doesn't have AsRef, we can't
 #[derive(Clone)]
 pub struct LatrConfig {
+    // Most frames drawn per second, 0 draws as fast as the present mode allows
     pub fps_cap: u32,
+
+    // Most ticks the physics loop runs back to back when it falls behind, ticks past this are dropped
+    // Keeps one slow tick from turning into a spiral of slower and slower catching up
+    pub max_catch_up_ticks: u32,
+
+    // Blends the camera and lights between physics ticks, so a low tick rate still moves smoothly
+    // This shows everything a tick late, turn it off if that latency matters more than smoothness
+    pub interpolate_ticks: bool,
+
+    // Records every pass of the physics loop to this file, for playing back later with LatrEngine::replay
+    pub record: Option<std::path::PathBuf>,
+
+    // Falls back to a supported mode if the surface doesn't have this one, see PresentMode
+    pub present_mode: PresentMode,
+
     pub model_file: Option<std::path::PathBuf>,
     pub resolution: (u32, u32),
     pub num_rays: (u32, u32),
     pub run_mode: RunMode,
+
+    // Whether the ray traced image stays at num_rays or follows the window size
+    pub render_resolution: RenderResolution,
+
+    // How the ray traced image is fit to the window when their shapes don't match
+    pub scale_mode: ScaleMode,
+
+    // Lowers the render resolution when frames take too long, off when None
+    pub dynamic_resolution: Option<DynamicResolution>,
+
+    // What rays that miss every model see, can be changed later with Engine::set_background
+    pub background: Background,
+
+    // How the hdr render gets squashed down to the screen, can be changed later with Engine::set_tone_mapping
+    pub tone_mapping: ToneMapping,
+
+    // Exposure in stops, each stop doubles the brightness, can be changed later with Engine::set_exposure
+    pub exposure: f32,
+
+    // Run in order between the trace and tone mapping, can be changed later with Engine::set_post_effects
+    pub post_effects: Vec<PostEffect>,
+
+    // Normal shading, or one of the debug views, can be changed later with Engine::set_render_mode or F3
+    pub render_mode: RenderMode,
+
+    // Also write albedo, normal, depth, position and instance id targets, for Engine::export_frame
+    // Off by default since they cost extra memory and bandwidth, can be changed later with Engine::set_aovs
+    pub aovs: bool,
+
+    // Which gpu to run on, see AdapterPreferences
+    pub adapter: AdapterPreferences,
+
+    // How much scene data the gpu buffers hold, they're sized once at startup so big scenes need this raised
+    pub scene_capacity: SceneCapacity,
+
+    // Where the camera starts, before PhysicsLoop::init runs
+    pub camera: CameraPose,
+
+    // Built in fly or orbit camera, None leaves the camera to the PhysicsLoop
+    // Can be changed later with Engine::set_camera_controller
+    pub camera_controller: Option<CameraController>,
 }
 
 impl LatrConfig {
@@ -21,6 +84,92 @@ impl LatrConfig {
         let path_buf_file = file_path.as_ref().to_path_buf();
         self.model_file = Some(path_buf_file);
     }
+
+    // Defaults, with whatever the file sets over them, then any LATR_ environment variables over that
+    // See the README for what the file can hold
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, LatrError> {
+        let path = path.as_ref();
+        let base_dir = path.parent().unwrap_or(std::path::Path::new("."));
+
+        let mut config = LatrConfig::default();
+        ConfigFile::load(path)?.apply(&mut config, base_dir);
+        config.apply_env_overrides()?;
+
+        Ok(config)
+    }
+
+    // Sets anything given by a LATR_ environment variable, like LATR_FPS_CAP=144 or LATR_RESOLUTION=1280x720
+    // from_file already does this, it's here for configs made in code
+    // Relative paths are from the working directory
+    // Variables that aren't valid UTF-8 can't be ours, so they're skipped rather than read with env::vars, which panics on them
+    pub fn apply_env_overrides(&mut self) -> Result<(), ConfigError> {
+        let vars = std::env::vars_os().filter_map(|(name, value)| {
+            let name = name.into_string().ok().filter(|name| name.starts_with("LATR_"))?;
+            Some((name, value.into_string().ok()?))
+        });
+
+        ConfigFile::from_env(vars)?.apply(self, std::path::Path::new("."));
+
+        Ok(())
+    }
+
+    // Checks everything that would otherwise fail deep inside wgpu or the engine thread
+    // LatrEngine::new runs this too, so it only needs calling by hand to check a config early
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.resolution.0 == 0 || self.resolution.1 == 0 {
+            return Err(ConfigError::ZeroResolution(self.resolution));
+        }
+
+        if self.num_rays.0 == 0 || self.num_rays.1 == 0 {
+            return Err(ConfigError::ZeroNumRays(self.num_rays));
+        }
+
+        if let RenderResolution::WindowScale(scale) = self.render_resolution
+            && !(scale.is_finite() && scale > 0.0) {
+            return Err(ConfigError::InvalidRenderScale(scale));
+        }
+
+        if let Some(dynamic) = self.dynamic_resolution {
+            let scales_valid = dynamic.min_scale.is_finite() && dynamic.max_scale.is_finite()
+                && dynamic.min_scale > 0.0 && dynamic.min_scale <= dynamic.max_scale;
+
+            if !scales_valid {
+                return Err(ConfigError::InvalidDynamicScales(dynamic.min_scale, dynamic.max_scale));
+            }
+
+            if dynamic.target_frame_time.is_zero() {
+                return Err(ConfigError::ZeroTargetFrameTime);
+            }
+        }
+
+        if self.max_catch_up_ticks == 0 {
+            return Err(ConfigError::ZeroCatchUpTicks);
+        }
+
+        if let Some(buffer) = self.scene_capacity.first_zero() {
+            return Err(ConfigError::ZeroSceneCapacity(buffer));
+        }
+
+        if !self.exposure.is_finite() {
+            return Err(ConfigError::InvalidExposure(self.exposure));
+        }
+
+        let camera = self.camera;
+        if !(camera.pos.iter().all(|v| v.is_finite()) && camera.yaw.is_finite() && camera.pitch.is_finite()) {
+            return Err(ConfigError::InvalidCamera);
+        }
+
+        if let Some(model_file) = &self.model_file
+            && !model_file.is_file() {
+            return Err(ConfigError::ModelFileNotFound(model_file.clone()));
+        }
+
+        if self.run_mode != RunMode::Gui {
+            return Err(ConfigError::UnsupportedRunMode(self.run_mode.clone()));
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for LatrConfig {
@@ -30,17 +179,35 @@ impl Default for LatrConfig {
 
         Self {
             fps_cap: 60,
+            max_catch_up_ticks: 5,
+            interpolate_ticks: true,
+            record: None,
+            present_mode: PresentMode::default(),
             resolution,
             num_rays,
             run_mode: RunMode::default(),
+            render_resolution: RenderResolution::default(),
+            scale_mode: ScaleMode::default(),
+            dynamic_resolution: None,
             model_file: None,
+            background: Background::default(),
+            tone_mapping: ToneMapping::default(),
+            exposure: 0.0,
+            render_mode: RenderMode::default(),
+            aovs: false,
+            adapter: AdapterPreferences::default(),
+            scene_capacity: SceneCapacity::default(),
+            post_effects: Vec::new(),
+            camera: CameraPose::default(),
+            camera_controller: None,
         }
     }
 }
 
 
 // This is an enum so more can be added later
-#[derive(Debug, PartialEq, Default, Clone)]
+#[derive(Debug, PartialEq, Default, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RunMode {
     #[default] // Gui is now default
     // Gui is the normal run mode with full features and screen
@@ -52,12 +219,351 @@ pub enum RunMode {
     NoWinit,
 }
 
+// Which gpu we run on, LatrEngine::adapters lists what's there to pick from
+// Out of the adapters that fit, the power preference decides, and no fit at all is a GpuError listing what was found
+#[derive(Debug, PartialEq, Eq, Default, Clone)]
+pub struct AdapterPreferences {
+    // Graphics apis to look on, empty looks on every one wgpu was built with
+    pub backends: Vec<Backend>,
+
+    pub power_preference: PowerPreference,
+
+    // Only adapters with this in their name, ignoring case, like "nvidia" or "llvmpipe"
+    pub name: Option<String>,
+
+    // Only software adapters, like lavapipe or llvmpipe, for machines without a gpu
+    pub force_fallback: bool,
+}
+
+impl AdapterPreferences {
+    pub(crate) fn wgpu_backends(&self) -> wgpu::Backends {
+        if self.backends.is_empty() {
+            return wgpu::Backends::all();
+        }
+
+        self.backends.iter().fold(wgpu::Backends::empty(), |backends, backend| backends | backend.to_wgpu())
+    }
+
+    pub(crate) fn matches(&self, info: &wgpu::AdapterInfo) -> bool {
+        let name_matches = self.name.as_ref()
+            .is_none_or(|name| info.name.to_lowercase().contains(&name.to_lowercase()));
+
+        let fallback_matches = !self.force_fallback || info.device_type == wgpu::DeviceType::Cpu;
+
+        name_matches && fallback_matches
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+
+    // OpenGL, or OpenGL ES through EGL
+    Gl,
+}
+
+impl Backend {
+    pub(crate) fn to_wgpu(self) -> wgpu::Backends {
+        match self {
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerPreference {
+    #[default]
+    // Usually the discrete gpu on machines with two
+    HighPerformance,
+
+    // Usually the integrated gpu, easier on laptop batteries
+    LowPower,
+}
+
+impl PowerPreference {
+    // Lower is picked first, software adapters always go last so they're only used when nothing else fits
+    pub(crate) fn rank(self, device_type: wgpu::DeviceType) -> u32 {
+        match (self, device_type) {
+            (PowerPreference::HighPerformance, wgpu::DeviceType::DiscreteGpu) => 0,
+            (PowerPreference::HighPerformance, wgpu::DeviceType::IntegratedGpu) => 1,
+            (PowerPreference::LowPower, wgpu::DeviceType::IntegratedGpu) => 0,
+            (PowerPreference::LowPower, wgpu::DeviceType::DiscreteGpu) => 1,
+            (_, wgpu::DeviceType::VirtualGpu) => 2,
+            (_, wgpu::DeviceType::Other) => 3,
+            (_, wgpu::DeviceType::Cpu) => 4,
+        }
+    }
+}
+
+// Number of elements each scene buffer is made with, anything loaded past these fails with EngineError::BufferFull
+// The device asks the adapter for bigger buffer limits when the defaults can't fit these, see request_device
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SceneCapacity {
+    pub triangles: u64,
+    pub vertices: u64,
+    pub blas_nodes: u64,
+    pub instances: u64,
+
+    // Emissive triangles, see collect_area_lights
+    pub area_lights: u64,
+}
+
+impl SceneCapacity {
+    // Room for this many triangles loaded as a soup, which is how build_blas uploads them
+    // Every triangle gets its own 3 vertices, and the bvh never has more nodes than twice the triangles
+    // Counts too big to multiply out stop at u64::MAX, which the device limits check then turns down
+    pub fn for_triangles(triangles: u64) -> Self {
+        Self { triangles, vertices: triangles.saturating_mul(3), blas_nodes: triangles.saturating_mul(2), ..Default::default() }
+    }
+
+    fn first_zero(&self) -> Option<&'static str> {
+        [
+            ("triangles", self.triangles),
+            ("vertices", self.vertices),
+            ("blas_nodes", self.blas_nodes),
+            ("instances", self.instances),
+            ("area_lights", self.area_lights),
+        ]
+        .into_iter()
+        .find(|(_, count)| *count == 0)
+        .map(|(name, _)| name)
+    }
+}
+
+impl Default for SceneCapacity {
+    fn default() -> Self {
+        Self {
+            triangles: 1 << 16,
+            vertices: 1 << 16,
+            blas_nodes: 1 << 17,
+            instances: 1 << 12,
+            area_lights: 1 << 14,
+        }
+    }
+}
+
+// Position and rotation of the camera, rotations are in radians, see the math conventions in the README
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CameraPose {
+    pub pos: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Default for CameraPose {
+    fn default() -> Self {
+        // Looking down +z, same as GpuUniformCamera
+        Self { pos: [0.0; 3], yaw: -std::f32::consts::FRAC_PI_2, pitch: 0.0 }
+    }
+}
+
+impl CameraPose {
+    pub(crate) fn clamped_pitch(self) -> f32 {
+        self.pitch.clamp(-MAX_PITCH, MAX_PITCH)
+    }
+}
+
+// How finished frames are handed to the screen
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    #[default]
+    // Waits for vsync, no tearing, supported everywhere
+    Fifo,
+
+    // Replaces the queued frame with the newest one, no tearing but without vsync's wait
+    // Falls back to Fifo when unsupported
+    Mailbox,
+
+    // Presents right away, lowest latency but can tear
+    // Falls back to Mailbox, then Fifo when unsupported
+    Immediate,
+}
+
+impl PresentMode {
+    // The mode we asked for followed by what to try if it isn't supported
+    pub(crate) fn preference_order(self) -> &'static [wgpu::PresentMode] {
+        match self {
+            PresentMode::Fifo => &[wgpu::PresentMode::Fifo],
+            PresentMode::Mailbox => &[wgpu::PresentMode::Mailbox, wgpu::PresentMode::Fifo],
+            PresentMode::Immediate => &[wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox, wgpu::PresentMode::Fifo],
+        }
+    }
+}
+
+// Size of the texture the rays are traced into, which can differ from the window
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RenderResolution {
+    // Always traces num_rays, no matter how the window is resized
+    Fixed,
+
+    // Follows the window, scaled by the factor, so 0.5 traces a quarter of the pixels
+    WindowScale(f32),
+}
+
+impl RenderResolution {
+    pub(crate) fn render_size(self, num_rays: (u32, u32), window_size: (u32, u32)) -> (u32, u32) {
+        let (width, height) = match self {
+            RenderResolution::Fixed => num_rays,
+            RenderResolution::WindowScale(scale) => (
+                (window_size.0 as f32 * scale).round() as u32,
+                (window_size.1 as f32 * scale).round() as u32,
+            ),
+        };
+
+        // Textures can't be empty
+        (width.max(1), height.max(1))
+    }
+}
+
+impl Default for RenderResolution {
+    fn default() -> Self {
+        RenderResolution::WindowScale(1.0)
+    }
+}
+
+// Scales the render resolution up and down to hold a frame time
+// Scales multiply the size from RenderResolution, so 0.5 traces a quarter of the pixels
+// Frame time is the gpu time of the ray tracing pass when the device has timestamp queries
+// Otherwise it's the time between frames, so the target should be longer than the fps cap or vsync allows
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DynamicResolution {
+    pub target_frame_time: std::time::Duration,
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+impl Default for DynamicResolution {
+    fn default() -> Self {
+        Self {
+            target_frame_time: std::time::Duration::from_secs_f64(1.0 / 60.0),
+            min_scale: 0.5,
+            max_scale: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleMode {
+    #[default]
+    // Keeps the aspect ratio, filling the leftover space with black bars
+    Letterbox,
+
+    // Fills the whole window, squashing the image if the aspect ratios differ
+    Stretch,
+}
+
+impl ScaleMode {
+    // Area of the surface the image gets drawn into, as x, y, width, height in pixels
+    pub(crate) fn viewport(self, render_size: (u32, u32), surface_size: (u32, u32)) -> [f32; 4] {
+        let (surface_w, surface_h) = (surface_size.0 as f32, surface_size.1 as f32);
+
+        match self {
+            ScaleMode::Stretch => [0.0, 0.0, surface_w, surface_h],
+            ScaleMode::Letterbox => {
+                let scale = (surface_w / render_size.0 as f32).min(surface_h / render_size.1 as f32);
+                let (w, h) = (render_size.0 as f32 * scale, render_size.1 as f32 * scale);
+
+                [(surface_w - w) * 0.5, (surface_h - h) * 0.5, w, h]
+            },
+        }
+    }
+}
+
+// Operators for mapping hdr color down into the 0-1 range of the screen
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
+pub enum ToneMapping {
+    // Just clamps, anything brighter than 1 gets cut off
+    None,
+
+    Reinhard,
+
+    // Filmic curve, punchy contrast and saturation
+    Aces,
+
+    #[default]
+    // Filmic curve that desaturates highlights the way film does, avoiding the hue shifts of Aces
+    AgX,
+}
+
+impl ToneMapping {
+    // Has to match the TONEMAP_ constants in blit.wgsl
+    pub(crate) fn to_gpu(self) -> u32 {
+        match self {
+            ToneMapping::None => 0,
+            ToneMapping::Reinhard => 1,
+            ToneMapping::Aces => 2,
+            ToneMapping::AgX => 3,
+        }
+    }
+}
+
+// What the raytracer writes to the screen
+// Everything besides Shaded is a debug view, and skips tone mapping so the colors come out exact
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub enum RenderMode {
+    #[default]
+    Shaded,
+
+    // World space normal, mapped from -1..1 into 0..1
+    Normals,
+
+    // Distance along the camera ray, bright is close
+    Depth,
+
+    // u, v and 1 - u - v of the hit as green, blue and red
+    Barycentrics,
+
+    // Random color per triangle and per instance
+    TriangleId,
+    InstanceId,
+
+    // Heatmaps of how many bvh nodes were visited and how many boxes were tested for each pixel
+    TraversalSteps,
+    BoxTests,
+}
+
+impl RenderMode {
+    const ALL: [RenderMode; 8] = [
+        RenderMode::Shaded,
+        RenderMode::Normals,
+        RenderMode::Depth,
+        RenderMode::Barycentrics,
+        RenderMode::TriangleId,
+        RenderMode::InstanceId,
+        RenderMode::TraversalSteps,
+        RenderMode::BoxTests,
+    ];
+
+    // Has to match the MODE_ constants in raytrace.wgsl
+    pub(crate) fn to_gpu(self) -> u32 {
+        Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0) as u32
+    }
+
+    // Used to cycle through the modes with a hotkey, wrapping back around to Shaded
+    pub fn next(self) -> Self {
+        Self::ALL[(self.to_gpu() as usize + 1) % Self::ALL.len()]
+    }
+}
+
 // All this code is here as it technically relates to a config, even though its used on engine
 // It's a bit iffy, but I want to pad the size of this file a bit, 40 lines is too small
 #[derive(Deserialize, Debug)]
 pub struct ExplicitModelConfig {
     pub name: String,
     pub path: String,
+
+    // Radiance the model gives off, makes every triangle of the model an area light
+    #[serde(default)]
+    pub emission: Option<[f32; 3]>,
 }
 
 // We use serde default and an Option here so that the user can leave fields blank
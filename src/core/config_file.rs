@@ -0,0 +1,179 @@
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::core::config::{Backend, LatrConfig, PowerPreference, PresentMode, RenderResolution, RunMode, ScaleMode};
+use crate::core::error::ConfigError;
+
+// End of imports
+
+// Schema of the file LatrConfig::from_file reads, see the README for an example
+// Every field can be left out, in which case the default stays
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConfigFile {
+    pub(crate) resolution: Option<[u32; 2]>,
+    pub(crate) num_rays: Option<[u32; 2]>,
+
+    // Follows the window at this scale, or "fixed" to always trace num_rays
+    pub(crate) render_scale: Option<RenderScale>,
+    pub(crate) scale_mode: Option<ScaleMode>,
+
+    pub(crate) fps_cap: Option<u32>,
+    pub(crate) present_mode: Option<PresentMode>,
+    pub(crate) run_mode: Option<RunMode>,
+
+    // Relative paths are from the folder the config file is in, like the model folders in a model config
+    pub(crate) model_config: Option<PathBuf>,
+
+    pub(crate) exposure: Option<f32>,
+    pub(crate) aovs: Option<bool>,
+    pub(crate) max_catch_up_ticks: Option<u32>,
+    pub(crate) interpolate_ticks: Option<bool>,
+
+    pub(crate) adapter: Option<AdapterFile>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AdapterFile {
+    pub(crate) backends: Option<Vec<Backend>>,
+    pub(crate) power_preference: Option<PowerPreference>,
+    pub(crate) name: Option<String>,
+    pub(crate) force_fallback: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(untagged)]
+pub(crate) enum RenderScale {
+    Scale(f32),
+    Fixed(FixedScale),
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FixedScale {
+    Fixed,
+}
+
+impl ConfigFile {
+    pub(crate) fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+
+        toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    // Reads every LATR_ variable, named after the field in capitals, like LATR_FPS_CAP or LATR_ADAPTER_POWER_PREFERENCE
+    // Values are written like they would be in the file, strings don't need quotes and sizes can also be 1280x720
+    pub(crate) fn from_env<I: IntoIterator<Item = (String, String)>>(vars: I) -> Result<Self, ConfigError> {
+        let mut file = ConfigFile::default();
+
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix("LATR_") else {
+                continue;
+            };
+
+            match key {
+                "RESOLUTION" => file.resolution = Some(parse_size(&name, &value)?),
+                "NUM_RAYS" => file.num_rays = Some(parse_size(&name, &value)?),
+                "RENDER_SCALE" => file.render_scale = Some(parse_value(&name, &value)?),
+                "SCALE_MODE" => file.scale_mode = Some(parse_value(&name, &value)?),
+                "FPS_CAP" => file.fps_cap = Some(parse_value(&name, &value)?),
+                "PRESENT_MODE" => file.present_mode = Some(parse_value(&name, &value)?),
+                "RUN_MODE" => file.run_mode = Some(parse_value(&name, &value)?),
+                "MODEL_CONFIG" => file.model_config = Some(PathBuf::from(value)),
+                "EXPOSURE" => file.exposure = Some(parse_value(&name, &value)?),
+                "AOVS" => file.aovs = Some(parse_value(&name, &value)?),
+                "MAX_CATCH_UP_TICKS" => file.max_catch_up_ticks = Some(parse_value(&name, &value)?),
+                "INTERPOLATE_TICKS" => file.interpolate_ticks = Some(parse_value(&name, &value)?),
+                "ADAPTER_BACKENDS" => file.adapter.get_or_insert_default().backends = Some(parse_list(&name, &value)?),
+                "ADAPTER_POWER_PREFERENCE" => {
+                    file.adapter.get_or_insert_default().power_preference = Some(parse_value(&name, &value)?);
+                },
+                "ADAPTER_NAME" => file.adapter.get_or_insert_default().name = Some(value),
+                "ADAPTER_FORCE_FALLBACK" => file.adapter.get_or_insert_default().force_fallback = Some(parse_value(&name, &value)?),
+
+                // A typo would otherwise be ignored without a word
+                _ => return Err(ConfigError::UnknownEnvVar(name)),
+            }
+        }
+
+        Ok(file)
+    }
+
+    // Writes every field that was set over the config, base_dir is where relative paths start from
+    pub(crate) fn apply(self, config: &mut LatrConfig, base_dir: &Path) {
+        if let Some([width, height]) = self.resolution {
+            config.resolution = (width, height);
+        }
+
+        if let Some([width, height]) = self.num_rays {
+            config.num_rays = (width, height);
+        }
+
+        match self.render_scale {
+            Some(RenderScale::Scale(scale)) => config.render_resolution = RenderResolution::WindowScale(scale),
+            Some(RenderScale::Fixed(_)) => config.render_resolution = RenderResolution::Fixed,
+            None => {},
+        }
+
+        if let Some(model_config) = self.model_config {
+            config.model_file = Some(base_dir.join(model_config));
+        }
+
+        if let Some(adapter) = self.adapter {
+            config.adapter.backends = adapter.backends.unwrap_or(config.adapter.backends.clone());
+            config.adapter.power_preference = adapter.power_preference.unwrap_or(config.adapter.power_preference);
+            config.adapter.name = adapter.name.or(config.adapter.name.clone());
+            config.adapter.force_fallback = adapter.force_fallback.unwrap_or(config.adapter.force_fallback);
+        }
+
+        config.scale_mode = self.scale_mode.unwrap_or(config.scale_mode);
+        config.fps_cap = self.fps_cap.unwrap_or(config.fps_cap);
+        config.present_mode = self.present_mode.unwrap_or(config.present_mode);
+        config.run_mode = self.run_mode.unwrap_or(config.run_mode.clone());
+        config.exposure = self.exposure.unwrap_or(config.exposure);
+        config.aovs = self.aovs.unwrap_or(config.aovs);
+        config.max_catch_up_ticks = self.max_catch_up_ticks.unwrap_or(config.max_catch_up_ticks);
+        config.interpolate_ticks = self.interpolate_ticks.unwrap_or(config.interpolate_ticks);
+    }
+}
+
+// Parses an environment value as if it were written after "value = " in the file
+// Anything that doesn't parse that way is tried again as a string, so names like mailbox don't need quotes
+fn parse_value<T: DeserializeOwned>(name: &str, value: &str) -> Result<T, ConfigError> {
+    #[derive(Deserialize)]
+    struct Wrapper<T> {
+        value: T,
+    }
+
+    let as_written = toml::from_str::<Wrapper<T>>(&format!("value = {value}"));
+    let as_string = || toml::from_str::<Wrapper<T>>(&format!("value = {}", toml::Value::String(value.to_string())));
+
+    as_written
+        .or_else(|_| as_string())
+        .map(|wrapper| wrapper.value)
+        .map_err(|_| ConfigError::InvalidEnvVar(name.to_string(), value.to_string()))
+}
+
+// Lists can be vulkan,gl as well as ["vulkan", "gl"]
+fn parse_list<T: DeserializeOwned>(name: &str, value: &str) -> Result<Vec<T>, ConfigError> {
+    if value.trim_start().starts_with('[') {
+        return parse_value(name, value);
+    }
+
+    value.split(',')
+        .map(|item| parse_value(name, item.trim()))
+        .collect()
+}
+
+// Sizes can be 1280x720 as well as [1280, 720]
+fn parse_size(name: &str, value: &str) -> Result<[u32; 2], ConfigError> {
+    if let Some((width, height)) = value.split_once('x')
+        && let (Ok(width), Ok(height)) = (width.trim().parse(), height.trim().parse()) {
+        return Ok([width, height]);
+    }
+
+    parse_value(name, value)
+}
@@ -11,6 +11,9 @@ pub enum LatrError {
 
     #[error("The Engine ran into an error: {0}")]
     Engine(#[from] EngineError),
+
+    #[error("Invalid config: {0}")]
+    Config(#[from] ConfigError),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -23,6 +26,62 @@ pub enum WindowError {
 
     #[error("Event loop exited for an unknown reason")]
     EventLoopExited,
+
+    #[error("LatrEngine::start needs its own event loop, use start_physics and frame with a window from with_window")]
+    HostOwnsEventLoop,
+}
+
+// Problems with a LatrConfig, caught before anything gets created, see LatrConfig::validate
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Window resolution has to be at least 1x1, got {}x{}", .0.0, .0.1)]
+    ZeroResolution((u32, u32)),
+
+    #[error("num_rays has to be at least 1x1, got {}x{}", .0.0, .0.1)]
+    ZeroNumRays((u32, u32)),
+
+    #[error("Render scale has to be a positive number, got {0}")]
+    InvalidRenderScale(f32),
+
+    #[error("Dynamic resolution scales have to be positive with min_scale at most max_scale, got {0} to {1}")]
+    InvalidDynamicScales(f32, f32),
+
+    #[error("Dynamic resolution target frame time can't be zero")]
+    ZeroTargetFrameTime,
+
+    #[error("max_catch_up_ticks has to be at least 1")]
+    ZeroCatchUpTicks,
+
+    #[error("Exposure has to be a finite number, got {0}")]
+    InvalidExposure(f32),
+
+    #[error("Camera position and rotation have to be finite numbers")]
+    InvalidCamera,
+
+    #[error("scene_capacity.{0} has to be at least 1")]
+    ZeroSceneCapacity(&'static str),
+
+    #[error("Model config file not found at {0}")]
+    ModelFileNotFound(PathBuf),
+
+    #[error("Run mode {0:?} isn't supported yet, use LatrEngine::replay to run without a window")]
+    UnsupportedRunMode(crate::core::config::RunMode),
+
+    #[error("Tick rate has to be at least 1 tick per second")]
+    ZeroTickRate,
+
+    // Config file and environment errors
+    #[error("Failed to read config file at {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+
+    #[error("Config file at {0} has invalid data: {1}")]
+    Parse(PathBuf, toml::de::Error),
+
+    #[error("Unknown config environment variable {0}")]
+    UnknownEnvVar(String),
+
+    #[error("Environment variable {0} has a value that doesn't fit the setting: {1}")]
+    InvalidEnvVar(String, String),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -37,6 +96,9 @@ pub enum EngineError {
     #[error("Invalid directory passed in model config. Directory: {0}")]
     InvalidDirectory(PathBuf),
 
+    #[error("Model file in model config has an unsupported format. Path: {0}")]
+    UnsupportedModelFormat(PathBuf),
+
     #[error("Model config file has invalid data. Path: {0}")]
     ModelConfigInvalidData(PathBuf),
 
@@ -51,6 +113,47 @@ pub enum EngineError {
 
     #[error("Tri file parse error. Floats in .tri file are not in a multiple of 9. Each triangle should be nine floats.")]
     TriFileFloatNum,
+
+    // Scene data errors
+    #[error("Scene data does not fit in the {0} buffer")]
+    BufferFull(&'static str),
+
+    #[error("No light exists with id {0}, it may have already been removed")]
+    LightNotFound(u32),
+
+    #[error("Failed to load environment map at {0}: {1}")]
+    EnvironmentMap(PathBuf, image::ImageError),
+
+    #[error("Failed to read LUT at {0}: {1}")]
+    LutRead(PathBuf, std::io::Error),
+
+    #[error("LUT at {0} has invalid data: {1}")]
+    LutParse(PathBuf, String),
+
+    #[error("Post effect {0} has {1} params, the most it can have is 16")]
+    TooManyPostParams(String, usize),
+
+    #[error("No post effect at index {0}, the chain is shorter than that")]
+    NoPostEffect(usize),
+
+    #[error("Render thread is no longer receiving commands")]
+    RenderThreadClosed,
+
+    #[error("Physics thread panicked")]
+    PhysicsThreadPanicked,
+
+    #[error("Failed to export frame to {0}: {1}")]
+    FrameExport(PathBuf, exr::error::Error),
+
+    // Record and replay errors
+    #[error("Failed to read or write the recording at {0}: {1}")]
+    Recording(PathBuf, std::io::Error),
+
+    #[error("Recording at {0} has invalid data: {1}")]
+    RecordingParse(PathBuf, toml::de::Error),
+
+    #[error("Failed to write a pass to the recording: {0}")]
+    RecordingWrite(#[from] toml::ser::Error),
 }
 
 impl EngineError {
@@ -66,6 +169,9 @@ pub enum GpuError {
     #[error("Failed to find suitable adapter during initialization")]
     AdapterNotFound(#[from] wgpu::RequestAdapterError),
 
+    #[error("No adapter matched {0}. Adapters found: {1}")]
+    NoMatchingAdapter(String, String),
+
     #[error("Failed to create surface during initialization: {0}")]
     SurfaceError(#[from] wgpu::CreateSurfaceError),
 
@@ -74,7 +180,35 @@ pub enum GpuError {
 
     #[error("Failed to find any supported alpha modes on adapter")]
     NoSupportedAlphaModes,
+
+    #[error("The {0} buffer needs {2} bytes, over the device's {1} of {3}, lower scene_capacity or pick another adapter")]
+    BufferLimitExceeded(&'static str, &'static str, u64, u64),
     
     #[error("Failed during encoding and submitting process")]
     EncoderError(#[from] wgpu::SurfaceError),
+
+    #[error("Ran out of gpu memory while getting the next frame from the surface")]
+    SurfaceOutOfMemory,
+
+    #[error("Post effect {0} failed to build: {1}")]
+    PostEffect(String, String),
+
+    #[error("Invalid render graph: {0}")]
+    RenderGraph(String),
+
+    // Readback errors
+    #[error("Failed to map a readback buffer: {0}")]
+    Readback(#[from] wgpu::BufferAsyncError),
+
+    #[error("Failed while waiting on the gpu: {0}")]
+    Poll(#[from] wgpu::PollError),
+
+    #[error("Readback buffer was dropped before it finished mapping")]
+    ReadbackDropped,
+
+    #[error("The frame to export was skipped, the window was minimized or the surface had no frame to draw to")]
+    ExportFrameSkipped,
+
+    #[error("Can't read back textures with format {0:?}")]
+    UnsupportedReadbackFormat(wgpu::TextureFormat),
 }
\ No newline at end of file
@@ -3,134 +3,326 @@ use crate::{
         blas::build_blas,
         engine_core::Engine,
     },
-    gpu::gpu_core::GpuCore,
+    gpu::{buffers::GpuBuffers, gpu_core::GpuCore},
     PhysicsLoop
 };
 
-use winit::event_loop::EventLoopWindowTarget;
+use winit::event_loop::{ControlFlow, EventLoopWindowTarget};
+use winit::keyboard::{KeyCode, PhysicalKey};
 
 use std::{
     cell::RefCell,
     rc::Rc,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
     thread,
+    time::Instant,
 };
 use crate::core::config::LatrConfig;
-use crate::core::error::{EngineError, LatrError, WindowError};
+use crate::core::frame_pacer::FramePacer;
+use crate::core::error::{EngineError, GpuError, LatrError, WindowError};
+use crate::gpu::render_commands::RenderCommand;
+use crate::engine::input::{self, InputEvent};
 
-pub fn run_event_loop<T: PhysicsLoop + 'static + std::marker::Send>(
-    config: LatrConfig,
-    engine_core: Engine,
-    gpu_core: GpuCore,
-    window: Arc<winit::window::Window>,
-    event_loop: winit::event_loop::EventLoop<()>,
-    state_tps_op: Option<(T, u32)>,
-) -> Result<(), LatrError> {
-    let mut gpu_core = gpu_core;
+const DEBUG_VIEW_KEY: KeyCode = KeyCode::F3;
 
-    // Since event_loop.run returns an event loop err, to get a LatrErr, we need to store it somewhere
-    // We use an Rc RefCell to be able to update the error if it occurs
-    let gpu_err: Rc<RefCell<Option<LatrError>>> = Rc::new(RefCell::new(None));
-    let gpu_err_clone = gpu_err.clone();
+// Channels between the render thread and the engine, kept together so they're passed around as one
+pub(crate) struct RenderChannels {
+    // Set when either side wants everything to stop, see Engine::request_exit
+    pub(crate) exit_requested: Arc<AtomicBool>,
 
-    // Now we spawn the secondary thread
-    // This thread will first build the bvh, then run the engine
-    // We also clone our buffers as to let both threads update them
-    // Wgpu handles this very helpfully and treats a wgpu::Buffer as atomically ref counted (At least I believe so)
-    let secondary_buffers = gpu_core.buffers.clone();
-    let secondary_queue = gpu_core.queue.clone();
-    let secondary_device = gpu_core.device.clone();
+    // Changes the engine wants made to render thread state, drained before each frame
+    pub(crate) render_commands: mpsc::Receiver<RenderCommand>,
 
-    let secondary_config = config.clone();
+    // Window input going over to the engine, see InputState
+    pub(crate) input_events: mpsc::Sender<InputEvent>,
+}
 
-    let secondary_thread = thread::spawn(move || {
-
-        // Move these explicitly
-        let mut secondary_buffers = secondary_buffers;
-        let secondary_queue = secondary_queue;
-        let secondary_device = secondary_device;
+// Render side of a window, the part of the event loop that doesn't care who owns the loop
+// run_event_loop drives one of these, and so does a LatrEngine embedded in someone else's loop, see LatrEngine::handle_event
+pub(crate) struct WindowRenderer {
+    pub(crate) gpu_core: GpuCore,
+    window: Arc<winit::window::Window>,
+    frame_pacer: FramePacer,
 
-        let secondary_config = secondary_config;
-        let mut engine = engine_core;
-        
-        let state_tps_op = state_tps_op;
+    render_commands: mpsc::Receiver<RenderCommand>,
+    input_events: mpsc::Sender<InputEvent>,
+    exit_requested: Arc<AtomicBool>,
+}
 
-        // We store a res to return if any programs run into an error
-        let mut bvh_res: Result<(), EngineError> = Ok(());
-        let mut engine_res: Result<(), LatrError> = Ok(());
+impl WindowRenderer {
+    pub(crate) fn new(gpu_core: GpuCore, window: Arc<winit::window::Window>, channels: RenderChannels, fps_cap: u32) -> Self {
+        let RenderChannels { render_commands, input_events, exit_requested } = channels;
 
-        // Run our first bvh task
-        match secondary_config.model_file {
-            Some(model_file) => bvh_res = build_blas(model_file, &mut secondary_buffers, &secondary_queue, &secondary_device),
-            None => (),
+        Self {
+            gpu_core,
+            window,
+            frame_pacer: FramePacer::new(fps_cap, Instant::now()),
+            render_commands, input_events, exit_requested,
         }
+    }
 
-        match bvh_res {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("{}", e);
-                return ();
-            },
-        }
+    pub(crate) fn window(&self) -> &Arc<winit::window::Window> {
+        &self.window
+    }
+
+    // Set by Engine::request_exit, closing the window, or when the physics thread ran into an error
+    pub(crate) fn exit_requested(&self) -> bool {
+        self.exit_requested.load(Ordering::Acquire)
+    }
 
-        // After that is done, we run our engine
-        println!("Started running engine");
+    pub(crate) fn request_exit(&self) {
+        self.exit_requested.store(true, Ordering::Release);
+    }
 
-        match state_tps_op {
-            Some((state, tps)) => {
-                engine_res = engine.start_physics_loop(state, tps, &mut secondary_buffers, &secondary_queue);
-            },
-            None => (),
-        }
+    pub(crate) fn exit_flag(&self) -> Arc<AtomicBool> {
+        self.exit_requested.clone()
+    }
+
+    // Lets go of everything the physics thread could be blocked on, so it can be joined
+    // Commands that were never applied are dropped along with the receiver, exports waiting in them included
+    pub(crate) fn release_engine(&mut self) {
+        self.gpu_core.cancel_exports();
+        self.render_commands = mpsc::channel().1;
+    }
 
-        match engine_res {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("{}", e);
-                return ();
-            },
+    // Passes input on to the engine and handles resizing, closing and the debug view key
+    // Events for other windows are ignored, and nothing gets drawn here, see frame
+    // A gpu error asks the physics thread to stop, same as in frame
+    pub(crate) fn handle_event<E>(&mut self, event: &winit::event::Event<E>) -> Result<(), GpuError> {
+        let res = self.apply_window_event(event);
+
+        if res.is_err() {
+            self.request_exit();
         }
 
-    });
+        res
+    }
 
-    let render_res = event_loop.run(move |event, elwt: &EventLoopWindowTarget<()>| {
+    fn apply_window_event<E>(&mut self, event: &winit::event::Event<E>) -> Result<(), GpuError> {
         match event {
             winit::event::Event::WindowEvent { window_id, event }
-            if window_id == window.id() => {
+            if *window_id == self.window.id() => {
+
+                // The physics thread may have already stopped, in which case nobody is listening
+                if let Some(input_event) = input::from_window_event(event) {
+                    let _ = self.input_events.send(input_event);
+                }
+
                 match event {
                     winit::event::WindowEvent::CloseRequested => {
                         println!("Close button was pressed - Exiting.");
-                        elwt.exit();
+                        self.request_exit();
                     }
 
-                    winit::event::WindowEvent::RedrawRequested => {
+                    winit::event::WindowEvent::Resized(size) => {
+                        self.gpu_core.resize(size.width, size.height)?;
+                    }
 
-                        match gpu_core.render() {
-                            Ok(_) => {},
-                            Err(e) => {
-                                *gpu_err_clone.borrow_mut() = Some(LatrError::Gpu(e));
-                                elwt.exit();
-                            }
-                        };
+                    // F3 cycles through the debug views
+                    winit::event::WindowEvent::KeyboardInput { event, .. }
+                    if event.physical_key == PhysicalKey::Code(DEBUG_VIEW_KEY)
+                        && event.state == winit::event::ElementState::Pressed
+                        && !event.repeat => {
 
-                        window.request_redraw();
+                        let render_mode = self.gpu_core.render_mode().next();
+                        self.gpu_core.set_render_mode(render_mode)?;
                     }
 
                     _ => ()
                 }
             }
+
+            // Device events come in even when another window has focus, so we filter those out
+            winit::event::Event::DeviceEvent { event: winit::event::DeviceEvent::MouseMotion { delta }, .. }
+            if self.window.has_focus() => {
+                let _ = self.input_events.send(InputEvent::MouseMotion { dx: delta.0 as f32, dy: delta.1 as f32 });
+            }
+
+            _ => ()
+        }
+
+        Ok(())
+    }
+
+    // Whether LatrConfig::fps_cap allows drawing another frame yet
+    pub(crate) fn frame_due(&self) -> bool {
+        self.frame_pacer.frame_due(Instant::now())
+    }
+
+    // When the next frame is due, None when uncapped
+    pub(crate) fn next_frame(&self) -> Option<Instant> {
+        self.frame_pacer.next_frame()
+    }
+
+    // Applies anything the engine sent since the last frame, then draws
+    // A gpu error also asks the physics thread to stop, since nothing will be drawn after it
+    pub(crate) fn frame(&mut self) -> Result<(), GpuError> {
+        self.frame_pacer.frame_started(Instant::now());
+
+        let res = self.render_commands
+            .try_iter()
+            .try_for_each(|command| self.gpu_core.apply_command(command))
+            .and_then(|_| self.gpu_core.render());
+
+        if res.is_err() {
+            self.request_exit();
+        }
+
+        res
+    }
+}
+
+// Set with LatrEngine::on_user_event, gets the window so it can ask for a redraw
+pub(crate) type UserEventCallback<U> = Box<dyn FnMut(U, &winit::window::Window)>;
+
+pub fn run_event_loop<T: PhysicsLoop + 'static + std::marker::Send, U: 'static>(
+    config: LatrConfig,
+    engine_core: Engine,
+    renderer: &mut WindowRenderer,
+    event_loop: winit::event_loop::EventLoop<U>,
+    mut on_user_event: Option<UserEventCallback<U>>,
+    state_tps_op: Option<(T, u32)>,
+) -> Result<(), LatrError> {
+    // Since event_loop.run returns an event loop err, to get a LatrErr, we need to store it somewhere
+    // We use an Rc RefCell to be able to update the error if it occurs
+    let gpu_err: Rc<RefCell<Option<LatrError>>> = Rc::new(RefCell::new(None));
+    let gpu_err_clone = gpu_err.clone();
+
+    let exit_requested = renderer.exit_flag();
+    let secondary_thread = spawn_secondary_thread(config, engine_core, &renderer.gpu_core, exit_requested.clone(), state_tps_op);
+
+    // Reborrowed so the renderer can still let go of the physics thread once the loop is done with it
+    let loop_renderer = &mut *renderer;
+
+    let render_res = event_loop.run(move |event, elwt: &EventLoopWindowTarget<U>| {
+        let renderer = &mut *loop_renderer;
+
+        if let Err(e) = renderer.handle_event(&event) {
+            *gpu_err_clone.borrow_mut() = Some(LatrError::Gpu(e));
+        }
+
+        match event {
+            winit::event::Event::WindowEvent { window_id, event: winit::event::WindowEvent::RedrawRequested }
+            if window_id == renderer.window().id() => {
+                if let Err(e) = renderer.frame() {
+                    *gpu_err_clone.borrow_mut() = Some(LatrError::Gpu(e));
+                }
+            }
+
+            // Once the loop runs out of events, we either draw or sleep until the next frame is due
+            winit::event::Event::AboutToWait => {
+                if renderer.frame_due() {
+                    renderer.window().request_redraw();
+                }
+
+                match renderer.next_frame() {
+                    Some(next_frame) => elwt.set_control_flow(ControlFlow::WaitUntil(next_frame)),
+                    None => elwt.set_control_flow(ControlFlow::Poll),
+                }
+            }
+
+            winit::event::Event::UserEvent(user_event) => {
+                if let Some(on_user_event) = on_user_event.as_mut() {
+                    on_user_event(user_event, renderer.window());
+                }
+            }
+
             _ => ()
         }
+
+        // Closing the window, a gpu error, or the physics thread asking to stop
+        if renderer.exit_requested() {
+            elwt.exit();
+        }
     });
 
+    // However the window closed, the physics thread stops at its next tick, and we wait for it here
+    exit_requested.store(true, Ordering::Release);
+    renderer.release_engine();
+
+    let thread_res = join_secondary_thread(secondary_thread);
+
+    // A gpu error is what made everything stop, so it comes first
     if let Ok(err_cell) = Rc::try_unwrap(gpu_err) {
         if let Some(gpu_err) = err_cell.into_inner() {
             return Err(gpu_err);
         }
     }
 
-    match render_res {
-        Ok(()) => Ok(()),
-        Err(e) => Err(LatrError::Window(WindowError::EventLoop(e))),
+    render_res.map_err(|e| LatrError::Window(WindowError::EventLoop(e)))?;
+
+    thread_res
+}
+
+// Loads the models and runs the physics loop on a thread of its own, for everything that doesn't replay
+// The buffers are cloned over for their scene copy, which both threads share, see GpuBuffers::upload_scene
+pub(crate) fn spawn_secondary_thread<T: PhysicsLoop + 'static + std::marker::Send>(
+    config: LatrConfig,
+    engine_core: Engine,
+    gpu_core: &GpuCore,
+    exit_requested: Arc<AtomicBool>,
+    state_tps_op: Option<(T, u32)>,
+) -> thread::JoinHandle<Result<(), LatrError>> {
+    let buffers = gpu_core.buffers.clone();
+
+    thread::spawn(move || {
+        // If anything in here panics, the guard closes the window on its way out
+        let _exit_guard = ExitOnPanic(exit_requested.clone());
+
+        let res = run_secondary_thread(config, engine_core, buffers, |engine| {
+            match state_tps_op {
+                Some((state, tps)) => engine.start_physics_loop(state, tps),
+                None => Ok(()),
+            }
+        });
+
+        // Errors close the window too, and get handed back once the thread is joined
+        if res.is_err() {
+            exit_requested.store(true, Ordering::Release);
+        }
+
+        res
+    })
+}
+
+pub(crate) fn join_secondary_thread(secondary_thread: thread::JoinHandle<Result<(), LatrError>>) -> Result<(), LatrError> {
+    secondary_thread
+        .join()
+        .unwrap_or(Err(LatrError::Engine(EngineError::PhysicsThreadPanicked)))
+}
+
+// Builds the bvh, then runs the engine with whatever loop it's given
+pub(crate) fn run_secondary_thread<F>(
+    config: LatrConfig,
+    mut engine: Engine,
+    mut buffers: GpuBuffers,
+    run: F,
+) -> Result<(), LatrError>
+where
+    F: FnOnce(&mut Engine) -> Result<(), LatrError>,
+{
+    if let Some(model_file) = config.model_file {
+        let bounds = build_blas(model_file, &mut buffers)?;
+        engine.set_scene_bounds(bounds);
+        engine.upload_scene()?;
+    }
+
+    // After that is done, we run our engine
+    println!("Started running engine");
+
+    run(&mut engine)
+}
+
+// Sets the exit flag if the thread holding it panics, so the window doesn't stay open with nothing running it
+pub(crate) struct ExitOnPanic(pub(crate) Arc<AtomicBool>);
+
+impl Drop for ExitOnPanic {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.store(true, Ordering::Release);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+// Decides when the event loop should draw the next frame, so we stay under LatrConfig::fps_cap
+// Frames are scheduled on a fixed grid from the last one, so small wakeup delays don't add up over time
+pub(crate) struct FramePacer {
+    // None means uncapped, where we draw as soon as the last frame is done
+    frame_duration: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    // An fps cap of 0 means uncapped
+    pub(crate) fn new(fps_cap: u32, now: Instant) -> Self {
+        let frame_duration = (fps_cap > 0).then(|| Duration::from_secs_f64(1.0 / fps_cap as f64));
+
+        Self { frame_duration, next_frame: now }
+    }
+
+    pub(crate) fn frame_due(&self, now: Instant) -> bool {
+        now >= self.next_frame
+    }
+
+    // Time the event loop should sleep until, None when uncapped
+    pub(crate) fn next_frame(&self) -> Option<Instant> {
+        self.frame_duration.map(|_| self.next_frame)
+    }
+
+    pub(crate) fn frame_started(&mut self, now: Instant) {
+        let Some(frame_duration) = self.frame_duration else {
+            return;
+        };
+
+        self.next_frame += frame_duration;
+
+        // If we fell more than a frame behind (a hitch, or the window was hidden), we start the grid over
+        // Otherwise we'd draw a burst of frames back to back trying to catch up
+        if self.next_frame + frame_duration < now {
+            self.next_frame = now + frame_duration;
+        }
+    }
+}
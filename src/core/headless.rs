@@ -0,0 +1,73 @@
+use crate::{
+    engine::{
+        engine_core::Engine,
+        recording::{FrameExport, Recording},
+    },
+    gpu::{gpu_core::GpuCore, render_commands::RenderCommand},
+    PhysicsLoop,
+};
+
+use std::{
+    sync::atomic::Ordering,
+    thread,
+};
+
+use crate::core::config::LatrConfig;
+use crate::core::error::LatrError;
+use crate::core::event_loop::{join_secondary_thread, run_secondary_thread, ExitOnPanic, RenderChannels};
+
+// End of imports
+
+// Plays a recording back with no window, see LatrEngine::replay
+// The engine runs on its own thread like it normally does, and this thread only traces a frame when one is being exported
+pub(crate) fn run_headless_replay<T: PhysicsLoop + 'static + std::marker::Send>(
+    config: LatrConfig,
+    engine_core: Engine,
+    gpu_core: GpuCore,
+    channels: RenderChannels,
+    state: T,
+    recording: Recording,
+    exports: Vec<FrameExport>,
+) -> Result<(), LatrError> {
+    let mut gpu_core = gpu_core;
+    let RenderChannels { render_commands, exit_requested, .. } = channels;
+
+    let secondary_buffers = gpu_core.buffers.clone();
+    let secondary_exit = exit_requested.clone();
+
+    let secondary_thread = thread::spawn(move || {
+        let _exit_guard = ExitOnPanic(secondary_exit);
+
+        run_secondary_thread(config, engine_core, secondary_buffers, |engine| {
+            engine.replay(state, &recording, &exports)
+        })
+    });
+
+    // Commands come in until the engine is done and drops its end of the channel
+    let mut render_res = Ok(());
+
+    for command in render_commands.iter() {
+        let export = matches!(command, RenderCommand::Export(_));
+
+        if let Err(e) = gpu_core.apply_command(command) {
+            render_res = Err(e);
+            break;
+        }
+
+        if export && let Err(e) = gpu_core.render() {
+            render_res = Err(e);
+            break;
+        }
+    }
+
+    // Dropping the gpu side lets an engine that's waiting on an export see that we're gone
+    exit_requested.store(true, Ordering::Release);
+    drop(render_commands);
+    drop(gpu_core);
+
+    let thread_res = join_secondary_thread(secondary_thread);
+
+    render_res?;
+
+    thread_res
+}
@@ -1,71 +1,208 @@
 use crate::{
-    engine::engine_core::{Engine, PhysicsLoop},
-    gpu::gpu_core::GpuCore,
+    engine::{
+        engine_core::{Engine, PhysicsLoop},
+        recording::{FrameExport, Recording},
+    },
+    gpu::{
+        adapter::{list_adapters, AdapterDetails},
+        gpu_core::GpuCore,
+        init_utils::make_instance,
+    },
 };
 
-use std::sync::Arc;
-use crate::core::config::LatrConfig;
-use crate::core::error::{LatrError, WindowError, };
-use crate::core::event_loop::run_event_loop;
+use std::sync::{atomic::AtomicBool, mpsc, Arc};
+use std::thread::JoinHandle;
+use crate::core::config::{AdapterPreferences, LatrConfig};
+use crate::core::builder::LatrEngineBuilder;
+use crate::core::error::{ConfigError, LatrError, WindowError, };
+use crate::core::event_loop::{join_secondary_thread, run_event_loop, spawn_secondary_thread, RenderChannels, UserEventCallback, WindowRenderer};
+use crate::core::headless::run_headless_replay;
 
-pub struct LatrEngine {
+// U is the type of user event the event loop carries, see with_user_events
+pub struct LatrEngine<U: Send + 'static = ()> {
     config: LatrConfig,
 
-    engine_core: Engine,
-    gpu_core: GpuCore,
-    
-    window: Arc<winit::window::Window>,
-    event_loop: winit::event_loop::EventLoop<()>,
+    // Taken when the physics thread starts
+    engine_core: Option<Engine>,
+    renderer: WindowRenderer,
+
+    // None when the event loop belongs to the host, see with_window
+    event_loop: Option<winit::event_loop::EventLoop<U>>,
+    on_user_event: Option<UserEventCallback<U>>,
+
+    // Only used when the host owns the event loop, start joins its own thread
+    secondary_thread: Option<JoinHandle<Result<(), LatrError>>>,
 }
 
-impl LatrEngine {
+impl<U: Send + 'static> LatrEngine<U> {
+    // Like new, with an event loop that carries user events of type U
+    // Other threads send them with the proxy from Engine::event_loop_proxy, and they arrive at on_user_event
+    pub fn with_user_events(latr_config: LatrConfig) -> Result<Self, LatrError> {
+        latr_config.validate()?;
+
+        let (window, event_loop) = Self::make_window_event_loop(latr_config.resolution)?;
+
+        Self::from_window(latr_config, window, Some(event_loop))
+    }
+
+    // Called on the render thread with every user event, and the window so it can be redrawn or retitled
+    // Only start runs it, a host with its own event loop gets user events from that loop instead
+    pub fn on_user_event<F>(&mut self, callback: F)
+    where
+        F: FnMut(U, &winit::window::Window) + 'static,
+    {
+        self.on_user_event = Some(Box::new(callback));
+    }
+
+    // Another handle on the proxy Engine::event_loop_proxy hands out, for threads that aren't the physics loop
+    pub fn event_loop_proxy(&self) -> Option<winit::event_loop::EventLoopProxy<U>> {
+        self.event_loop.as_ref().map(|event_loop| event_loop.create_proxy())
+    }
+
+    // For hosts that own the event loop, so Engine::event_loop_proxy has something to hand out
+    // Has to be called before start_physics, the engine is on its own thread after that
+    pub fn set_event_loop_proxy<V: Send + 'static>(&mut self, proxy: winit::event_loop::EventLoopProxy<V>) {
+        if let Some(engine_core) = self.engine_core.as_mut() {
+            engine_core.set_event_loop_proxy(proxy);
+        }
+    }
+
+    // Runs everything until the window closes, the event loop is ours for the whole time
     pub fn start<T>(
-        self,
+        mut self,
         state_tps_op: Option<(T, u32)>,
     ) -> Result<(), LatrError>
     where
         T: PhysicsLoop + 'static + std::marker::Send,
     {
-        let LatrEngine {
-            config,
-            engine_core,
-            gpu_core,
-            window,
-            event_loop,
-        } = self;
+        if let Some((_, 0)) = state_tps_op {
+            return Err(ConfigError::ZeroTickRate.into());
+        }
 
-        run_event_loop::<T>(
-           config,
+        let (Some(event_loop), Some(engine_core)) = (self.event_loop.take(), self.engine_core.take()) else {
+            return Err(WindowError::HostOwnsEventLoop.into());
+        };
+
+        run_event_loop::<T, U>(
+           self.config.clone(),
            engine_core,
-           gpu_core,
-           window,
+           &mut self.renderer,
            event_loop,
+           self.on_user_event.take(),
            state_tps_op,
         )?;
 
         Ok(())
     }
 
-    pub fn new(latr_config: LatrConfig) -> Result<Self, LatrError> {
-        let (window, event_loop) = Self::make_window_event_loop(latr_config.resolution)?;
+    // Loads the models and runs the physics loop on its own thread, for when the host owns the event loop
+    // Same as start without blocking, a second call does nothing
+    pub fn start_physics<T>(&mut self, state_tps_op: Option<(T, u32)>) -> Result<(), LatrError>
+    where
+        T: PhysicsLoop + 'static + std::marker::Send,
+    {
+        if let Some((_, 0)) = state_tps_op {
+            return Err(ConfigError::ZeroTickRate.into());
+        }
+
+        let Some(engine_core) = self.engine_core.take() else {
+            return Ok(());
+        };
+
+        let exit_requested = self.renderer.exit_flag();
+        let secondary_thread = spawn_secondary_thread(
+            self.config.clone(), engine_core, &self.renderer.gpu_core, exit_requested, state_tps_op,
+        );
+
+        self.secondary_thread = Some(secondary_thread);
+
+        Ok(())
+    }
+
+    // Input, resizing and closing for our window, events for other windows are ignored
+    // Resizing and switching debug views rebuild the render graph, which can fail like frame can
+    pub fn handle_event<E>(&mut self, event: &winit::event::Event<E>) -> Result<(), LatrError> {
+        Ok(self.renderer.handle_event(event)?)
+    }
+
+    // Draws a frame, call it whenever the host wants one, like on RedrawRequested for window()
+    pub fn frame(&mut self) -> Result<(), LatrError> {
+        Ok(self.renderer.frame()?)
+    }
+
+    // Whether LatrConfig::fps_cap allows another frame yet, hosts that don't pace frames themselves can check this
+    pub fn frame_due(&self) -> bool {
+        self.renderer.frame_due()
+    }
+
+    // When the next frame is due under the fps cap, None when uncapped
+    pub fn next_frame(&self) -> Option<std::time::Instant> {
+        self.renderer.next_frame()
+    }
+
+    pub fn window(&self) -> &Arc<winit::window::Window> {
+        self.renderer.window()
+    }
+
+    // The window was closed, Engine::request_exit was called, or something ran into an error
+    // The host should stop calling frame and call finish to find out which
+    pub fn exit_requested(&self) -> bool {
+        self.renderer.exit_requested()
+    }
+
+    // Stops the physics loop after its current tick and waits for it, handing back any error it ran into
+    pub fn finish(mut self) -> Result<(), LatrError> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<(), LatrError> {
+        self.renderer.request_exit();
+        self.renderer.release_engine();
 
-        let engine_core = Engine::new(&latr_config)?;
+        match self.secondary_thread.take() {
+            Some(secondary_thread) => join_secondary_thread(secondary_thread),
+            None => Ok(()),
+        }
+    }
+
+    fn from_window(
+        latr_config: LatrConfig,
+        window: Arc<winit::window::Window>,
+        event_loop: Option<winit::event_loop::EventLoop<U>>,
+    ) -> Result<Self, LatrError> {
+        let (render_command_sender, render_commands) = mpsc::channel();
+        let (input_events, input_receiver) = mpsc::channel();
+        let exit_requested = Arc::new(AtomicBool::new(false));
+
+        let mut engine_core = Engine::new(&latr_config, render_command_sender, input_receiver, exit_requested.clone())?;
+
+        if let Some(event_loop) = event_loop.as_ref() {
+            engine_core.set_event_loop_proxy(event_loop.create_proxy());
+        }
         
-        let gpu_core = GpuCore::new(
+        let mut gpu_core = GpuCore::new(
             window.clone(),
+            &latr_config,
         )?;
 
-        let config = latr_config;
+        LatrEngine::apply_starting_settings(&mut gpu_core, &latr_config)?;
+        engine_core.set_device_limits(gpu_core.device.limits());
+
+        let channels = RenderChannels { render_commands, input_events, exit_requested };
+        let renderer = WindowRenderer::new(gpu_core, window, channels, latr_config.fps_cap);
 
         Ok(Self {
-            config,
-            gpu_core, engine_core,
-            window, event_loop,
+            config: latr_config,
+            engine_core: Some(engine_core),
+            renderer,
+            event_loop,
+            on_user_event: None,
+            secondary_thread: None,
         })
     }
 
-    fn make_window_event_loop(resolution: (u32, u32)) -> Result<(Arc<winit::window::Window>, winit::event_loop::EventLoop<()>), WindowError> {
-        let event_loop = winit::event_loop::EventLoop::new()?;
+    fn make_window_event_loop(resolution: (u32, u32)) -> Result<(Arc<winit::window::Window>, winit::event_loop::EventLoop<U>), WindowError> {
+        let event_loop = winit::event_loop::EventLoopBuilder::<U>::with_user_event().build()?;
 
         let window_arc = Arc::new(winit::window::WindowBuilder::new()
             .with_inner_size(winit::dpi::LogicalSize::new(resolution.0, resolution.1))
@@ -73,4 +210,89 @@ impl LatrEngine {
 
         Ok((window_arc, event_loop))
     }
-}
\ No newline at end of file
+}
+
+impl LatrEngine {
+    // For hosts that own the event loop, made with a window they built on it
+    // Forward every event to handle_event, and call frame to draw, see the README for a loop that does both
+    pub fn with_window(latr_config: LatrConfig, window: Arc<winit::window::Window>) -> Result<Self, LatrError> {
+        latr_config.validate()?;
+
+        Self::from_window(latr_config, window, None)
+    }
+
+    // Every adapter on the backends in the preferences, best fit first, for picking a name to put in the config
+    pub fn adapters(preferences: &AdapterPreferences) -> Vec<AdapterDetails> {
+        list_adapters(&make_instance(preferences), preferences)
+    }
+
+    pub fn builder() -> LatrEngineBuilder {
+        LatrEngineBuilder::default()
+    }
+
+    // Fails with a ConfigError before anything is created if the config doesn't make sense, see LatrConfig::validate
+    pub fn new(latr_config: LatrConfig) -> Result<Self, LatrError> {
+        latr_config.validate()?;
+
+        let (window, event_loop) = Self::make_window_event_loop(latr_config.resolution)?;
+
+        Self::from_window(latr_config, window, Some(event_loop))
+    }
+
+    // Plays back a session written with LatrConfig::record, with no window and no sleeping between ticks
+    // The state should start out the same as when it was recorded, and the config should load the same models
+    // Each export is saved once its tick is reached, exports past the end of the recording never happen
+    pub fn replay<T>(
+        latr_config: LatrConfig,
+        state: T,
+        recording: Recording,
+        exports: Vec<FrameExport>,
+    ) -> Result<(), LatrError>
+    where
+        T: PhysicsLoop + 'static + std::marker::Send,
+    {
+        latr_config.validate()?;
+
+        if recording.tick().is_zero() {
+            return Err(ConfigError::ZeroTickRate.into());
+        }
+
+        let (render_command_sender, render_commands) = mpsc::channel();
+
+        // Nothing sends input during a replay, it all comes from the recording
+        let (input_events, input_receiver) = mpsc::channel();
+        let exit_requested = Arc::new(AtomicBool::new(false));
+
+        let mut engine_core = Engine::new(&latr_config, render_command_sender, input_receiver, exit_requested.clone())?;
+
+        let mut gpu_core = GpuCore::new_headless(&latr_config)?;
+        Self::apply_starting_settings(&mut gpu_core, &latr_config)?;
+        engine_core.set_device_limits(gpu_core.device.limits());
+
+        let channels = RenderChannels { render_commands, input_events, exit_requested };
+
+        run_headless_replay(latr_config, engine_core, gpu_core, channels, state, recording, exports)
+    }
+
+    // The starting background goes straight in, rather than waiting on the channel for the first frame
+    pub(crate) fn apply_starting_settings(gpu_core: &mut GpuCore, latr_config: &LatrConfig) -> Result<(), LatrError> {
+        gpu_core.set_background(Box::new(latr_config.background.prepare(gpu_core.device.limits().max_texture_dimension_2d)?))?;
+        gpu_core.set_tone_mapping(latr_config.tone_mapping);
+        gpu_core.set_exposure(latr_config.exposure);
+        gpu_core.set_render_mode(latr_config.render_mode)?;
+        gpu_core.set_aovs(latr_config.aovs)?;
+
+        let max_lut_size = gpu_core.device.limits().max_texture_dimension_3d;
+        let post_effects = latr_config.post_effects.iter().map(|effect| effect.prepare(max_lut_size)).collect::<Result<Vec<_>, _>>()?;
+        gpu_core.set_post_effects(post_effects)?;
+
+        Ok(())
+    }
+}
+
+// A host that drops the engine without calling finish still gets the physics thread stopped
+impl<U: Send + 'static> Drop for LatrEngine<U> {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
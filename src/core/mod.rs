@@ -1,4 +1,9 @@
 pub mod latr_core;
 mod event_loop;
+pub(crate) mod frame_pacer;
 pub mod error;
-pub mod config;
\ No newline at end of file
+pub mod config;
+mod headless;
+pub mod builder;
+pub(crate) mod config_file;
+pub mod renderer;
\ No newline at end of file
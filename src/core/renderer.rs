@@ -0,0 +1,201 @@
+use crate::{
+    engine::{
+        engine_core::{Engine, PhysicsLoop},
+        input::InputEvent,
+    },
+    gpu::gpu_core::GpuCore,
+};
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::JoinHandle,
+};
+
+use crate::core::config::LatrConfig;
+use crate::core::error::{ConfigError, GpuError, LatrError};
+use crate::core::event_loop::{join_secondary_thread, spawn_secondary_thread};
+use crate::core::latr_core::LatrEngine;
+use crate::gpu::aovs::AovTextures;
+use crate::gpu::render_commands::RenderCommand;
+
+// End of imports
+
+// The ray tracer on a device that belongs to someone else, for using it as one pass in an existing wgpu app
+// There's no window or event loop, the caller records frames into their own encoder with render_into
+// The physics loop still runs on its own thread, started with start
+pub struct LatrRenderer {
+    gpu_core: GpuCore,
+
+    // Taken by start, which hands it to the physics thread
+    engine_core: Option<Engine>,
+    config: LatrConfig,
+
+    render_commands: mpsc::Receiver<RenderCommand>,
+    input_events: mpsc::Sender<InputEvent>,
+    exit_requested: Arc<AtomicBool>,
+
+    secondary_thread: Option<JoinHandle<Result<(), LatrError>>>,
+
+    // A post effect that failed to build on the render side, handed back by finish
+    render_error: Option<LatrError>,
+}
+
+impl LatrRenderer {
+    // The target is what render_into draws to, the blit pipeline gets built for its format
+    // The device needs limits big enough for LatrConfig::scene_capacity, or this fails with GpuError::BufferLimitExceeded
+    pub fn new(
+        latr_config: LatrConfig,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+        target_size: (u32, u32),
+    ) -> Result<Self, LatrError> {
+        latr_config.validate()?;
+
+        let (render_command_sender, render_commands) = mpsc::channel();
+        let (input_events, input_receiver) = mpsc::channel();
+        let exit_requested = Arc::new(AtomicBool::new(false));
+
+        let mut engine_core = Engine::new(&latr_config, render_command_sender, input_receiver, exit_requested.clone())?;
+
+        let mut gpu_core = GpuCore::from_device(device.clone(), queue.clone(), target_format, target_size, &latr_config)?;
+        LatrEngine::apply_starting_settings(&mut gpu_core, &latr_config)?;
+        engine_core.set_device_limits(device.limits());
+
+        Ok(Self {
+            gpu_core,
+            engine_core: Some(engine_core),
+            config: latr_config,
+            render_commands, input_events, exit_requested,
+            secondary_thread: None,
+            render_error: None,
+        })
+    }
+
+    // Loads the models and runs the physics loop on its own thread, same as LatrEngine::start but without blocking
+    // None only loads the models, a second call does nothing
+    pub fn start<T>(&mut self, state_tps_op: Option<(T, u32)>) -> Result<(), LatrError>
+    where
+        T: PhysicsLoop + 'static + std::marker::Send,
+    {
+        if let Some((_, 0)) = state_tps_op {
+            return Err(ConfigError::ZeroTickRate.into());
+        }
+
+        let Some(engine_core) = self.engine_core.take() else {
+            return Ok(());
+        };
+
+        let secondary_thread = spawn_secondary_thread(
+            self.config.clone(), engine_core, &self.gpu_core, self.exit_requested.clone(), state_tps_op,
+        );
+
+        self.secondary_thread = Some(secondary_thread);
+
+        Ok(())
+    }
+
+    // Records the trace and the blit into the encoder, drawing over the whole view
+    // The view has to match the format given to new, and the size given to new or the last resize
+    // Exports are read back at the start of the next call, once the frame has been submitted
+    pub fn render_into(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.apply_commands();
+
+        let res = self.gpu_core.render_into(encoder, Some(view));
+        self.record_error(res);
+    }
+
+    // Records only the trace, for callers that do their own tone mapping, compositing or encoding from output_texture
+    // Exports still work, they're read back at the start of the next trace_into or render_into
+    pub fn trace_into(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.apply_commands();
+
+        let res = self.gpu_core.render_into(encoder, None);
+        self.record_error(res);
+    }
+
+    // An error asks the physics loop to stop, like one from the physics thread would
+    fn apply_commands(&mut self) {
+        let res = self.render_commands
+            .try_iter()
+            .try_for_each(|command| self.gpu_core.apply_command(command));
+
+        self.record_error(res);
+    }
+
+    // Keeps the first error for finish to hand back
+    fn record_error(&mut self, res: Result<(), GpuError>) {
+        if let Err(e) = res {
+            self.render_error.get_or_insert(e.into());
+            self.exit_requested.store(true, Ordering::Release);
+        }
+    }
+
+    // What the trace writes to, linear hdr color in Rgba16Float at the render size, before post effects and tone mapping
+    // It can be sampled or copied from, and holds the frame once the encoder from trace_into or render_into is submitted
+    // A new texture gets made when the render size changes, so compare it against the last one before reusing bind groups
+    pub fn output_texture(&self) -> &wgpu::Texture {
+        self.gpu_core.screen_texture()
+    }
+
+    pub fn output_view(&self) -> &wgpu::TextureView {
+        self.gpu_core.screen_texture_view()
+    }
+
+    // Albedo, normals with depth, and positions with instance ids, at the same size as output_texture
+    // None unless they're turned on, see LatrConfig::aovs, and they're made again on resize the same way
+    pub fn aovs(&self) -> Option<&AovTextures> {
+        self.gpu_core.aovs()
+    }
+
+    // For when the target changes size, the render resolution follows it unless it's fixed
+    // Like render_into, an error here stops the physics loop and comes back from finish
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let res = self.gpu_core.resize(width, height);
+        self.record_error(res);
+    }
+
+    // Input the caller wants the physics loop to see, see InputState
+    pub fn send_input(&self, event: InputEvent) {
+        // The physics thread may have already stopped, in which case nobody is listening
+        let _ = self.input_events.send(event);
+    }
+
+    // Set by Engine::request_exit, or when either side ran into an error, finish has the error
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested.load(Ordering::Acquire)
+    }
+
+    // Stops the physics loop after its current tick and waits for it, handing back any error it ran into
+    pub fn finish(mut self) -> Result<(), LatrError> {
+        let thread_res = self.stop();
+
+        match self.render_error.take() {
+            Some(e) => Err(e),
+            None => thread_res,
+        }
+    }
+
+    // Exports the physics thread may be waiting on are dropped first, along with any commands never applied,
+    // so it sees the render side is gone instead of blocking the join
+    fn stop(&mut self) -> Result<(), LatrError> {
+        self.exit_requested.store(true, Ordering::Release);
+        self.gpu_core.cancel_exports();
+        self.render_commands = mpsc::channel().1;
+
+        match self.secondary_thread.take() {
+            Some(secondary_thread) => join_secondary_thread(secondary_thread),
+            None => Ok(()),
+        }
+    }
+}
+
+// Dropping without finish still stops the physics thread, the result just goes nowhere
+impl Drop for LatrRenderer {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
@@ -0,0 +1,240 @@
+use std::path::{Path, PathBuf};
+
+use crate::core::error::EngineError;
+use crate::gpu::buffers::GpuUniformBackground;
+
+// End of imports
+
+// What rays see when they miss every piece of geometry
+// The background also lights the scene, so a bright sky gives bright models
+#[derive(Clone, Debug, PartialEq)]
+pub enum Background {
+    Color([f32; 3]),
+
+    // Blends from the horizon up to the zenith, with a flat ground color below the horizon
+    Gradient {
+        zenith: [f32; 3],
+        horizon: [f32; 3],
+        ground: [f32; 3],
+    },
+
+    // Simple analytic daylight sky, brightening towards the sun with a sun disk on top
+    // The sun intensity is how brightly it lights a surface facing it, same as a directional light
+    Sky {
+        sun_dir: [f32; 3],
+        sun_color: [f32; 3],
+        sun_intensity: f32,
+    },
+
+    // Equirectangular .hdr or .exr image wrapped around the scene
+    // Rotation is in radians around the y axis
+    EnvironmentMap {
+        path: PathBuf,
+        intensity: f32,
+        rotation: f32,
+    },
+}
+
+impl Background {
+    pub fn environment_map<P: AsRef<Path>>(path: P) -> Self {
+        Background::EnvironmentMap {
+            path: path.as_ref().to_path_buf(),
+            intensity: 1.0,
+            rotation: 0.0,
+        }
+    }
+
+    // Does all the cpu side work of a background, including loading and building the cdf of environment maps
+    // This is the slow part, so it's done before anything is handed to the render thread
+    // Maps bigger than max_dimension, the device's largest 2d texture, get shrunk to fit
+    pub(crate) fn prepare(&self, max_dimension: u32) -> Result<PreparedBackground, EngineError> {
+        let mut uniform = GpuUniformBackground::default();
+        let mut environment = None;
+
+        match self {
+            Background::Color(color) => {
+                uniform.kind = GpuUniformBackground::KIND_COLOR;
+                uniform.color = *color;
+            },
+            Background::Gradient { zenith, horizon, ground } => {
+                uniform.kind = GpuUniformBackground::KIND_GRADIENT;
+                uniform.color = *zenith;
+                uniform.horizon = *horizon;
+                uniform.ground = *ground;
+            },
+            Background::Sky { sun_dir, sun_color, sun_intensity } => {
+                uniform.kind = GpuUniformBackground::KIND_SKY;
+                uniform.sun_dir = glam::Vec3::from_array(*sun_dir).normalize_or(glam::Vec3::Y).to_array();
+                uniform.sun_color = *sun_color;
+                uniform.intensity = *sun_intensity;
+            },
+            Background::EnvironmentMap { path, intensity, rotation } => {
+                let image = EnvironmentImage::load(path, max_dimension)?;
+
+                uniform.kind = GpuUniformBackground::KIND_ENVIRONMENT;
+                uniform.intensity = *intensity;
+                uniform.env_rotation = *rotation;
+                uniform.env_width = image.width;
+                uniform.env_height = image.height;
+                uniform.env_total = image.total;
+
+                environment = Some(image);
+            },
+        }
+
+        Ok(PreparedBackground { uniform, environment })
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Gradient {
+            zenith: [0.3, 0.5, 0.9],
+            horizon: [0.8, 0.85, 0.9],
+            ground: [0.2, 0.2, 0.2],
+        }
+    }
+}
+
+pub(crate) struct PreparedBackground {
+    pub(crate) uniform: GpuUniformBackground,
+    pub(crate) environment: Option<EnvironmentImage>,
+}
+
+// Decoded environment map along with the tables used to importance sample it
+pub(crate) struct EnvironmentImage {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixels: Vec<[f32; 4]>,
+
+    // cdf_width x (height + 1) table
+    // Rows 0..height are the cdf of each row of pixels, the last row is the cdf of picking each row
+    pub(crate) cdf: Vec<f32>,
+    pub(crate) cdf_width: u32,
+
+    // Sum of every pixel's weight, the shader needs it to turn a pick back into a pdf
+    pub(crate) total: f32,
+}
+
+impl EnvironmentImage {
+    fn load(path: &Path, max_dimension: u32) -> Result<Self, EngineError> {
+        let image = image::open(path)
+            .map_err(|e| EngineError::EnvironmentMap(path.to_path_buf(), e))?
+            .into_rgba32f();
+
+        let (width, height) = image.dimensions();
+        let pixels: Vec<[f32; 4]> = image.pixels().map(|p| p.0).collect();
+
+        let (width, height, pixels) = downsample_to_fit(width, height, pixels, max_dimension);
+
+        Ok(Self::from_pixels(width, height, pixels))
+    }
+
+    pub(crate) fn from_pixels(width: u32, height: u32, pixels: Vec<[f32; 4]>) -> Self {
+        let (w, h) = (width as usize, height as usize);
+
+        // Wide enough to hold both a row of pixels and one entry per row
+        let cdf_width = w.max(h);
+        let mut cdf = vec![0.0f32; cdf_width * (h + 1)];
+        let mut row_sums = vec![0.0f32; h];
+
+        for y in 0..h {
+            // Rows near the poles get squished together by the equirectangular mapping, so they're weighted down
+            let theta = std::f32::consts::PI * (y as f32 + 0.5) / h as f32;
+            let sin_theta = theta.sin();
+
+            let row = &mut cdf[y * cdf_width..y * cdf_width + w];
+            let mut running = 0.0;
+
+            for x in 0..w {
+                let p = pixels[y * w + x];
+                running += luminance(p) * sin_theta;
+                row[x] = running;
+            }
+
+            row_sums[y] = running;
+            normalize_cdf(row);
+        }
+
+        let total: f32 = row_sums.iter().sum();
+
+        let marginal = &mut cdf[h * cdf_width..h * cdf_width + h];
+        let mut running = 0.0;
+        for y in 0..h {
+            running += row_sums[y];
+            marginal[y] = running;
+        }
+        normalize_cdf(marginal);
+
+        EnvironmentImage {
+            width, height, pixels,
+            cdf, cdf_width: cdf_width as u32,
+            total,
+        }
+    }
+}
+
+// Box filters the map down by a whole factor until both it and its cdf fit in a max_dimension texture
+// The cdf is max(width, height) wide and height + 1 tall, so the height has to leave room for the extra row
+pub(crate) fn downsample_to_fit(width: u32, height: u32, pixels: Vec<[f32; 4]>, max_dimension: u32) -> (u32, u32, Vec<[f32; 4]>) {
+    let max_dimension = max_dimension.max(2);
+    let fits = |f: u32| width.div_ceil(f) <= max_dimension && height.div_ceil(f) < max_dimension;
+
+    let mut factor = 1;
+    while !fits(factor) {
+        factor += 1;
+    }
+
+    if factor == 1 {
+        return (width, height, pixels);
+    }
+
+    let (new_width, new_height) = (width.div_ceil(factor), height.div_ceil(factor));
+    let mut out = Vec::with_capacity((new_width * new_height) as usize);
+
+    for y in 0..new_height {
+        for x in 0..new_width {
+            // Blocks on the right and bottom edges can be cut short
+            let (x0, x1) = (x * factor, ((x + 1) * factor).min(width));
+            let (y0, y1) = (y * factor, ((y + 1) * factor).min(height));
+
+            let mut sum = [0.0f32; 4];
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    let p = pixels[(sy * width + sx) as usize];
+                    for c in 0..4 {
+                        sum[c] += p[c];
+                    }
+                }
+            }
+
+            let count = ((x1 - x0) * (y1 - y0)) as f32;
+            out.push(sum.map(|c| c / count));
+        }
+    }
+
+    (new_width, new_height, out)
+}
+
+// Divides a running sum by its last value
+// Black rows can't be picked, so we spread them evenly instead of dividing by zero
+fn normalize_cdf(cdf: &mut [f32]) {
+    let total = match cdf.last() {
+        Some(total) => *total,
+        None => return,
+    };
+
+    let len = cdf.len() as f32;
+
+    for (i, value) in cdf.iter_mut().enumerate() {
+        *value = if total > 0.0 { *value / total } else { (i + 1) as f32 / len };
+    }
+
+    if let Some(last) = cdf.last_mut() {
+        *last = 1.0;
+    }
+}
+
+fn luminance(p: [f32; 4]) -> f32 {
+    0.2126 * p[0] + 0.7152 * p[1] + 0.0722 * p[2]
+}
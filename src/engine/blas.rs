@@ -1,7 +1,14 @@
 use std::path::{ PathBuf, Path };
 use std::io::ErrorKind;
 
-use crate::gpu::buffers::{GpuBuffers, GpuStorageBvhNode};
+use crate::gpu::buffers::{
+    GpuBuffers,
+    GpuStorageAreaLight,
+    GpuStorageBvhNode,
+    GpuStorageInstanceMesh,
+    GpuStorageTriangleData,
+    GpuStorageVertex,
+};
 
 use crate::core::config::{
     ModelConfig,
@@ -17,9 +24,10 @@ use super::mesh_file_parsers::{
 };
 
 use super::bvh::{BvhNode, BvhPrimitive, AABB};
+use super::lights::{collect_area_lights, finish_area_lights};
 
 use rayon::prelude::*;
-use glam::{ Vec3A, Vec3 };
+use glam::{ Vec3A, Vec3, Mat4 };
 
 // End of importing
 
@@ -53,9 +61,24 @@ impl BvhPrimitive for RawTriangle {
     fn get_centroid(&self) -> Vec3 { (self[0] + self[1] + self[2]) / 3.0 }
 }
 
+// A mesh waiting in the batch, along with the per model data that has to reach the triangle buffer
+struct RawMesh {
+    triangles: Vec<RawTriangle>,
+    emission: [f32; 3],
+}
+
 pub struct BvhTriBatch {
-    raw_meshes: Vec<Vec<RawTriangle>>,
+    raw_meshes: Vec<RawMesh>,
     current_mem: usize,
+
+    // Emissive triangles found so far, these carry over between flushes
+    area_lights: Vec<GpuStorageAreaLight>,
+
+    // Box around every triangle pushed so far, also carried over between flushes
+    bounds: AABB,
+
+    // Box around each instance written so far, in instance order, which the TLAS is built over once everything is flushed
+    instance_bounds: Vec<AABB>,
 }
 
 impl BvhTriBatch {
@@ -66,36 +89,108 @@ impl BvhTriBatch {
         BvhTriBatch {
             raw_meshes: Vec::new(),
             current_mem: 0,
+            area_lights: Vec::new(),
+            bounds: AABB::new_max_inv(),
+            instance_bounds: Vec::new(),
         }
     }
 
-    fn push(&mut self, vertices: Vec<RawTriangle>) {
+    fn push(&mut self, vertices: Vec<RawTriangle>, emission: [f32; 3]) {
         self.current_mem += size_of::<RawTriangle>() * vertices.len();
-        self.raw_meshes.push(vertices);
+
+        for point in vertices.iter().flatten() {
+            self.bounds.grow_from_point(*point);
+        }
+
+        self.raw_meshes.push(RawMesh { triangles: vertices, emission });
     }
 
-    fn check_push(mut self, vertices: Vec<RawTriangle>, buffers: &mut GpuBuffers, queue: &wgpu::Queue, device: &wgpu::Device) -> Self {
+    fn check_push(mut self, vertices: Vec<RawTriangle>, emission: [f32; 3], buffers: &mut GpuBuffers) -> Result<Self, EngineError> {
+        // An empty file has nothing to build a bvh from
+        if vertices.is_empty() {
+            return Ok(self);
+        }
+
         if self.raw_meshes.len() >= Self::MAX_MESH_NUM || size_of::<RawTriangle>() * vertices.len() + self.current_mem >= Self::MAX_MEM_NUM {
-            self = self.flush(buffers, queue, device);
+            self = self.flush(buffers)?;
         }
 
-        self.push(vertices);
-        self
+        self.push(vertices, emission);
+        Ok(self)
     }
 
-    fn flush(self, buffers: &mut GpuBuffers, queue: &wgpu::Queue, device: &wgpu::Device) -> Self {
+    fn flush(self, buffers: &mut GpuBuffers) -> Result<Self, EngineError> {
+        let mut next = Self::new();
+        next.area_lights = self.area_lights;
+        next.bounds = self.bounds;
+        next.instance_bounds = self.instance_bounds;
+
         if self.raw_meshes.is_empty() {
-            return Self::new();
+            return Ok(next);
         }
 
-        let res: Vec<BvhNode> = self.raw_meshes.into_par_iter().map(|v| BvhNode::build(v)).collect();
+        // The bvh build takes ownership of the triangles, so we split out the gpu side copies first
+        // Triangles are a soup for now, every triangle gets its own 3 vertices
+        let uploads: Vec<(Vec<GpuStorageVertex>, [f32; 3], AABB)> = self.raw_meshes.iter()
+            .map(|mesh| {
+                let vertices = mesh.triangles.iter()
+                    .flatten()
+                    .map(|v| GpuStorageVertex::from_arr(v.to_array()))
+                    .collect();
+
+                let mut aabb = AABB::new_max_inv();
+                mesh.triangles.iter().flatten().for_each(|point| aabb.grow_from_point(*point));
+
+                (vertices, mesh.emission, aabb)
+            })
+            .collect();
+
+        let res: Vec<BvhNode> = self.raw_meshes.into_par_iter().map(|m| BvhNode::build(m.triangles)).collect();
         let gpu_batches: Vec<Vec<GpuStorageBvhNode>> = res.into_iter().map(|r| r.flatten_to_blas()).collect();
 
-        for batch in gpu_batches {
-            buffers.write_blas_bvh(queue, device, batch.as_slice());
+        for ((vertices, emission, aabb), batch) in uploads.into_iter().zip(gpu_batches) {
+            let vertex_base = buffers.write_vertices(&vertices)?;
+
+            let triangles: Vec<GpuStorageTriangleData> = (0..vertices.len() as u32 / 3)
+                .map(|i| GpuStorageTriangleData {
+                    vertices: [vertex_base + i * 3, vertex_base + i * 3 + 1, vertex_base + i * 3 + 2],
+                    _pad: 0,
+                    rgba: [1.0, 1.0, 1.0, 1.0],
+                    emission: [emission[0], emission[1], emission[2], 0.0],
+                })
+                .collect();
+
+            let triangle_base = buffers.write_triangles(&triangles)?;
+            next.area_lights.extend(collect_area_lights(&triangles, &vertices, vertex_base, triangle_base));
+
+            let blas_entry = buffers.write_blas_bvh(batch.as_slice(), triangle_base)?;
+
+            // Models are loaded in world space for now, so every instance gets the identity transform
+            buffers.write_instances(&[GpuStorageInstanceMesh::new(Mat4::IDENTITY, blas_entry)])?;
+            next.instance_bounds.push(aabb);
         }
 
-        Self::new()
+        Ok(next)
+    }
+}
+
+// Builds the TLAS over the bounds of every instance, leaves point at the instance with the same index
+// No instances leaves an empty entry node, which every ray misses
+pub fn build_tlas(instance_bounds: Vec<AABB>) -> Vec<GpuStorageBvhNode> {
+    if instance_bounds.is_empty() {
+        return vec![GpuStorageBvhNode::default()];
+    }
+
+    BvhNode::build(instance_bounds).flatten_to_blas()
+}
+
+// Matches the extension of a model file against the formats we can read
+// Returns None for files we don't know how to parse, so they can be skipped
+fn parse_model_file(file_path: &Path) -> Option<Result<Vec<RawTriangle>, EngineError>> {
+    // For now we just have .tri, representing a soup of sets of 9 vertices
+    match file_path.extension().and_then(|s| s.to_str()) {
+        Some("tri") => Some(parse_tri_file(file_path)),
+        _ => None,
     }
 }
 
@@ -104,8 +199,9 @@ impl BvhTriBatch {
 
 // This function is meant to be run on a separate thread
 // This is the public entry to this file
-// It handles most of the annoying io and writes to the buffers
-pub fn build_blas(model_config_file_path: PathBuf, buffers: &mut GpuBuffers, queue: &wgpu::Queue, device: &wgpu::Device) -> Result<(), EngineError> {
+// It handles most of the annoying io and fills in the scene copy, which the render thread uploads after, see upload_scene
+// Returns the bounds of every loaded triangle, or None if no models had any triangles
+pub fn build_blas(model_config_file_path: PathBuf, buffers: &mut GpuBuffers) -> Result<Option<AABB>, EngineError> {
     // We get the parent to use for any other io operations using the contents of the model config toml file
     let config_parent_dir = model_config_file_path.parent()
         .unwrap_or(Path::new("."));
@@ -146,27 +242,18 @@ pub fn build_blas(model_config_file_path: PathBuf, buffers: &mut GpuBuffers, que
 
                 // We get the osstr from the extension and convert it to a str
                 // We then match it against supported file extensions
-                // For now we just have .tri, representing a soup of sets of 9 vertices
                 let mut raw_triangles_op: Option<Vec<RawTriangle>> = None;
 
-                if let Some(extension) = file_path.extension().and_then(|s| s.to_str()) {
-                    let mut parse_res_op: Option<Result<Vec<RawTriangle>, EngineError>> = None;
-
-                    match extension {
-                        "tri" => parse_res_op = Some(parse_tri_file(&file_path)),
-                        _ => (),
-                    }
-
-                    if let Some(parse_res) = parse_res_op {
-                        match parse_res {
-                            Ok(r) => raw_triangles_op = Some(r),
-                            Err(e) => eprintln!("Error while parsing file \"{}\", error: {}", file_path.display(), e),
-                        }
+                if let Some(parse_res) = parse_model_file(&file_path) {
+                    match parse_res {
+                        Ok(r) => raw_triangles_op = Some(r),
+                        Err(e) => eprintln!("Error while parsing file \"{}\", error: {}", file_path.display(), e),
                     }
                 }
 
+                // Models found through directories have no config of their own, so they don't emit light
                 if let Some(raw_triangles) = raw_triangles_op {
-                    bvh_tri_batch = bvh_tri_batch.check_push(raw_triangles, buffers, queue, device);
+                    bvh_tri_batch = bvh_tri_batch.check_push(raw_triangles, [0.0; 3], buffers)?;
                 }
             }
         }
@@ -174,14 +261,31 @@ pub fn build_blas(model_config_file_path: PathBuf, buffers: &mut GpuBuffers, que
 
     // Our loop over the model directories is done, now we do explicit directories
 
+    // Unlike directory models, a bad explicit model is an error, since the user asked for it by name
     for explicit_model in model_config.models {
+        let file_path = config_parent_dir.join(&explicit_model.path);
 
-    }
+        let raw_triangles = match parse_model_file(&file_path) {
+            Some(parse_res) => parse_res?,
+            None => return Err(EngineError::UnsupportedModelFormat(file_path)),
+        };
 
-    // todo todo
+        let emission = explicit_model.emission.unwrap_or([0.0; 3]);
+        bvh_tri_batch = bvh_tri_batch.check_push(raw_triangles, emission, buffers)?;
+    }
 
     // After both runs, we flush any remaining models still in our batch
-    bvh_tri_batch.flush(buffers, queue, device);
+    let mut bvh_tri_batch = bvh_tri_batch.flush(buffers)?;
+
+    // Every emissive triangle is known now, so the area lights can be normalized and uploaded together
+    let area_light_power = finish_area_lights(&mut bvh_tri_batch.area_lights);
+    buffers.write_area_lights(&bvh_tri_batch.area_lights, area_light_power)?;
+
+    // Every instance is written too, so the TLAS can go over all of them
+    let tlas = build_tlas(std::mem::take(&mut bvh_tri_batch.instance_bounds));
+    buffers.write_tlas(&tlas)?;
 
-    Ok(())
+    // Nothing was pushed if the box is still inside out
+    let bounds = bvh_tri_batch.bounds;
+    Ok((bounds.min.cmple(bounds.max).all()).then_some(bounds))
 }
\ No newline at end of file
@@ -253,6 +253,17 @@ impl AABB {
     }
 }
 
+// Lets the TLAS be built over the bounds of each instance, the same way a BLAS is built over triangles
+impl BvhPrimitive for AABB {
+    fn get_aabb(&self) -> AABB {
+        *self
+    }
+
+    fn get_centroid(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+}
+
 impl Default for AABB {
     fn default() -> Self {
         AABB {
@@ -275,7 +286,95 @@ fn bvh_recurse(idxs: &mut [usize], parent_bounds: AABB, ctx: RecurseCtx) -> BvhN
         });
     }
 
-    todo!()
+    // The centroid bounds are only for binning, the branch itself needs the box around every primitive
+    let mut aabb = AABB::new_max_inv();
+    aabb.iter_grow(idxs.iter().map(|idx| ctx.aabbs[*idx]));
+
+    // Primitives go left if their centroid lands in a bin before the split
+    // When nothing splits them, like when every centroid is in the same spot, we just cut the list in half
+    let mut mid = match find_split(idxs, parent_bounds, ctx) {
+        Some((axis, split_bin)) => {
+            let mut left_len = 0;
+
+            for i in 0..idxs.len() {
+                if centroid_bin(ctx.centroids[idxs[i]], parent_bounds, axis) < split_bin {
+                    idxs.swap(i, left_len);
+                    left_len += 1;
+                }
+            }
+
+            left_len
+        },
+        None => idxs.len() / 2,
+    };
+
+    if mid == 0 || mid == idxs.len() {
+        mid = idxs.len() / 2;
+    }
+
+    let (left_idxs, right_idxs) = idxs.split_at_mut(mid);
+    let left_bounds = get_parent_bounds(left_idxs, ctx);
+    let right_bounds = get_parent_bounds(right_idxs, ctx);
+
+    BvhNode::Branch(BvhBranch {
+        left: Box::new(bvh_recurse(left_idxs, left_bounds, ctx)),
+        right: Box::new(bvh_recurse(right_idxs, right_bounds, ctx)),
+        aabb,
+    })
+}
+
+// Which of the BINS along an axis a centroid falls in, the centroid bounds are split evenly between them
+fn centroid_bin(centroid: Vec3, centroid_bounds: AABB, axis: usize) -> usize {
+    let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+    let offset = (centroid[axis] - centroid_bounds.min[axis]) / extent;
+
+    ((offset * BINS as f32) as usize).min(BINS - 1)
+}
+
+// Binned SAH, returns the axis and the first bin of the right side for the cheapest split
+// Cost is the surface area of each side times how many primitives are in it
+fn find_split(idxs: &[usize], centroid_bounds: AABB, ctx: RecurseCtx) -> Option<(usize, usize)> {
+    let mut best: Option<(f32, usize, usize)> = None;
+
+    for axis in 0..3 {
+        // Every centroid is on the same plane along this axis, so there's nothing to bin
+        if centroid_bounds.max[axis] <= centroid_bounds.min[axis] {
+            continue;
+        }
+
+        let mut bins = [BvhBin::default(); BINS];
+
+        for idx in idxs {
+            bins[centroid_bin(ctx.centroids[*idx], centroid_bounds, axis)].add(&ctx.aabbs[*idx]);
+        }
+
+        for split_bin in 1..BINS {
+            let (left, right) = bins.split_at(split_bin);
+            let cost = bin_cost(left) + bin_cost(right);
+
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, split_bin));
+            }
+        }
+    }
+
+    best.map(|(_, axis, split_bin)| (axis, split_bin))
+}
+
+fn bin_cost(bins: &[BvhBin]) -> f32 {
+    let mut bounds = AABB::new_max_inv();
+    let mut count = 0;
+
+    for bin in bins.iter().filter(|bin| bin.tri_count > 0) {
+        bounds.grow(&bin.bounds);
+        count += bin.tri_count;
+    }
+
+    if count == 0 {
+        return 0.0;
+    }
+
+    bounds.surface_area() * count as f32
 }
 
 fn get_parent_bounds(idxs: &[usize], ctx: RecurseCtx) -> AABB {
@@ -0,0 +1,215 @@
+use glam::Vec3;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+use crate::engine::bvh::AABB;
+use crate::engine::input::InputState;
+use crate::gpu::buffers::GpuUniformCamera;
+
+// End of imports
+
+// Stops the camera just short of looking straight up or down, where yaw stops meaning anything
+pub(crate) const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+// Direction the camera looks, using the pitch and yaw conventions from the README
+// Yaw 0 looks down +x and yaw pi/2 looks down -z, pitch 0 is level and pi/2 looks straight up
+pub(crate) fn camera_forward(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        pitch.cos() * yaw.cos(),
+        pitch.sin(),
+        -pitch.cos() * yaw.sin(),
+    )
+}
+
+// Level direction to the camera's right, movement sideways shouldn't depend on pitch
+pub(crate) fn camera_right(yaw: f32) -> Vec3 {
+    camera_forward(yaw, 0.0).cross(Vec3::Y)
+}
+
+// Built in ways of moving the camera from input, run every tick right before PhysicsLoop::update
+// Turn one on with LatrConfig::camera_controller or Engine::set_camera_controller
+#[derive(Clone, Debug, PartialEq)]
+pub enum CameraController {
+    Fly(FlyCamera),
+    Orbit(OrbitCamera),
+}
+
+impl CameraController {
+    pub fn fly() -> Self {
+        CameraController::Fly(FlyCamera::default())
+    }
+
+    pub fn orbit(target: OrbitTarget) -> Self {
+        CameraController::Orbit(OrbitCamera { target, ..Default::default() })
+    }
+
+    pub(crate) fn update(&mut self, camera: &mut GpuUniformCamera, input: &InputState, scene_bounds: Option<AABB>, dt: f32) {
+        match self {
+            CameraController::Fly(fly) => fly.update(camera, input, dt),
+            CameraController::Orbit(orbit) => orbit.update(camera, input, scene_bounds, dt),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlyKeys {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub up: KeyCode,
+    pub down: KeyCode,
+
+    // Held to move at FlyCamera::fast_multiplier times the speed
+    pub fast: KeyCode,
+}
+
+impl Default for FlyKeys {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            back: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            up: KeyCode::Space,
+            down: KeyCode::ShiftLeft,
+            fast: KeyCode::ControlLeft,
+        }
+    }
+}
+
+// WASD to move along where the camera is looking, the mouse to look around
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlyCamera {
+    // Units per second
+    pub speed: f32,
+    pub fast_multiplier: f32,
+
+    // Radians turned per unit of mouse movement
+    pub sensitivity: f32,
+
+    pub keys: FlyKeys,
+
+    // Mouse look only happens while this is held, None looks around whenever the mouse moves
+    pub look_button: Option<MouseButton>,
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self {
+            speed: 5.0,
+            fast_multiplier: 4.0,
+            sensitivity: 0.003,
+            keys: FlyKeys::default(),
+            look_button: Some(MouseButton::Right),
+        }
+    }
+}
+
+impl FlyCamera {
+    fn update(&mut self, camera: &mut GpuUniformCamera, input: &InputState, dt: f32) {
+        if self.look_button.is_none_or(|button| input.is_mouse_down(button)) {
+            let (dx, dy) = input.pass_mouse_delta();
+
+            // Moving the mouse right turns right, which is a smaller yaw, see camera_forward
+            camera.yaw -= dx * self.sensitivity;
+            camera.pitch = (camera.pitch - dy * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+
+        // 1 when only the positive key is held, -1 when only the negative one is, 0 otherwise
+        let axis = |positive: KeyCode, negative: KeyCode| {
+            (input.is_key_down(positive) as i32 - input.is_key_down(negative) as i32) as f32
+        };
+
+        let forward = camera_forward(camera.yaw, camera.pitch);
+        let right = camera_right(camera.yaw);
+
+        let direction = forward * axis(self.keys.forward, self.keys.back)
+            + right * axis(self.keys.right, self.keys.left)
+            + Vec3::Y * axis(self.keys.up, self.keys.down);
+
+        let mut speed = self.speed;
+        if input.is_key_down(self.keys.fast) {
+            speed *= self.fast_multiplier;
+        }
+
+        let pos = Vec3::from_array(camera.pos) + direction.normalize_or_zero() * speed * dt;
+        camera.pos = pos.to_array();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrbitTarget {
+    Point([f32; 3]),
+
+    // Center of every loaded model, with the starting distance fit so they're all in view
+    SceneBounds,
+}
+
+// Circles around a target, dragging the mouse to turn and scrolling to zoom
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrbitCamera {
+    pub target: OrbitTarget,
+
+    // Distance from the target, 0 picks one from the scene bounds on the first tick (or 5 if there are none)
+    pub distance: f32,
+
+    // Radians turned per unit of mouse movement
+    pub sensitivity: f32,
+
+    // Each line scrolled moves this fraction of the distance closer or further
+    pub zoom_speed: f32,
+
+    // Radians per second the camera turns on its own, for a turntable, 0 stays still
+    pub auto_rotate: f32,
+
+    // Turning only happens while this is held, None turns whenever the mouse moves
+    pub drag_button: Option<MouseButton>,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            target: OrbitTarget::SceneBounds,
+            distance: 0.0,
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+            auto_rotate: 0.0,
+            drag_button: Some(MouseButton::Left),
+        }
+    }
+}
+
+impl OrbitCamera {
+    const FALLBACK_DISTANCE: f32 = 5.0;
+    const MIN_DISTANCE: f32 = 0.01;
+
+    fn update(&mut self, camera: &mut GpuUniformCamera, input: &InputState, scene_bounds: Option<AABB>, dt: f32) {
+        let (target, radius) = match (self.target, scene_bounds) {
+            (OrbitTarget::Point(point), _) => (Vec3::from_array(point), None),
+            (OrbitTarget::SceneBounds, Some(bounds)) => ((bounds.min + bounds.max) * 0.5, Some((bounds.max - bounds.min).length() * 0.5)),
+            (OrbitTarget::SceneBounds, None) => (Vec3::ZERO, None),
+        };
+
+        if self.distance <= 0.0 {
+            // The view is 90 degrees tall, so a sphere fits when we're about 1.5 radii away
+            self.distance = radius.map_or(Self::FALLBACK_DISTANCE, |radius| radius * 1.5);
+        }
+
+        if self.drag_button.is_none_or(|button| input.is_mouse_down(button)) {
+            let (dx, dy) = input.pass_mouse_delta();
+
+            // Dragging right swings the camera left around the target, so the model appears to turn with the mouse
+            camera.yaw -= dx * self.sensitivity;
+            camera.pitch = (camera.pitch - dy * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+
+        camera.yaw += self.auto_rotate * dt;
+
+        let (_, scroll) = input.pass_scroll_delta();
+        self.distance = (self.distance * (1.0 - self.zoom_speed).powf(scroll)).max(Self::MIN_DISTANCE);
+
+        let pos = target - camera_forward(camera.yaw, camera.pitch) * self.distance;
+        camera.pos = pos.to_array();
+    }
+}
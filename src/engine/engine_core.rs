@@ -1,32 +1,240 @@
-use crate::gpu::buffers::GpuUniformCamera;
+use crate::gpu::buffers::{GpuBuffers, GpuUniformCamera};
+use crate::gpu::tick_interpolation::TickState;
+use crate::engine::lights::{Light, LightId, LightSet};
+use crate::engine::background::Background;
+use crate::engine::post::PostEffect;
+use crate::engine::export::{ExportFormat, ExportRequest};
+use crate::engine::input::{InputEvent, InputState};
+use crate::engine::camera::{CameraController, MAX_PITCH};
+use crate::engine::bvh::AABB;
+use crate::engine::timestep::{FixedTimestep, TickStats};
+use crate::engine::recording::{FrameExport, RecordedPass, Recorder, Recording};
+use crate::gpu::render_commands::RenderCommand;
 
 use std::{
+    any::Any,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
     thread,
     time::{Duration, Instant},
 };
-use crate::core::config::LatrConfig;
+use crate::core::config::{LatrConfig, RenderMode, ToneMapping};
 use crate::core::error::{EngineError, LatrError};
-use crate::gpu::buffers::GpuBuffers;
+use winit::event_loop::EventLoopProxy;
+use winit::keyboard::KeyCode;
+
+// How often export_frame checks whether exit was asked for while it waits on the render thread
+const EXPORT_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub trait PhysicsLoop {
     fn init(&mut self, en: &mut Engine) -> Result<(), LatrError>;
     fn update(&mut self, en: &mut Engine) -> Result<(), LatrError>;
+
+    // Called for every input event that came in since the last tick, right before update
+    // Engine::input already includes the event, so this is only needed to react to single presses and such
+    fn on_input(&mut self, en: &mut Engine, event: &InputEvent) -> Result<(), LatrError> {
+        let _ = (en, event);
+        Ok(())
+    }
 }
 
 // Methods open to the user return LatrErrors, methods only exposed to the engine return engine errors
 pub struct Engine {
     pub gpu_cam: GpuUniformCamera,
+
+    lights: LightSet,
+
+    // Used for changes that have to be made on the render thread, see RenderCommand
+    render_commands: Sender<RenderCommand>,
+
+    // Filled from the window's events at the start of every tick
+    input: InputState,
+    input_events: Receiver<InputEvent>,
+
+    // Moves gpu_cam from input each tick when set, see CameraController
+    camera_controller: Option<CameraController>,
+
+    // Box around every loaded model, None until the models are loaded or if there were none
+    scene_bounds: Option<AABB>,
+
+    // Decides how many ticks run each pass of the loop, and keeps track of simulated time
+    timestep: FixedTimestep,
+
+    // Where to record the session to, see LatrConfig::record
+    // The recorder itself is made once the loop starts, since the file starts with the tick length
+    record_path: Option<PathBuf>,
+    recorder: Option<Recorder>,
+
+    // Shared with the render thread, set by request_exit or when the window closes
+    exit_requested: Arc<AtomicBool>,
+
+    // Length of the post effect chain on the render thread, so a bad index is caught here
+    post_effect_count: usize,
+
+    // Limits of the render thread's device, environment maps get shrunk to fit them
+    // Defaults until the GpuCore is made, see set_device_limits
+    device_limits: wgpu::Limits,
+
+    // EventLoopProxy for whatever user event type the event loop carries, see event_loop_proxy
+    // Kept as Any so Engine and PhysicsLoop don't need to carry the type around
+    event_loop_proxy: Option<Box<dyn Any + Send>>,
 }
 
 impl Engine {
-    pub fn new(config: &LatrConfig) -> Result<Self, EngineError> {
-        let gpu_cam: GpuUniformCamera = GpuUniformCamera::default();
+    pub(crate) fn new(
+        config: &LatrConfig,
+        render_commands: Sender<RenderCommand>,
+        input_events: Receiver<InputEvent>,
+        exit_requested: Arc<AtomicBool>,
+    ) -> Result<Self, EngineError> {
+        let mut gpu_cam: GpuUniformCamera = GpuUniformCamera::default();
+        gpu_cam.pos = config.camera.pos;
+        gpu_cam.yaw = config.camera.yaw;
+        gpu_cam.pitch = config.camera.clamped_pitch();
 
         Ok(Self {
             gpu_cam,
+            lights: LightSet::default(),
+            render_commands,
+            input: InputState::default(),
+            input_events,
+            camera_controller: config.camera_controller.clone(),
+            scene_bounds: None,
+            timestep: FixedTimestep::new(Duration::ZERO, config.max_catch_up_ticks, Instant::now()),
+            record_path: config.record.clone(),
+            recorder: None,
+            exit_requested,
+            post_effect_count: config.post_effects.len(),
+            device_limits: wgpu::Limits::default(),
+            event_loop_proxy: None,
         })
     }
 
+    pub(crate) fn set_device_limits(&mut self, limits: wgpu::Limits) {
+        self.device_limits = limits;
+    }
+
+    pub(crate) fn set_event_loop_proxy<U: Send + 'static>(&mut self, proxy: EventLoopProxy<U>) {
+        self.event_loop_proxy = Some(Box::new(proxy));
+    }
+
+    // Sends user events to the render thread, where LatrEngine::on_user_event gets them
+    // U has to be the type the LatrEngine was made with, anything else gets None, as does running headless
+    // Hosts with their own event loop hand one over with LatrEngine::set_event_loop_proxy
+    pub fn event_loop_proxy<U: Send + 'static>(&self) -> Option<EventLoopProxy<U>> {
+        self.event_loop_proxy.as_ref()?.downcast_ref::<EventLoopProxy<U>>().cloned()
+    }
+
+    // Stops the physics loop after the current tick and closes the window
+    // LatrEngine::start then returns once both threads are done
+    pub fn request_exit(&self) {
+        self.exit_requested.store(true, Ordering::Release);
+    }
+
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested.load(Ordering::Acquire)
+    }
+
+    // Seconds of simulated time each update covers, this is always the same no matter the time scale
+    pub fn dt(&self) -> f32 {
+        self.timestep.tick().as_secs_f32()
+    }
+
+    // Simulated time since the loop started, which stops while paused and follows the time scale
+    pub fn elapsed(&self) -> Duration {
+        self.timestep.elapsed()
+    }
+
+    // Number of updates that have finished
+    pub fn tick_count(&self) -> u64 {
+        self.timestep.tick_count()
+    }
+
+    // Stops calling update until resume, input and the camera controller keep running so the scene can still be looked around
+    pub fn pause(&mut self) {
+        self.timestep.set_paused(true);
+    }
+
+    pub fn resume(&mut self) {
+        self.timestep.set_paused(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.timestep.is_paused()
+    }
+
+    // Runs a single update on the next pass while paused, does nothing otherwise
+    pub fn step_once(&mut self) {
+        self.timestep.step_once();
+    }
+
+    // 0.5 runs updates half as often, 2 runs them twice as often, dt stays the same either way
+    // Big scales are limited by LatrConfig::max_catch_up_ticks
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.timestep.set_time_scale(time_scale);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.timestep.time_scale()
+    }
+
+    // How often the loop fell behind, and how many ticks it had to drop
+    pub fn tick_stats(&self) -> TickStats {
+        self.timestep.stats()
+    }
+
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
+
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.input.is_key_down(key)
+    }
+
+    // How far the mouse moved since the last tick, in raw mouse units
+    pub fn mouse_delta(&self) -> (f32, f32) {
+        self.input.mouse_delta()
+    }
+
+    // Replaces the camera controller, None goes back to moving the camera by hand
+    pub fn set_camera_controller(&mut self, controller: Option<CameraController>) {
+        self.camera_controller = controller;
+    }
+
+    // For changing the settings of the running controller, like its speed or orbit target
+    pub fn camera_controller_mut(&mut self) -> Option<&mut CameraController> {
+        self.camera_controller.as_mut()
+    }
+
+    // Radians, pitch gets clamped just short of straight up and down, see the math conventions in the README
+    pub fn set_camera_rotation(&mut self, yaw: f32, pitch: f32) {
+        self.gpu_cam.yaw = yaw;
+        self.gpu_cam.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn rotate_camera(&mut self, d_yaw: f32, d_pitch: f32) {
+        self.set_camera_rotation(self.gpu_cam.yaw + d_yaw, self.gpu_cam.pitch + d_pitch);
+    }
+
+    // Min and max corners of the box around every loaded model
+    pub fn scene_bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        self.scene_bounds.map(|bounds| (bounds.min.to_array(), bounds.max.to_array()))
+    }
+
+    pub(crate) fn set_scene_bounds(&mut self, bounds: Option<AABB>) {
+        self.scene_bounds = bounds;
+    }
+
+    // The models are only in the cpu copy after build_blas, the render thread puts them on its device
+    pub(crate) fn upload_scene(&self) -> Result<(), LatrError> {
+        self.send_render_command(RenderCommand::UploadScene)
+    }
+
     pub fn move_camera(&mut self, dx: f32, dy: f32, dz: f32) {
         let pos = &mut self.gpu_cam.pos;
 
@@ -35,44 +243,294 @@ impl Engine {
         pos[2] += dz;
     }
 
+    pub fn add_light(&mut self, light: Light) -> Result<LightId, LatrError> {
+        if self.lights.len() as u64 >= GpuBuffers::LIGHT_START {
+            return Err(EngineError::BufferFull("light").into());
+        }
+
+        Ok(self.lights.add(light))
+    }
+
+    // Replaces a light entirely, for changing things like color or cone angles
+    pub fn set_light(&mut self, id: LightId, light: Light) -> Result<(), LatrError> {
+        let slot = self.lights.get_mut(id).ok_or(EngineError::LightNotFound(id.0))?;
+        *slot = light;
+
+        Ok(())
+    }
+
+    pub fn move_light(&mut self, id: LightId, x: f32, y: f32, z: f32) -> Result<(), LatrError> {
+        let light = self.lights.get_mut(id).ok_or(EngineError::LightNotFound(id.0))?;
+        light.set_pos([x, y, z]);
+
+        Ok(())
+    }
+
+    pub fn remove_light(&mut self, id: LightId) -> Result<Light, LatrError> {
+        let light = self.lights.remove(id).ok_or(EngineError::LightNotFound(id.0))?;
+
+        Ok(light)
+    }
+
+    // Environment maps are loaded right here on the engine thread, so a big map will hold up the tick
+    pub fn set_background(&mut self, background: Background) -> Result<(), LatrError> {
+        let prepared = background.prepare(self.device_limits.max_texture_dimension_2d)?;
+
+        self.send_render_command(RenderCommand::Background(Box::new(prepared)))
+    }
+
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) -> Result<(), LatrError> {
+        self.send_render_command(RenderCommand::ToneMapping(tone_mapping))
+    }
+
+    // In stops, so +1 is twice as bright and -1 is half as bright
+    pub fn set_exposure(&mut self, exposure: f32) -> Result<(), LatrError> {
+        self.send_render_command(RenderCommand::Exposure(exposure))
+    }
+
+    // Switches between normal shading and the debug views
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) -> Result<(), LatrError> {
+        self.send_render_command(RenderCommand::RenderMode(render_mode))
+    }
+
+    // Turns the extra albedo, normal, depth, position and instance id targets on or off
+    pub fn set_aovs(&mut self, enabled: bool) -> Result<(), LatrError> {
+        self.send_render_command(RenderCommand::Aovs(enabled))
+    }
+
+    // Replaces the whole post effect chain, LUTs are loaded right here like environment maps
+    pub fn set_post_effects(&mut self, effects: &[PostEffect]) -> Result<(), LatrError> {
+        let max_lut_size = self.device_limits.max_texture_dimension_3d;
+        let prepared = effects.iter().map(|effect| effect.prepare(max_lut_size)).collect::<Result<Vec<_>, _>>()?;
+        self.post_effect_count = prepared.len();
+
+        self.send_render_command(RenderCommand::PostEffects(prepared))
+    }
+
+    // Swaps out the effect at this place in the chain, changing only the numbers of an effect keeps its pipelines
+    pub fn set_post_effect(&mut self, index: usize, effect: PostEffect) -> Result<(), LatrError> {
+        if index >= self.post_effect_count {
+            return Err(EngineError::NoPostEffect(index).into());
+        }
+
+        let prepared = effect.prepare(self.device_limits.max_texture_dimension_3d)?;
+
+        self.send_render_command(RenderCommand::PostEffect(index, Box::new(prepared)))
+    }
+
+    // Writes over the first params of an effect, what a PostPass reads with param(0) and on
+    // Anything past the end of values keeps what it was
+    pub fn set_post_params(&mut self, index: usize, values: &[f32]) -> Result<(), LatrError> {
+        if index >= self.post_effect_count {
+            return Err(EngineError::NoPostEffect(index).into());
+        }
+
+        if values.len() > PostEffect::MAX_PARAMS {
+            return Err(EngineError::TooManyPostParams(format!("at index {index}"), values.len()).into());
+        }
+
+        self.send_render_command(RenderCommand::PostParams(index, values.to_vec()))
+    }
+
+    // Saves the next frame that gets drawn, as linear hdr color before exposure and tone mapping
+    // The aov layers are only written when aovs are turned on
+    // This blocks the tick until the render thread has drawn and read back the frame, so it's meant for offline use
+    // Fails with GpuError::ExportFrameSkipped if the frame isn't drawn, like while the window is minimized
+    pub fn export_frame<P: AsRef<Path>>(&mut self, path: P, format: ExportFormat) -> Result<(), LatrError> {
+        let (reply, receiver) = mpsc::channel();
+        self.send_render_command(RenderCommand::Export(ExportRequest { reply }))?;
+
+        // The render thread may be shutting down without getting to the request, so we stop waiting once exit is asked for
+        let capture = loop {
+            match receiver.recv_timeout(EXPORT_POLL_INTERVAL) {
+                Ok(capture) => break capture?,
+                Err(RecvTimeoutError::Timeout) if !self.exit_requested() => continue,
+                Err(_) => return Err(EngineError::RenderThreadClosed.into()),
+            }
+        };
+
+        capture.write(path.as_ref(), format)?;
+
+        Ok(())
+    }
+
+    fn send_render_command(&self, command: RenderCommand) -> Result<(), LatrError> {
+        self.render_commands
+            .send(command)
+            .map_err(|_| EngineError::RenderThreadClosed)?;
+
+        Ok(())
+    }
+
+    // Takes in everything the window sent since the last tick, updating the snapshot before handing each event to the state
+    fn process_input<T: PhysicsLoop>(&mut self, state: &mut T, events: &[InputEvent]) -> Result<(), LatrError> {
+        self.input.begin_pass();
+
+        for event in events {
+            self.input.apply(event);
+            state.on_input(self, event)?;
+        }
+
+        Ok(())
+    }
+
+    // Hands everything the user could have changed this tick over to the render thread, which writes it to the gpu
+    fn sync_gpu(&mut self) -> Result<(), LatrError> {
+        let lights = self.lights.dirty.then(|| self.lights.to_gpu());
+        self.lights.dirty = false;
+
+        self.send_render_command(RenderCommand::Tick(TickState {
+            camera: self.gpu_cam,
+            lights,
+            published: Instant::now(),
+        }))
+    }
+
     pub fn start_physics_loop<T: PhysicsLoop + 'static>(
         &mut self,
         state: T,
         tps /* Tick rate per second of loop */: u32,
-        buffers: &mut GpuBuffers,
-        queue: &wgpu::Queue,
     ) -> Result<(), LatrError> {
         let mut state = state;
 
-        let tick_duration = Duration::from_secs_f64(1f64 / tps as f64);
+        self.timestep.set_tick(Duration::from_secs_f64(1f64 / tps as f64));
+
+        if let Some(path) = &self.record_path {
+            self.recorder = Some(Recorder::create(path, self.timestep.tick())?);
+        }
 
         {
             state.init(self)?;
         }
 
-        loop {
+        self.sync_gpu()?;
+
+        let mut last_pass = Instant::now();
+        self.timestep.start(last_pass);
+
+        while !self.exit_requested() {
             // Gets the start of the loop
-            let loop_start = Instant::now();
-
-            // Run the actual physics of the loop
-            // This needs to be nested because borrowing rules and such (it's a mutable reference to self)
-            {
-                state.update(self)?;
-            };
-
-            //println!("Engine loop time: {:?}", loop_start.elapsed());
-            //println!("{}", self.engine_params.camera.pos[0]);
-
-            // Gets the time elapsed while running physics
-            let loop_duration = loop_start.elapsed();
-
-            // Subtracts the tick time from the time spent
-            // If the time spent is greater, then it returns none, and we don't pause
-            // If the time spent is less, we only pause for the extra bit as to not waste time
-            match tick_duration.checked_sub(loop_duration) {
-                Some(time) => thread::sleep(time),
-                None => println!("Lag"),
+            let pass_start = Instant::now();
+
+            match self.pass(&mut state, pass_start - last_pass) {
+                Ok(()) => {},
+
+                // The window closing in the middle of a tick drops the render thread's end of the channel
+                // That's just us shutting down, not something the user did wrong
+                Err(LatrError::Engine(EngineError::RenderThreadClosed)) if self.exit_requested() => break,
+
+                Err(e) => return Err(e),
+            }
+
+            last_pass = pass_start;
+            self.timestep.pass_finished(pass_start.elapsed());
+
+            // Only sleep for what's left until the next tick is due, which is nothing if we're behind
+            thread::sleep(self.timestep.time_until_next(Instant::now()));
+        }
+
+        Ok(())
+    }
+
+    // Runs however many ticks are due with the input that came in since the last pass
+    fn pass<T: PhysicsLoop>(
+        &mut self,
+        state: &mut T,
+        real_dt: Duration,
+    ) -> Result<(), LatrError> {
+        let ticks = self.timestep.ticks_due(Instant::now());
+        let events: Vec<InputEvent> = self.input_events.try_iter().collect();
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&RecordedPass { real_dt_nanos: real_dt.as_nanos() as u64, ticks, events: events.clone() })?;
+        }
+
+        self.run_pass(state, &events, ticks, real_dt)
+    }
+
+    // Runs the loop over again from a recording, as fast as it can with no sleeping
+    // Every pass gets the same input, ticks and camera time it got when it was recorded, so given the same
+    // starting state the PhysicsLoop ends up in the same place
+    pub(crate) fn replay<T: PhysicsLoop>(&mut self, state: T, recording: &Recording, exports: &[FrameExport]) -> Result<(), LatrError> {
+        let mut state = state;
+
+        self.timestep.set_tick(recording.tick());
+
+        state.init(self)?;
+        self.sync_gpu()?;
+
+        let mut exports: Vec<&FrameExport> = exports.iter().collect();
+        exports.sort_by_key(|export| export.tick);
+        let mut exports = exports.into_iter().peekable();
+
+        self.export_due(&mut exports)?;
+
+        for pass in &recording.passes {
+            if self.exit_requested() {
+                break;
+            }
+
+            let mut events = pass.events.as_slice();
+            let mut real_dt = Duration::from_nanos(pass.real_dt_nanos);
+            let mut ticks_left = pass.ticks;
+
+            // Passes get split at export ticks so the frame shows exactly that tick
+            // The rest of the pass gets no new input or camera time, which runs the same as if it wasn't split
+            loop {
+                let until_export = exports.peek().map_or(u64::MAX, |export| export.tick - self.tick_count());
+                let ticks = (ticks_left as u64).min(until_export) as u32;
+
+                self.run_pass(&mut state, events, ticks, real_dt)?;
+                self.export_due(&mut exports)?;
+
+                ticks_left -= ticks;
+                events = &[];
+                real_dt = Duration::ZERO;
+
+                if ticks_left == 0 {
+                    break;
+                }
             }
         }
+
+        Ok(())
+    }
+
+    // Saves every export whose tick we've reached, exports are sorted by tick
+    fn export_due<'a, I: Iterator<Item = &'a FrameExport>>(&mut self, exports: &mut Peekable<I>) -> Result<(), LatrError> {
+        let tick_count = self.tick_count();
+
+        while let Some(export) = exports.next_if(|export| export.tick <= tick_count) {
+            self.export_frame(&export.path, export.format)?;
+        }
+
+        Ok(())
+    }
+
+    // Input and the camera controller run once a pass even when no ticks are due, so they work while paused
+    fn run_pass<T: PhysicsLoop>(
+        &mut self,
+        state: &mut T,
+        events: &[InputEvent],
+        ticks: u32,
+        real_dt: Duration,
+    ) -> Result<(), LatrError> {
+        self.process_input(state, events)?;
+
+        // The camera moves in real time, so looking around isn't slowed down by the time scale
+        if let Some(controller) = &mut self.camera_controller {
+            controller.update(&mut self.gpu_cam, &self.input, self.scene_bounds, real_dt.as_secs_f32());
+        }
+
+        for _ in 0..ticks {
+            state.update(self)?;
+            self.timestep.ticks_ran(1);
+
+            // Only the first tick to run sees the mouse and scroll movement, or catching up would count it twice
+            // Passes with no tick due leave it for whichever tick runs next
+            self.input.begin_tick();
+        }
+
+        self.sync_gpu()
     }
 }
\ No newline at end of file
@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use exr::prelude::{
+    AnyChannel,
+    AnyChannels,
+    Encoding,
+    FlatSamples,
+    Image,
+    ImageAttributes,
+    IntegerBounds,
+    Layer,
+    LayerAttributes,
+    SmallVec,
+    Vec2,
+    WritableImage,
+};
+
+use crate::core::error::{EngineError, GpuError};
+
+// End of imports
+
+// How Engine::export_frame lays out the files it writes
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub enum ExportFormat {
+    #[default]
+    // Every layer as its own part of a single .exr file
+    MultiLayerExr,
+
+    // One .exr per layer, named <name>_<layer>.exr next to the given path
+    SeparateExr,
+}
+
+// Raw pixels of a frame, read back from the render thread
+// The aov layers are only there when aovs are turned on, see LatrConfig::aovs
+pub(crate) struct FrameCapture {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+
+    // Linear hdr color, before exposure and tone mapping
+    pub(crate) beauty: Vec<[f32; 4]>,
+
+    // Same packing as the aov textures, see AovTextures
+    pub(crate) albedo: Option<Vec<[f32; 4]>>,
+    pub(crate) normal_depth: Option<Vec<[f32; 4]>>,
+    pub(crate) position_id: Option<Vec<[f32; 4]>>,
+}
+
+// Named channels of one layer, like ("R", samples)
+pub(crate) type LayerChannels = Vec<(&'static str, Vec<f32>)>;
+
+// Sent to the render thread, which answers once the next frame is drawn
+pub(crate) struct ExportRequest {
+    pub(crate) reply: Sender<Result<FrameCapture, GpuError>>,
+}
+
+impl FrameCapture {
+    // Splits the packed textures back out into the layers a compositor expects
+    // Channels are listed in the order they were packed, exr sorts them by name when writing
+    pub(crate) fn layers(&self) -> Vec<(&'static str, LayerChannels)> {
+        let channel = |pixels: &[[f32; 4]], i: usize| pixels.iter().map(|p| p[i]).collect::<Vec<f32>>();
+
+        let mut layers = vec![
+            ("beauty", vec![
+                ("R", channel(&self.beauty, 0)),
+                ("G", channel(&self.beauty, 1)),
+                ("B", channel(&self.beauty, 2)),
+                ("A", channel(&self.beauty, 3)),
+            ]),
+        ];
+
+        if let Some(albedo) = &self.albedo {
+            layers.push(("albedo", vec![
+                ("R", channel(albedo, 0)),
+                ("G", channel(albedo, 1)),
+                ("B", channel(albedo, 2)),
+            ]));
+        }
+
+        if let Some(normal_depth) = &self.normal_depth {
+            layers.push(("normal", vec![
+                ("X", channel(normal_depth, 0)),
+                ("Y", channel(normal_depth, 1)),
+                ("Z", channel(normal_depth, 2)),
+            ]));
+            layers.push(("depth", vec![("Z", channel(normal_depth, 3))]));
+        }
+
+        if let Some(position_id) = &self.position_id {
+            layers.push(("position", vec![
+                ("X", channel(position_id, 0)),
+                ("Y", channel(position_id, 1)),
+                ("Z", channel(position_id, 2)),
+            ]));
+            layers.push(("instance_id", vec![("id", channel(position_id, 3))]));
+        }
+
+        layers
+    }
+
+    pub(crate) fn write(&self, path: &Path, format: ExportFormat) -> Result<(), EngineError> {
+        let layers = self.layers();
+
+        match format {
+            ExportFormat::MultiLayerExr => {
+                let exr_layers = layers.into_iter()
+                    .map(|(name, channels)| self.to_exr_layer(name, channels))
+                    .collect();
+
+                self.write_exr(path, exr_layers)
+            },
+            ExportFormat::SeparateExr => {
+                for (name, channels) in layers {
+                    let exr_layer = self.to_exr_layer(name, channels);
+                    self.write_exr(&separate_layer_path(path, name), vec![exr_layer])?;
+                }
+
+                Ok(())
+            },
+        }
+    }
+
+    fn to_exr_layer(&self, name: &str, channels: LayerChannels) -> Layer<AnyChannels<FlatSamples>> {
+        let channels: SmallVec<[AnyChannel<FlatSamples>; 4]> = channels.into_iter()
+            .map(|(channel_name, samples)| AnyChannel::new(channel_name, FlatSamples::F32(samples)))
+            .collect();
+
+        Layer::new(
+            self.size(),
+            LayerAttributes::named(name),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        )
+    }
+
+    fn write_exr(&self, path: &Path, layers: Vec<Layer<AnyChannels<FlatSamples>>>) -> Result<(), EngineError> {
+        let attributes = ImageAttributes::new(IntegerBounds::from_dimensions(self.size()));
+
+        Image::from_layers(attributes, layers)
+            .write()
+            .to_file(path)
+            .map_err(|e| EngineError::FrameExport(path.to_path_buf(), e))
+    }
+
+    fn size(&self) -> Vec2<usize> {
+        Vec2(self.width as usize, self.height as usize)
+    }
+}
+
+// frame.exr with the albedo layer becomes frame_albedo.exr
+pub(crate) fn separate_layer_path(path: &Path, layer: &str) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+
+    path.with_file_name(format!("{stem}_{layer}.exr"))
+}
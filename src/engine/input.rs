@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+// End of imports
+
+// Input forwarded from the window over to the physics thread
+// Positions and deltas are in physical pixels
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key {
+        key: KeyCode,
+        pressed: bool,
+
+        // Repeats come from holding a key down, they don't change what's held
+        repeat: bool,
+    },
+
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+
+    // Position of the cursor inside the window, from the top left corner
+    CursorMoved {
+        x: f32,
+        y: f32,
+    },
+
+    // Raw mouse movement, which keeps coming when the cursor hits the edge of the screen, good for mouse look
+    MouseMotion {
+        dx: f32,
+        dy: f32,
+    },
+
+    // Positive y is scrolling up, in lines for a normal wheel
+    Scroll {
+        dx: f32,
+        dy: f32,
+    },
+
+    Focused(bool),
+}
+
+// Snapshot of the input as of the current tick, see Engine::input
+// Deltas add up everything since the last tick, even over passes of the loop that didn't run one
+#[derive(Clone, Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<KeyCode>,
+    buttons_down: HashSet<MouseButton>,
+    cursor_pos: Option<(f32, f32)>,
+    mouse_delta: (f32, f32),
+    scroll_delta: (f32, f32),
+
+    // Same as above but only since the last pass, the camera controller moves every pass rather than every tick
+    pass_mouse_delta: (f32, f32),
+    pass_scroll_delta: (f32, f32),
+
+    focused: bool,
+}
+
+impl InputState {
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    // None until the cursor has moved over the window
+    pub fn cursor_pos(&self) -> Option<(f32, f32)> {
+        self.cursor_pos
+    }
+
+    pub fn mouse_delta(&self) -> (f32, f32) {
+        self.mouse_delta
+    }
+
+    pub fn scroll_delta(&self) -> (f32, f32) {
+        self.scroll_delta
+    }
+
+    pub(crate) fn pass_mouse_delta(&self) -> (f32, f32) {
+        self.pass_mouse_delta
+    }
+
+    pub(crate) fn pass_scroll_delta(&self) -> (f32, f32) {
+        self.pass_scroll_delta
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    // Deltas only cover a single tick, so they're cleared once a tick has seen them
+    pub(crate) fn begin_tick(&mut self) {
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = (0.0, 0.0);
+    }
+
+    // Cleared before each batch of events
+    pub(crate) fn begin_pass(&mut self) {
+        self.pass_mouse_delta = (0.0, 0.0);
+        self.pass_scroll_delta = (0.0, 0.0);
+    }
+
+    pub(crate) fn apply(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Key { key, pressed, .. } => {
+                if pressed {
+                    self.keys_down.insert(key);
+                } else {
+                    self.keys_down.remove(&key);
+                }
+            },
+            InputEvent::MouseButton { button, pressed } => {
+                if pressed {
+                    self.buttons_down.insert(button);
+                } else {
+                    self.buttons_down.remove(&button);
+                }
+            },
+            InputEvent::CursorMoved { x, y } => self.cursor_pos = Some((x, y)),
+            InputEvent::MouseMotion { dx, dy } => {
+                self.mouse_delta.0 += dx;
+                self.mouse_delta.1 += dy;
+                self.pass_mouse_delta.0 += dx;
+                self.pass_mouse_delta.1 += dy;
+            },
+            InputEvent::Scroll { dx, dy } => {
+                self.scroll_delta.0 += dx;
+                self.scroll_delta.1 += dy;
+                self.pass_scroll_delta.0 += dx;
+                self.pass_scroll_delta.1 += dy;
+            },
+            InputEvent::Focused(focused) => {
+                self.focused = focused;
+
+                // Releases that happen while unfocused never reach us, so anything held would get stuck down
+                if !focused {
+                    self.keys_down.clear();
+                    self.buttons_down.clear();
+                }
+            },
+        }
+    }
+}
+
+// Turns the winit events we care about into InputEvents, anything else gives None
+pub(crate) fn from_window_event(event: &winit::event::WindowEvent) -> Option<InputEvent> {
+    use winit::event::{ElementState, MouseScrollDelta, WindowEvent};
+    use winit::keyboard::PhysicalKey;
+
+    // Pixel scrolling (touchpads) gets turned into lines at roughly the size of a line of text
+    const PIXELS_PER_LINE: f32 = 20.0;
+
+    match event {
+        WindowEvent::KeyboardInput { event, .. } => match event.physical_key {
+            PhysicalKey::Code(key) => Some(InputEvent::Key {
+                key,
+                pressed: event.state == ElementState::Pressed,
+                repeat: event.repeat,
+            }),
+            PhysicalKey::Unidentified(_) => None,
+        },
+        WindowEvent::MouseInput { state, button, .. } => Some(InputEvent::MouseButton {
+            button: *button,
+            pressed: *state == ElementState::Pressed,
+        }),
+        WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved {
+            x: position.x as f32,
+            y: position.y as f32,
+        }),
+        WindowEvent::MouseWheel { delta, .. } => {
+            let (dx, dy) = match delta {
+                MouseScrollDelta::LineDelta(dx, dy) => (*dx, *dy),
+                MouseScrollDelta::PixelDelta(pos) => (pos.x as f32 / PIXELS_PER_LINE, pos.y as f32 / PIXELS_PER_LINE),
+            };
+
+            Some(InputEvent::Scroll { dx, dy })
+        },
+        WindowEvent::Focused(focused) => Some(InputEvent::Focused(*focused)),
+        _ => None,
+    }
+}
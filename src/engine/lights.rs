@@ -0,0 +1,226 @@
+use bytemuck::Zeroable;
+use glam::Vec3;
+
+use crate::gpu::buffers::{
+    GpuStorageAreaLight,
+    GpuStorageLight,
+    GpuStorageTriangleData,
+    GpuStorageVertex,
+};
+
+// End of imports
+
+// Lights that are placed by hand through the engine
+// Emissive triangles are handled separately as area lights, see collect_area_lights
+// Angles are in radians, directions don't need to be normalized
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    Point {
+        pos: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+
+        // Distance the light fades out at, 0 means the light reaches forever
+        range: f32,
+    },
+
+    // Light that comes from infinitely far away, like the sun
+    Directional {
+        dir: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+    },
+
+    Spot {
+        pos: [f32; 3],
+        dir: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+
+        // Full intensity inside the inner angle, fading out to nothing at the outer angle
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl Light {
+    pub fn point(pos: [f32; 3], color: [f32; 3], intensity: f32) -> Self {
+        Light::Point { pos, color, intensity, range: 0.0 }
+    }
+
+    pub fn directional(dir: [f32; 3], color: [f32; 3], intensity: f32) -> Self {
+        Light::Directional { dir, color, intensity }
+    }
+
+    pub fn spot(pos: [f32; 3], dir: [f32; 3], color: [f32; 3], intensity: f32, inner_angle: f32, outer_angle: f32) -> Self {
+        Light::Spot { pos, dir, color, intensity, range: 0.0, inner_angle, outer_angle }
+    }
+
+    // Directional lights have no position, so moving them does nothing
+    pub(crate) fn set_pos(&mut self, new_pos: [f32; 3]) {
+        match self {
+            Light::Point { pos, .. } | Light::Spot { pos, .. } => *pos = new_pos,
+            Light::Directional { .. } => (),
+        }
+    }
+
+    pub(crate) fn to_gpu(self) -> GpuStorageLight {
+        let mut gpu_light = GpuStorageLight::zeroed();
+
+        match self {
+            Light::Point { pos, color, intensity, range } => {
+                gpu_light.kind = GpuStorageLight::KIND_POINT;
+                gpu_light.position = pos;
+                gpu_light.color = color;
+                gpu_light.intensity = intensity;
+                gpu_light.range = range;
+            },
+            Light::Directional { dir, color, intensity } => {
+                gpu_light.kind = GpuStorageLight::KIND_DIRECTIONAL;
+                gpu_light.direction = Vec3::from_array(dir).normalize_or_zero().to_array();
+                gpu_light.color = color;
+                gpu_light.intensity = intensity;
+            },
+            Light::Spot { pos, dir, color, intensity, range, inner_angle, outer_angle } => {
+                gpu_light.kind = GpuStorageLight::KIND_SPOT;
+                gpu_light.position = pos;
+                gpu_light.direction = Vec3::from_array(dir).normalize_or_zero().to_array();
+                gpu_light.color = color;
+                gpu_light.intensity = intensity;
+                gpu_light.range = range;
+
+                // The shader only ever compares against the cosine, so we do the trig once here
+                gpu_light.cos_inner = inner_angle.cos();
+                gpu_light.cos_outer = outer_angle.cos();
+            },
+        }
+
+        gpu_light
+    }
+}
+
+// Handle returned when adding a light, used to move or remove it later
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LightId(pub(crate) u32);
+
+// Lights are stored in slots so ids stay valid when other lights are removed
+// Removed slots get reused by the next light that is added
+#[derive(Default)]
+pub(crate) struct LightSet {
+    slots: Vec<Option<Light>>,
+    free_slots: Vec<u32>,
+
+    // Set whenever a light changes, so we only rewrite the buffer when we have to
+    pub(crate) dirty: bool,
+}
+
+impl LightSet {
+    pub(crate) fn add(&mut self, light: Light) -> LightId {
+        self.dirty = true;
+
+        match self.free_slots.pop() {
+            Some(slot) => {
+                self.slots[slot as usize] = Some(light);
+                LightId(slot)
+            },
+            None => {
+                self.slots.push(Some(light));
+                LightId(self.slots.len() as u32 - 1)
+            },
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, id: LightId) -> Option<&mut Light> {
+        let light = self.slots.get_mut(id.0 as usize)?.as_mut()?;
+        self.dirty = true;
+
+        Some(light)
+    }
+
+    pub(crate) fn remove(&mut self, id: LightId) -> Option<Light> {
+        let light = self.slots.get_mut(id.0 as usize)?.take()?;
+
+        self.free_slots.push(id.0);
+        self.dirty = true;
+
+        Some(light)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.slots.len() - self.free_slots.len()
+    }
+
+    // The gpu doesn't care about ids, so we just pack the lights that exist
+    pub(crate) fn to_gpu(&self) -> Vec<GpuStorageLight> {
+        self.slots.iter()
+            .flatten()
+            .map(|light| light.to_gpu())
+            .collect()
+    }
+}
+
+// Goes through a batch of triangles and pulls out every one that emits light
+// Triangle indices are offset by triangle_base so they match where the batch sits in the triangle buffer
+// The cdf of each light is left as its raw power, use finish_area_lights once every batch is collected
+pub(crate) fn collect_area_lights(
+    triangles: &[GpuStorageTriangleData],
+    vertices: &[GpuStorageVertex],
+    vertex_base: u32,
+    triangle_base: u32,
+) -> Vec<GpuStorageAreaLight> {
+    let get_vertex = |idx: u32| {
+        let v = vertices[(idx - vertex_base) as usize];
+        Vec3::new(v.x, v.y, v.z)
+    };
+
+    triangles.iter()
+        .enumerate()
+        .filter(|(_, tri)| tri.emission[0] > 0.0 || tri.emission[1] > 0.0 || tri.emission[2] > 0.0)
+        .map(|(i, tri)| {
+            let [v0, v1, v2] = tri.vertices.map(get_vertex);
+            let area = 0.5 * (v1 - v0).cross(v2 - v0).length();
+            let emission = [tri.emission[0], tri.emission[1], tri.emission[2]];
+
+            GpuStorageAreaLight {
+                v0: v0.to_array(),
+                triangle: triangle_base + i as u32,
+                v1: v1.to_array(),
+                area,
+                v2: v2.to_array(),
+                cdf: luminance(emission) * area,
+                emission,
+                _pad: 0,
+            }
+        })
+        // Degenerate triangles can't be sampled, and would divide by zero in the shader
+        .filter(|light| light.area > 0.0)
+        .collect()
+}
+
+// Turns the raw powers left by collect_area_lights into a normalized running sum
+// Returns the total power, which the shader needs to get back each light's pick probability
+pub(crate) fn finish_area_lights(area_lights: &mut [GpuStorageAreaLight]) -> f32 {
+    let total: f32 = area_lights.iter().map(|light| light.cdf).sum();
+
+    if total <= 0.0 {
+        return 0.0;
+    }
+
+    let mut running = 0.0;
+    for light in area_lights.iter_mut() {
+        running += light.cdf;
+        light.cdf = running / total;
+    }
+
+    // Float error can leave the last entry just under 1, which would make the top of the range unpickable
+    if let Some(last) = area_lights.last_mut() {
+        last.cdf = 1.0;
+    }
+
+    total
+}
+
+fn luminance(rgb: [f32; 3]) -> f32 {
+    0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+}
@@ -2,4 +2,12 @@ pub mod engine_core;
 pub mod mesh;
 pub mod blas;
 pub mod bvh;
-pub mod mesh_file_parsers;
\ No newline at end of file
+pub mod mesh_file_parsers;
+pub mod lights;
+pub mod background;
+pub mod export;
+pub mod input;
+pub mod camera;
+pub mod timestep;
+pub mod recording;
+pub mod post;
\ No newline at end of file
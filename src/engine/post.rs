@@ -0,0 +1,287 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::core::error::EngineError;
+
+// End of imports
+
+const BLOOM_BLUR_WGSL: &str = include_str!("../shaders/post/bloom_blur.wgsl");
+const BLOOM_COMBINE_WGSL: &str = include_str!("../shaders/post/bloom_combine.wgsl");
+const VIGNETTE_WGSL: &str = include_str!("../shaders/post/vignette.wgsl");
+const CHROMATIC_ABERRATION_WGSL: &str = include_str!("../shaders/post/chromatic_aberration.wgsl");
+const SHARPEN_WGSL: &str = include_str!("../shaders/post/sharpen.wgsl");
+const COLOR_GRADE_WGSL: &str = include_str!("../shaders/post/color_grade.wgsl");
+
+// One step of the chain that runs between the trace and the blit, in the order they're listed in LatrConfig::post_effects
+// Everything here works on linear hdr color, before exposure and tone mapping
+// Can be changed later with Engine::set_post_effects, or one at a time with Engine::set_post_effect
+#[derive(Clone)]
+pub enum PostEffect {
+    // Light over the threshold bleeds into the pixels around it, radius is in pixels and tops out at 64
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        radius: f32,
+    },
+
+    // Darkens towards the corners, starting at radius, where 0 is the center and 1 is the corners
+    Vignette {
+        strength: f32,
+        radius: f32,
+    },
+
+    // Splits red and blue apart towards the edges, strength is how far they move at the corners as a fraction of the image
+    ChromaticAberration {
+        strength: f32,
+    },
+
+    Sharpen {
+        strength: f32,
+    },
+
+    // Grades with a 3d LUT from a .cube file, the kind most grading tools export
+    // Strength blends between the original at 0 and the fully graded image at 1
+    ColorGrade {
+        lut: PathBuf,
+        strength: f32,
+    },
+
+    // A pass of the user's own, see PostPass
+    Custom(Arc<dyn PostPass>),
+}
+
+// A post effect written in WGSL, added to the chain with PostEffect::Custom
+pub trait PostPass: Send + Sync {
+    // Used in errors, like when the shader doesn't compile
+    fn name(&self) -> &str;
+
+    // Has to define fn post(pixel: vec2<i32>, uv: vec2<f32>) -> vec4<f32>, which is run for every pixel
+    // It can use load_input(pixel), sample_input(uv), param(index) and frame.size, see the README for the rest
+    fn wgsl(&self) -> String;
+
+    // Starting values for param(0) and on, up to PostEffect::MAX_PARAMS of them
+    // Engine::set_post_params changes them later without rebuilding the pass
+    fn params(&self) -> Vec<f32> {
+        Vec::new()
+    }
+}
+
+impl PostEffect {
+    pub const MAX_PARAMS: usize = 16;
+
+    pub fn bloom() -> Self {
+        PostEffect::Bloom { threshold: 1.0, intensity: 0.5, radius: 16.0 }
+    }
+
+    pub fn vignette() -> Self {
+        PostEffect::Vignette { strength: 0.5, radius: 0.5 }
+    }
+
+    pub fn chromatic_aberration() -> Self {
+        PostEffect::ChromaticAberration { strength: 0.005 }
+    }
+
+    pub fn sharpen() -> Self {
+        PostEffect::Sharpen { strength: 0.25 }
+    }
+
+    pub fn color_grade<P: AsRef<Path>>(lut: P) -> Self {
+        PostEffect::ColorGrade { lut: lut.as_ref().to_path_buf(), strength: 1.0 }
+    }
+
+    pub fn custom<P: PostPass + 'static>(pass: P) -> Self {
+        PostEffect::Custom(Arc::new(pass))
+    }
+
+    // Loads the LUT for color grading and lays the numbers out the way the shaders read them
+    // Like backgrounds, this is done before anything goes to the render thread
+    // max_lut_size is the device's largest 3d texture, a bigger LUT is an error
+    pub(crate) fn prepare(&self, max_lut_size: u32) -> Result<PreparedPostEffect, EngineError> {
+        let mut prepared = PreparedPostEffect {
+            name: self.name().to_string(),
+            stages: Vec::new(),
+            params: [0.0; Self::MAX_PARAMS],
+            lut: None,
+        };
+
+        let values = match self {
+            PostEffect::Bloom { threshold, intensity, radius } => {
+                prepared.stages = vec![BLOOM_BLUR_WGSL.into(), BLOOM_COMBINE_WGSL.into()];
+                vec![*threshold, *intensity, *radius]
+            },
+            PostEffect::Vignette { strength, radius } => {
+                prepared.stages = vec![VIGNETTE_WGSL.into()];
+                vec![*strength, *radius]
+            },
+            PostEffect::ChromaticAberration { strength } => {
+                prepared.stages = vec![CHROMATIC_ABERRATION_WGSL.into()];
+                vec![*strength]
+            },
+            PostEffect::Sharpen { strength } => {
+                prepared.stages = vec![SHARPEN_WGSL.into()];
+                vec![*strength]
+            },
+            PostEffect::ColorGrade { lut, strength } => {
+                prepared.stages = vec![COLOR_GRADE_WGSL.into()];
+                prepared.lut = Some(Arc::new(CubeLut::load(lut, max_lut_size)?));
+
+                // The shader gets the LUT size from the texture, so every param is free for set_post_params
+                vec![*strength]
+            },
+            PostEffect::Custom(pass) => {
+                prepared.stages = vec![pass.wgsl().into()];
+                pass.params()
+            },
+        };
+
+        prepared.set_params(&values)?;
+
+        Ok(prepared)
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            PostEffect::Bloom { .. } => "bloom",
+            PostEffect::Vignette { .. } => "vignette",
+            PostEffect::ChromaticAberration { .. } => "chromatic aberration",
+            PostEffect::Sharpen { .. } => "sharpen",
+            PostEffect::ColorGrade { .. } => "color grade",
+            PostEffect::Custom(pass) => pass.name(),
+        }
+    }
+}
+
+// Custom passes can't be printed, so they show up by name
+impl fmt::Debug for PostEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostEffect::Bloom { threshold, intensity, radius } => f.debug_struct("Bloom")
+                .field("threshold", threshold)
+                .field("intensity", intensity)
+                .field("radius", radius)
+                .finish(),
+            PostEffect::Vignette { strength, radius } => f.debug_struct("Vignette")
+                .field("strength", strength)
+                .field("radius", radius)
+                .finish(),
+            PostEffect::ChromaticAberration { strength } => f.debug_struct("ChromaticAberration")
+                .field("strength", strength)
+                .finish(),
+            PostEffect::Sharpen { strength } => f.debug_struct("Sharpen")
+                .field("strength", strength)
+                .finish(),
+            PostEffect::ColorGrade { lut, strength } => f.debug_struct("ColorGrade")
+                .field("lut", lut)
+                .field("strength", strength)
+                .finish(),
+            PostEffect::Custom(pass) => f.debug_tuple("Custom").field(&pass.name()).finish(),
+        }
+    }
+}
+
+// Everything the render thread needs to build an effect's passes
+// Bloom has two stages, everything else has one, and each stage is run over the whole image
+#[derive(Clone)]
+pub(crate) struct PreparedPostEffect {
+    pub(crate) name: String,
+
+    // WGSL for each stage, without the prelude, see PostChain
+    pub(crate) stages: Vec<Arc<str>>,
+    pub(crate) params: [f32; PostEffect::MAX_PARAMS],
+    pub(crate) lut: Option<Arc<CubeLut>>,
+}
+
+impl PreparedPostEffect {
+    // Writes over the first values.len() params and leaves the rest
+    pub(crate) fn set_params(&mut self, values: &[f32]) -> Result<(), EngineError> {
+        if values.len() > PostEffect::MAX_PARAMS {
+            return Err(EngineError::TooManyPostParams(self.name.clone(), values.len()));
+        }
+
+        self.params[..values.len()].copy_from_slice(values);
+
+        Ok(())
+    }
+
+    // Whether the pipelines built for other can be kept for this, which is when only the params differ
+    // Every prepare loads the LUT again, so they're compared by what's in them
+    pub(crate) fn same_passes(&self, other: &PreparedPostEffect) -> bool {
+        let same_lut = match (&self.lut, &other.lut) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b) || a == b,
+            (None, None) => true,
+            _ => false,
+        };
+
+        self.stages == other.stages && same_lut
+    }
+}
+
+// A 3d LUT read from a .cube file, red changes fastest, then green, then blue
+#[derive(Debug, PartialEq)]
+pub(crate) struct CubeLut {
+    pub(crate) size: u32,
+    pub(crate) texels: Vec<[f32; 4]>,
+}
+
+impl CubeLut {
+    fn load(path: &Path, max_size: u32) -> Result<Self, EngineError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| EngineError::LutRead(path.to_path_buf(), e))?;
+
+        Self::parse(&contents, max_size).map_err(|reason| EngineError::LutParse(path.to_path_buf(), reason))
+    }
+
+    pub(crate) fn parse(contents: &str, max_size: u32) -> Result<Self, String> {
+        let mut size = None;
+        let mut texels = Vec::new();
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let first = words.next().unwrap_or_default();
+
+            match first {
+                "LUT_3D_SIZE" => {
+                    let value = words.next().and_then(|word| word.parse::<u32>().ok());
+                    let value = value.filter(|size| *size >= 2).ok_or_else(|| format!("invalid LUT_3D_SIZE line {line:?}"))?;
+
+                    if value > max_size {
+                        return Err(format!("a size of {value} is over the device's limit of {max_size}"));
+                    }
+
+                    size = Some(value);
+                },
+                "LUT_1D_SIZE" => return Err("1d LUTs aren't supported".to_string()),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let expected = if first == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    let values: Vec<f32> = words.filter_map(|word| word.parse().ok()).collect();
+
+                    if values.iter().any(|value| *value != expected) {
+                        return Err("only the default domain of 0 to 1 is supported".to_string());
+                    }
+                },
+                "TITLE" => {},
+                _ => {
+                    let values: Result<Vec<f32>, _> = line.split_whitespace().map(str::parse).collect();
+
+                    match values.as_deref() {
+                        Ok([r, g, b]) => texels.push([*r, *g, *b, 1.0]),
+                        _ => return Err(format!("invalid line {line:?}")),
+                    }
+                },
+            }
+        }
+
+        let size = size.ok_or("missing LUT_3D_SIZE")?;
+        let expected = (size as usize).checked_pow(3).ok_or_else(|| format!("a size of {size} is too big"))?;
+
+        if texels.len() != expected {
+            return Err(format!("expected {expected} entries for a size of {size}, found {}", texels.len()));
+        }
+
+        Ok(Self { size, texels })
+    }
+}
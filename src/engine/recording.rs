@@ -0,0 +1,107 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::{EngineError, LatrError};
+use crate::engine::export::ExportFormat;
+use crate::engine::input::InputEvent;
+
+// End of imports
+
+// Everything a PhysicsLoop got over a session, written by LatrConfig::record and played back with LatrEngine::replay
+// Stored as toml, with one [[pass]] table for every pass of the loop
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    // Length of a tick in nanoseconds, kept exact so replays get the same dt down to the bit
+    pub(crate) tick_nanos: u64,
+
+    #[serde(rename = "pass", default)]
+    pub(crate) passes: Vec<RecordedPass>,
+}
+
+// One pass of the loop, see Engine::start_physics_loop
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct RecordedPass {
+    // Real time since the last pass, which is what the camera controller moves by
+    pub(crate) real_dt_nanos: u64,
+
+    pub(crate) ticks: u32,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) events: Vec<InputEvent>,
+}
+
+impl Recording {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LatrError> {
+        let path = path.as_ref();
+
+        let contents = std::fs::read_to_string(path).map_err(|e| EngineError::Recording(path.to_path_buf(), e))?;
+        let recording = toml::from_str(&contents).map_err(|e| EngineError::RecordingParse(path.to_path_buf(), e))?;
+
+        Ok(recording)
+    }
+
+    pub fn tick(&self) -> Duration {
+        Duration::from_nanos(self.tick_nanos)
+    }
+
+    // Number of updates the session ran, the last tick a FrameExport can land on
+    pub fn tick_count(&self) -> u64 {
+        self.passes.iter().map(|pass| pass.ticks as u64).sum()
+    }
+}
+
+// A frame for LatrEngine::replay to save, once the given number of ticks have run
+// Tick 0 is right after PhysicsLoop::init
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameExport {
+    pub tick: u64,
+    pub path: PathBuf,
+    pub format: ExportFormat,
+}
+
+impl FrameExport {
+    pub fn new<P: AsRef<Path>>(tick: u64, path: P) -> Self {
+        Self { tick, path: path.as_ref().to_path_buf(), format: ExportFormat::default() }
+    }
+}
+
+// Writes passes out as they happen, so a session that crashes still has everything up to the crash
+pub(crate) struct Recorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+// Single pass wrapped up so toml writes it as a [[pass]] table, which can be appended to the end of the file
+#[derive(Serialize)]
+struct PassTable<'a> {
+    pass: [&'a RecordedPass; 1],
+}
+
+impl Recorder {
+    pub(crate) fn create(path: &Path, tick: Duration) -> Result<Self, EngineError> {
+        let file = File::create(path).map_err(|e| EngineError::Recording(path.to_path_buf(), e))?;
+
+        let mut recorder = Self { path: path.to_path_buf(), writer: BufWriter::new(file) };
+        recorder.write(&format!("tick_nanos = {}\n", tick.as_nanos()))?;
+
+        Ok(recorder)
+    }
+
+    pub(crate) fn record(&mut self, pass: &RecordedPass) -> Result<(), EngineError> {
+        let table = toml::to_string(&PassTable { pass: [pass] })?;
+
+        self.write(&format!("\n{table}"))
+    }
+
+    // Flushed every time, a pass sitting in the buffer would be lost in a crash
+    fn write(&mut self, text: &str) -> Result<(), EngineError> {
+        self.writer
+            .write_all(text.as_bytes())
+            .and_then(|()| self.writer.flush())
+            .map_err(|e| EngineError::Recording(self.path.clone(), e))
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+// End of imports
+
+// How the physics loop has been keeping up, see Engine::tick_stats
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TickStats {
+    // Passes of the loop that took longer than a tick, which puts us behind
+    pub overruns: u64,
+
+    // Ticks that were skipped because we were further behind than LatrConfig::max_catch_up_ticks
+    pub dropped_ticks: u64,
+
+    // Real time the last pass took, including every tick it caught up on
+    pub last_pass_time: Duration,
+    pub max_pass_time: Duration,
+}
+
+// Decides how many ticks the physics loop should run each time it wakes up
+// Time goes into an accumulator and comes out a whole tick at a time, so every update gets the same dt
+// When we fall behind, a few extra ticks get run to catch up, anything past that is dropped
+pub(crate) struct FixedTimestep {
+    tick: Duration,
+    max_catch_up: u32,
+
+    // Scaled time that hasn't been turned into ticks yet
+    accumulator: Duration,
+    last_update: Instant,
+
+    time_scale: f32,
+    paused: bool,
+
+    // Ticks asked for with step_once, only used while paused
+    pending_steps: u32,
+
+    tick_count: u64,
+    stats: TickStats,
+}
+
+impl FixedTimestep {
+    // The tick length is set once the physics loop starts, see set_tick
+    pub(crate) fn new(tick: Duration, max_catch_up: u32, now: Instant) -> Self {
+        Self {
+            tick,
+            max_catch_up: max_catch_up.max(1),
+            accumulator: Duration::ZERO,
+            last_update: now,
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+            tick_count: 0,
+            stats: TickStats::default(),
+        }
+    }
+
+    pub(crate) fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    pub(crate) fn set_tick(&mut self, tick: Duration) {
+        self.tick = tick;
+    }
+
+    // Starts counting time from now, so a slow PhysicsLoop::init doesn't turn into catch up ticks
+    pub(crate) fn start(&mut self, now: Instant) {
+        self.last_update = now;
+        self.accumulator = Duration::ZERO;
+    }
+
+    pub(crate) fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    // Simulated time, which is every tick that ran times the tick length
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.tick.mul_f64(self.tick_count as f64)
+    }
+
+    pub(crate) fn stats(&self) -> TickStats {
+        self.stats
+    }
+
+    pub(crate) fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    // Negative scales don't mean anything for a fixed step, so they're treated as 0
+    pub(crate) fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.pending_steps = 0;
+
+        // Time spent paused shouldn't come rushing out as catch up ticks on resume
+        self.accumulator = Duration::ZERO;
+    }
+
+    pub(crate) fn step_once(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    // Moves the clock up to now, returning how many ticks should run
+    pub(crate) fn ticks_due(&mut self, now: Instant) -> u32 {
+        let real = now.saturating_duration_since(self.last_update);
+        self.last_update = now;
+
+        if self.paused {
+            let steps = self.pending_steps.min(self.max_catch_up);
+            self.pending_steps -= steps;
+
+            return steps;
+        }
+
+        // Scaled in whole nanoseconds, float durations would lose a bit each pass and slowly drop ticks
+        self.accumulator += Duration::from_nanos((real.as_nanos() as f64 * self.time_scale as f64).round() as u64);
+
+        let mut due = (self.accumulator.as_nanos() / self.tick.as_nanos().max(1)) as u64;
+        self.accumulator -= self.tick.mul_f64(due as f64);
+
+        if due > self.max_catch_up as u64 {
+            self.stats.dropped_ticks += due - self.max_catch_up as u64;
+            due = self.max_catch_up as u64;
+        }
+
+        due as u32
+    }
+
+    pub(crate) fn ticks_ran(&mut self, ticks: u32) {
+        self.tick_count += ticks as u64;
+    }
+
+    // Records how long a pass of the loop took, anything over a tick counts as an overrun
+    pub(crate) fn pass_finished(&mut self, pass_time: Duration) {
+        self.stats.last_pass_time = pass_time;
+        self.stats.max_pass_time = self.stats.max_pass_time.max(pass_time);
+
+        if pass_time > self.tick {
+            self.stats.overruns += 1;
+        }
+    }
+
+    // Real time until the accumulator fills up another tick
+    // We still wake at least once a tick while paused or slowed down, so input keeps getting handled
+    pub(crate) fn time_until_next(&self, now: Instant) -> Duration {
+        let since_update = now.saturating_duration_since(self.last_update);
+
+        if self.paused || self.time_scale <= 0.0 {
+            return self.tick.saturating_sub(since_update);
+        }
+
+        // A tiny scale can push this past what a Duration holds, we wake after a tick either way
+        let remaining = self.tick.saturating_sub(self.accumulator).as_secs_f64() / self.time_scale as f64;
+        let remaining = Duration::try_from_secs_f64(remaining).unwrap_or(Duration::MAX);
+
+        remaining.min(self.tick).saturating_sub(since_update)
+    }
+}
@@ -0,0 +1,78 @@
+use crate::core::config::AdapterPreferences;
+use crate::core::error::GpuError;
+
+// End of imports
+
+// What LatrEngine::adapters reports about each adapter
+#[derive(Debug, Clone)]
+pub struct AdapterDetails {
+    // Name, backend, device type and driver
+    pub info: wgpu::AdapterInfo,
+
+    // Most the adapter can do, like the biggest texture or storage buffer
+    pub limits: wgpu::Limits,
+
+    // Whether the adapter fits the preferences it was listed with
+    pub matches: bool,
+}
+
+// Every adapter on the backends from the preferences, in the order they'd be picked
+pub(crate) fn list_adapters(instance: &wgpu::Instance, preferences: &AdapterPreferences) -> Vec<AdapterDetails> {
+    let mut adapters: Vec<AdapterDetails> = instance.enumerate_adapters(preferences.wgpu_backends())
+        .iter()
+        .map(|adapter| {
+            let info = adapter.get_info();
+
+            AdapterDetails { matches: preferences.matches(&info), info, limits: adapter.limits() }
+        })
+        .collect();
+
+    adapters.sort_by_key(|adapter| (!adapter.matches, preferences.power_preference.rank(adapter.info.device_type)));
+
+    adapters
+}
+
+// Best fitting adapter that can draw to the surface, surface is None when running headless
+pub(crate) fn choose_adapter(
+    instance: &wgpu::Instance,
+    preferences: &AdapterPreferences,
+    surface: Option<&wgpu::Surface>,
+) -> Result<wgpu::Adapter, GpuError> {
+    let adapters = instance.enumerate_adapters(preferences.wgpu_backends());
+    let found: Vec<wgpu::AdapterInfo> = adapters.iter().map(|adapter| adapter.get_info()).collect();
+
+    adapters.into_iter()
+        .filter(|adapter| preferences.matches(&adapter.get_info()))
+        .filter(|adapter| surface.is_none_or(|surface| adapter.is_surface_supported(surface)))
+        .min_by_key(|adapter| preferences.power_preference.rank(adapter.get_info().device_type))
+        .ok_or_else(|| GpuError::NoMatchingAdapter(describe_preferences(preferences), describe_adapters(&found)))
+}
+
+fn describe_preferences(preferences: &AdapterPreferences) -> String {
+    let mut wanted = if preferences.backends.is_empty() {
+        vec!["any backend".to_string()]
+    } else {
+        vec![format!("backends {:?}", preferences.backends)]
+    };
+
+    if let Some(name) = &preferences.name {
+        wanted.push(format!("name containing \"{name}\""));
+    }
+
+    if preferences.force_fallback {
+        wanted.push("software only".to_string());
+    }
+
+    wanted.join(", ")
+}
+
+fn describe_adapters(found: &[wgpu::AdapterInfo]) -> String {
+    if found.is_empty() {
+        return "none".to_string();
+    }
+
+    found.iter()
+        .map(|info| format!("{} ({:?}, {:?})", info.name, info.backend, info.device_type))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
@@ -0,0 +1,76 @@
+use super::render_graph::{GraphTextures, RenderGraph, TextureId};
+
+// Extra targets the raytracer fills next to the beauty render, for denoisers and compositing
+// Everything is packed into three textures, since a compute shader only gets 4 storage textures by default
+// When they're turned off we keep 1x1 placeholders bound, same as the environment textures
+// Handed out by LatrRenderer::aovs, for sampling them in the caller's own passes
+pub struct AovTextures {
+    // rgb = albedo of the hit surface
+    pub albedo: wgpu::Texture,
+
+    // xyz = world space normal, w = distance along the camera ray
+    pub normal_depth: wgpu::Texture,
+
+    // xyz = world space position, w = instance index, -1 on a miss
+    pub position_id: wgpu::Texture,
+
+    pub albedo_view: wgpu::TextureView,
+    pub normal_depth_view: wgpu::TextureView,
+    pub position_id_view: wgpu::TextureView,
+
+    enabled: bool,
+}
+
+impl AovTextures {
+    pub const ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    // Depth and position need more precision than half floats have once things get far from the origin
+    pub const NORMAL_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+    pub const POSITION_ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+    // Handles to the graph's textures, which are only full size while enabled, see ComputeRaytraceShader::io
+    pub(crate) fn from_graph(textures: &GraphTextures, ids: &AovIds, enabled: bool) -> Self {
+        Self {
+            albedo: textures.texture(ids.albedo).clone(),
+            normal_depth: textures.texture(ids.normal_depth).clone(),
+            position_id: textures.texture(ids.position_id).clone(),
+
+            albedo_view: textures.view(ids.albedo).clone(),
+            normal_depth_view: textures.view(ids.normal_depth).clone(),
+            position_id_view: textures.view(ids.position_id).clone(),
+
+            enabled,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+// Where the aovs live in the render graph
+#[derive(Clone, Copy)]
+pub(crate) struct AovIds {
+    pub(crate) albedo: TextureId,
+    pub(crate) normal_depth: TextureId,
+    pub(crate) position_id: TextureId,
+}
+
+impl AovIds {
+    pub(crate) fn add_to(graph: &mut RenderGraph) -> Self {
+        // Always bound as storage by the trace, COPY_SRC so they can be read back when exporting a frame,
+        // and TEXTURE_BINDING for LatrRenderer::aovs
+        // The 32 bit ones can't be filtered, so they need a non filtering sampler or textureLoad
+        let usage = wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC;
+
+        Self {
+            albedo: graph.add_texture("AOV Albedo Texture", AovTextures::ALBEDO_FORMAT, usage, true),
+            normal_depth: graph.add_texture("AOV Normal Depth Texture", AovTextures::NORMAL_DEPTH_FORMAT, usage, true),
+            position_id: graph.add_texture("AOV Position Id Texture", AovTextures::POSITION_ID_FORMAT, usage, true),
+        }
+    }
+
+    pub(crate) fn ids(&self) -> [TextureId; 3] {
+        [self.albedo, self.normal_depth, self.position_id]
+    }
+}
@@ -1,5 +1,14 @@
 use super::init_utils::TEXTURE_FORMAT;
-use super::buffers::{ GpuUniformCamera, GpuBuffers };
+use super::buffers::{
+    GpuUniformCamera,
+    GpuUniformLightInfo,
+    GpuUniformBackground,
+    GpuUniformToneMap,
+    GpuUniformRenderSettings,
+    GpuBuffers,
+};
+use super::environment::EnvironmentTextures;
+use super::aovs::AovTextures;
 
 fn create_compute_layout_entry(read_only: bool, binding: u32) -> wgpu::BindGroupLayoutEntry {
     wgpu::BindGroupLayoutEntry {
@@ -14,6 +23,47 @@ fn create_compute_layout_entry(read_only: bool, binding: u32) -> wgpu::BindGroup
     }
 }
 
+fn create_compute_uniform_layout_entry(binding: u32, size: usize) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(size as u64),
+        },
+        count: None,
+    }
+}
+
+// Texture that the compute shader only writes to
+fn create_compute_storage_texture_layout_entry(binding: u32, format: wgpu::TextureFormat) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+// Texture that the compute shader only reads through textureLoad
+fn create_compute_texture_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
 pub fn create_raytrace_bindgroup_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("Raytrace Bindgroup Layout"),
@@ -61,6 +111,30 @@ pub fn create_raytrace_bindgroup_layout(device: &wgpu::Device) -> wgpu::BindGrou
 
             // BLAS
             create_compute_layout_entry(true, 6),
+
+            // Lights
+            create_compute_layout_entry(true, 7),
+
+            // Area lights
+            create_compute_layout_entry(true, 8),
+
+            // Light counts
+            create_compute_uniform_layout_entry(9, std::mem::size_of::<GpuUniformLightInfo>()),
+
+            // Background
+            create_compute_uniform_layout_entry(10, std::mem::size_of::<GpuUniformBackground>()),
+
+            // Environment map and its cdf
+            create_compute_texture_layout_entry(11),
+            create_compute_texture_layout_entry(12),
+
+            // Render mode
+            create_compute_uniform_layout_entry(13, std::mem::size_of::<GpuUniformRenderSettings>()),
+
+            // Aovs, see AovTextures
+            create_compute_storage_texture_layout_entry(14, AovTextures::ALBEDO_FORMAT),
+            create_compute_storage_texture_layout_entry(15, AovTextures::NORMAL_DEPTH_FORMAT),
+            create_compute_storage_texture_layout_entry(16, AovTextures::POSITION_ID_FORMAT),
         ],
     })
 }
@@ -69,6 +143,8 @@ pub fn create_raytrace_bindgroup(
     device: &wgpu::Device, 
     buffers: &GpuBuffers,
     screen_texture: &wgpu::TextureView,
+    environment: &EnvironmentTextures,
+    aovs: &AovTextures,
     layout: &wgpu::BindGroupLayout,
 ) -> wgpu::BindGroup {
     device.create_bind_group(&wgpu::BindGroupDescriptor{
@@ -102,7 +178,47 @@ pub fn create_raytrace_bindgroup(
             wgpu::BindGroupEntry {
                 binding: 6,
                 resource: buffers.blas_buffer.as_entire_binding(),
-            }
+            },
+            wgpu::BindGroupEntry {
+                binding: 7,
+                resource: buffers.light_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 8,
+                resource: buffers.area_light_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 9,
+                resource: buffers.light_info_uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 10,
+                resource: buffers.background_uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 11,
+                resource: wgpu::BindingResource::TextureView(&environment.env_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 12,
+                resource: wgpu::BindingResource::TextureView(&environment.cdf_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 13,
+                resource: buffers.render_settings_uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 14,
+                resource: wgpu::BindingResource::TextureView(&aovs.albedo_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 15,
+                resource: wgpu::BindingResource::TextureView(&aovs.normal_depth_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 16,
+                resource: wgpu::BindingResource::TextureView(&aovs.position_id_view),
+            },
         ],
     })
 }
@@ -130,6 +246,18 @@ pub fn create_render_bindgroup_layout(device: &wgpu::Device) -> wgpu::BindGroupL
                 ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                 count: None,
             },
+
+            // Tone mapping settings
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<GpuUniformToneMap>() as u64),
+                },
+                count: None,
+            },
         ],
     })
 }
@@ -139,6 +267,7 @@ pub fn create_render_bindgroup(
     bindgroup_layout: &wgpu::BindGroupLayout,
     screen_texture_view: &wgpu::TextureView,
     sampler: &wgpu::Sampler,
+    buffers: &GpuBuffers,
 ) -> wgpu::BindGroup {
     device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: Some("Render Bind Group"),
@@ -152,6 +281,10 @@ pub fn create_render_bindgroup(
                 binding: 1,
                 resource: wgpu::BindingResource::Sampler(sampler),
             },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffers.tone_map_uniform_buffer.as_entire_binding(),
+            },
         ],
     })
 }
\ No newline at end of file
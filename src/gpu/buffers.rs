@@ -1,6 +1,11 @@
 use bytemuck::{Pod, Zeroable};
 
 use std::mem::size_of;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::core::config::SceneCapacity;
+use crate::core::error::{EngineError, GpuError};
+use super::render_graph::{BufferId, RenderGraph};
 
 // Since wgpu::Buffer is a ref count, we can just derive clone
 #[derive(Clone)]
@@ -14,50 +19,210 @@ pub struct GpuBuffers {
     pub(crate) tlas_buffer: wgpu::Buffer,
     pub(crate) blas_buffer: wgpu::Buffer,
 
+    // Lighting data
+    pub(crate) light_buffer: wgpu::Buffer,
+    pub(crate) area_light_buffer: wgpu::Buffer,
+    pub(crate) light_info_uniform_buffer: wgpu::Buffer,
+
     // Rendering data
     pub(crate) camera_uniform_buffer: wgpu::Buffer,
+    pub(crate) background_uniform_buffer: wgpu::Buffer,
+    pub(crate) tone_map_uniform_buffer: wgpu::Buffer,
+    pub(crate) render_settings_uniform_buffer: wgpu::Buffer,
+
+    // Write heads for the scene buffers, in elements
+    // Each clone keeps its own heads, so only one thread should be adding scene data at a time
+    vertex_count: u64,
+    triangle_count: u64,
+    blas_count: u64,
+    instance_count: u64,
+
+    // What the scene buffers were made to hold, in elements
+    capacity: SceneCapacity,
+
+    // Shared between every clone, so uploads from the engine thread end up in the same copy
+    scene_copy: Arc<Mutex<SceneCopy>>,
+}
+
+// Cpu side copy of everything in the scene buffers
+// The engine thread only ever adds to this, and the render thread uploads it with upload_scene, so the scene
+// always ends up on whatever device the render thread has, even one made again by GpuCore::recover_device
+#[derive(Default)]
+struct SceneCopy {
+    vertices: Vec<GpuStorageVertex>,
+    triangles: Vec<GpuStorageTriangleData>,
+    blas_nodes: Vec<GpuStorageBvhNode>,
+    instances: Vec<GpuStorageInstanceMesh>,
+    tlas_nodes: Vec<GpuStorageBvhNode>,
+    area_lights: Vec<GpuStorageAreaLight>,
+    area_light_power: f32,
+}
+
+// Graph handles for the buffers the nodes bind, so the graph can see what each pass reads
+// Everything here is filled from the cpu, either by the engine thread or by GpuCore between frames
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GpuBufferIds {
+    pub(crate) instance_mesh: BufferId,
+    pub(crate) triangle_data: BufferId,
+    pub(crate) vertex: BufferId,
+    pub(crate) tlas: BufferId,
+    pub(crate) blas: BufferId,
+    pub(crate) light: BufferId,
+    pub(crate) area_light: BufferId,
+    pub(crate) light_info: BufferId,
+    pub(crate) camera: BufferId,
+    pub(crate) background: BufferId,
+    pub(crate) tone_map: BufferId,
+    pub(crate) render_settings: BufferId,
+}
+
+impl GpuBufferIds {
+    pub(crate) fn add_to(graph: &mut RenderGraph) -> Self {
+        let mut add = |name| graph.add_buffer(name, true, false);
+
+        Self {
+            instance_mesh: add("Instance Mesh Storage Buffer"),
+            triangle_data: add("Triangle Data Storage Buffer"),
+            vertex: add("Vertex Storage Buffer"),
+            tlas: add("TLAS Storage Buffer"),
+            blas: add("BLAS Tree Storage Buffer"),
+            light: add("Light Storage Buffer"),
+            area_light: add("Area Light Storage Buffer"),
+            light_info: add("Light Info Uniform Buffer"),
+            camera: add("Camera Uniform Buffer"),
+            background: add("Background Uniform Buffer"),
+            tone_map: add("Tone Map Uniform Buffer"),
+            render_settings: add("Render Settings Uniform Buffer"),
+        }
+    }
+
+    // Everything create_raytrace_bindgroup binds
+    pub(crate) fn trace_reads(&self) -> Vec<BufferId> {
+        vec![
+            self.camera, self.instance_mesh, self.triangle_data, self.vertex, self.tlas, self.blas,
+            self.light, self.area_light, self.light_info, self.background, self.render_settings,
+        ]
+    }
 }
 
 impl GpuBuffers {
-    const INSTANCE_START: u64 = 1 << 12;
-    const TRIANGLE_START: u64 = 1 << 16;
-    const VERTEX_START: u64 = 1 << 16;
+    // Punctual lights are added by hand through the engine, so they don't scale with the scene
+    pub const LIGHT_START: u64 = 1 << 8;
+
+    // Size in bytes of every storage buffer the capacity asks for, in the order new makes them
+    // The TLAS gets two nodes per instance, same as the BLAS gets per triangle in SceneCapacity::for_triangles
+    // A size too big for a u64 comes out as u64::MAX, so check_limits still fails on it with the buffer's name
+    pub(crate) fn planned_sizes(capacity: &SceneCapacity) -> [(&'static str, u64); 7] {
+        fn bytes<T>(count: u64) -> u64 {
+            (size_of::<T>() as u64).saturating_mul(count)
+        }
+
+        [
+            ("instance mesh", bytes::<GpuStorageInstanceMesh>(capacity.instances)),
+            ("triangle data", bytes::<GpuStorageTriangleData>(capacity.triangles)),
+            ("vertex", bytes::<GpuStorageVertex>(capacity.vertices)),
+            ("TLAS", bytes::<GpuStorageBvhNode>(capacity.instances.saturating_mul(2))),
+            ("BLAS", bytes::<GpuStorageBvhNode>(capacity.blas_nodes)),
+            ("light", bytes::<GpuStorageLight>(Self::LIGHT_START)),
+            ("area light", bytes::<GpuStorageAreaLight>(capacity.area_lights)),
+        ]
+    }
+
+    // Checks every planned buffer against the device before anything is made, so a scene that can't fit
+    // fails here with the limit it ran into, instead of as a wgpu validation error partway through
+    pub(crate) fn check_limits(limits: &wgpu::Limits, capacity: &SceneCapacity) -> Result<(), GpuError> {
+        for (buffer, size) in Self::planned_sizes(capacity) {
+            if size > limits.max_storage_buffer_binding_size as u64 {
+                return Err(GpuError::BufferLimitExceeded(
+                    buffer, "max_storage_buffer_binding_size", size, limits.max_storage_buffer_binding_size as u64,
+                ));
+            }
+
+            if size > limits.max_buffer_size {
+                return Err(GpuError::BufferLimitExceeded(buffer, "max_buffer_size", size, limits.max_buffer_size));
+            }
+        }
+
+        Ok(())
+    }
 
-    const TLAS_START: u64 = Self::INSTANCE_START * 2;
-    const BLAS_START: u64 = Self::TRIANGLE_START * 2;
+    pub fn new(device: &wgpu::Device, capacity: &SceneCapacity) -> Result<Self, GpuError> {
+        Self::check_limits(&device.limits(), capacity)?;
+
+        let [instance_size, triangle_size, vertex_size, tlas_size, blas_size, light_size, area_light_size] =
+            Self::planned_sizes(capacity).map(|(_, size)| size);
 
-    pub fn new(device: &wgpu::Device) -> Self {
         let instance_mesh_buffer = Self::create_storage_buffer(
-            &device, size_of::<GpuStorageInstanceMesh>() as u64 * Self::INSTANCE_START, "Instance Mesh Storage Buffer"
+            &device, instance_size, "Instance Mesh Storage Buffer"
         );
 
         let triangle_data_buffer = Self::create_storage_buffer(
-            &device, size_of::<GpuStorageTriangleData>() as u64 * Self::TRIANGLE_START, "Triangle Data Storage Buffer"
+            &device, triangle_size, "Triangle Data Storage Buffer"
         );
 
         let vertex_buffer = Self::create_storage_buffer(
-            &device, size_of::<GpuStorageVertex>() as u64 * Self::VERTEX_START, "Vertex Storage Buffer"
+            &device, vertex_size, "Vertex Storage Buffer"
         );
 
         let tlas_buffer = Self::create_storage_buffer(
-            &device, size_of::<GpuStorageBvhNode>() as u64 * Self::TLAS_START, "TLAS Storage Buffer"
+            &device, tlas_size, "TLAS Storage Buffer"
         );
 
         let blas_buffer = Self::create_storage_buffer(
-            &device, size_of::<GpuStorageBvhNode>() as u64 * Self::BLAS_START, "BLAS Tree Storage Buffer"
+            &device, blas_size, "BLAS Tree Storage Buffer"
         );
 
-        let camera_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Camera Uniform Buffer"),
-            size: size_of::<GpuUniformCamera>() as u64,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let light_buffer = Self::create_storage_buffer(
+            &device, light_size, "Light Storage Buffer"
+        );
 
-        Self {
+        let area_light_buffer = Self::create_storage_buffer(
+            &device, area_light_size, "Area Light Storage Buffer"
+        );
+
+        let light_info_uniform_buffer = Self::create_uniform_buffer(
+            &device, size_of::<GpuUniformLightInfo>() as u64, "Light Info Uniform Buffer"
+        );
+
+        let camera_uniform_buffer = Self::create_uniform_buffer(
+            &device, size_of::<GpuUniformCamera>() as u64, "Camera Uniform Buffer"
+        );
+
+        let background_uniform_buffer = Self::create_uniform_buffer(
+            &device, size_of::<GpuUniformBackground>() as u64, "Background Uniform Buffer"
+        );
+
+        let tone_map_uniform_buffer = Self::create_uniform_buffer(
+            &device, size_of::<GpuUniformToneMap>() as u64, "Tone Map Uniform Buffer"
+        );
+
+        let render_settings_uniform_buffer = Self::create_uniform_buffer(
+            &device, size_of::<GpuUniformRenderSettings>() as u64, "Render Settings Uniform Buffer"
+        );
+
+        Ok(Self {
             instance_mesh_buffer, triangle_data_buffer,
-            vertex_buffer, tlas_buffer, blas_buffer, camera_uniform_buffer,
-        }
+            vertex_buffer, tlas_buffer, blas_buffer,
+            light_buffer, area_light_buffer, light_info_uniform_buffer,
+            camera_uniform_buffer, background_uniform_buffer, tone_map_uniform_buffer,
+            render_settings_uniform_buffer,
+            vertex_count: 0, triangle_count: 0, blas_count: 0, instance_count: 0,
+            capacity: *capacity,
+            scene_copy: Arc::default(),
+        })
+    }
+
+    fn create_uniform_buffer(
+        device: &wgpu::Device,
+        size_of_struct: u64,
+        label: &'static str,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size_of_struct,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
     }
 
     fn create_storage_buffer(
@@ -75,13 +240,172 @@ impl GpuBuffers {
         })
     }
 
-    pub fn write_blas_bvh(&mut self, queue: &wgpu::Queue, device: &wgpu::Device, bvh_node_vec: &[GpuStorageBvhNode]) {
+    // Moves a write head past a slice of elements, returning the index of the first element
+    // The capacity is in elements, same as the head, nothing is written to the gpu until upload_scene
+    fn append(head: &mut u64, capacity: u64, len: usize, label: &'static str) -> Result<u64, EngineError> {
+        let start = *head;
+
+        if start + len as u64 > capacity {
+            return Err(EngineError::BufferFull(label));
+        }
+
+        *head += len as u64;
+
+        Ok(start)
+    }
+
+    // The BLAS nodes from flatten_to_blas are local to their own mesh, so we rebase them here
+    // Branches (positive) get shifted by the node head, leaves (negative) get shifted by the triangle base
+    // Returns the encoded entry point of the BLAS to store on an instance
+    pub fn write_blas_bvh(&mut self, bvh_node_vec: &[GpuStorageBvhNode], triangle_base: u32) -> Result<i32, EngineError> {
+        let node_base = self.blas_count as i32;
+
+        let rebased: Vec<GpuStorageBvhNode> = bvh_node_vec.iter()
+            .map(|node| {
+                let mut node = *node;
+
+                for idx in node.indices.iter_mut() {
+                    if *idx > 0 {
+                        *idx += node_base;
+                    } else if *idx < 0 {
+                        *idx = !(!*idx + triangle_base as i32);
+                    }
+                }
+
+                node
+            })
+            .collect();
+
+        Self::append(&mut self.blas_count, self.capacity.blas_nodes, rebased.len(), "BLAS")?;
+        self.lock_scene_copy().blas_nodes.extend_from_slice(&rebased);
+
+        // Node 0 of every flattened tree is its root
+        Ok(node_base + 1)
+    }
+
+    pub fn write_instances(&mut self, gpu_instances: &[GpuStorageInstanceMesh]) -> Result<u32, EngineError> {
+        let base = Self::append(&mut self.instance_count, self.capacity.instances, gpu_instances.len(), "instance mesh")?;
+        self.lock_scene_copy().instances.extend_from_slice(gpu_instances);
+
+        Ok(base as u32)
+    }
+
+    // The TLAS is built over every instance at once, so it replaces whatever was there
+    // Node 0 is the entry node, which is where trace_scene starts
+    pub fn write_tlas(&mut self, tlas_nodes: &[GpuStorageBvhNode]) -> Result<(), EngineError> {
+        if tlas_nodes.len() as u64 > self.capacity.instances.saturating_mul(2) {
+            return Err(EngineError::BufferFull("TLAS"));
+        }
+
+        self.lock_scene_copy().tlas_nodes = tlas_nodes.to_vec();
+
+        Ok(())
+    }
+
+    pub fn write_vertices(&mut self, gpu_vertices: &[GpuStorageVertex]) -> Result<u32, EngineError> {
+        let base = Self::append(&mut self.vertex_count, self.capacity.vertices, gpu_vertices.len(), "vertex")?;
+        self.lock_scene_copy().vertices.extend_from_slice(gpu_vertices);
+
+        Ok(base as u32)
+    }
+
+    pub fn write_triangles(&mut self, gpu_triangles: &[GpuStorageTriangleData]) -> Result<u32, EngineError> {
+        let base = Self::append(&mut self.triangle_count, self.capacity.triangles, gpu_triangles.len(), "triangle data")?;
+        self.lock_scene_copy().triangles.extend_from_slice(gpu_triangles);
+
+        Ok(base as u32)
+    }
+
+    // Lights are rewritten as a whole whenever the engine changes them, so there's no write head
+    pub fn write_lights(&mut self, queue: &wgpu::Queue, gpu_lights: &[GpuStorageLight]) -> Result<(), EngineError> {
+        if gpu_lights.len() as u64 > Self::LIGHT_START {
+            return Err(EngineError::BufferFull("light"));
+        }
+
+        if !gpu_lights.is_empty() {
+            queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(gpu_lights));
+        }
+
+        // Only the light count is ours, the rest of the info uniform belongs to the area lights
+        let count = gpu_lights.len() as u32;
+        queue.write_buffer(&self.light_info_uniform_buffer, 0, bytemuck::bytes_of(&count));
+
+        Ok(())
+    }
+
+    pub fn write_area_lights(&mut self, gpu_area_lights: &[GpuStorageAreaLight], total_power: f32) -> Result<(), EngineError> {
+        if gpu_area_lights.len() as u64 > self.capacity.area_lights {
+            return Err(EngineError::BufferFull("area light"));
+        }
+
+        let mut scene_copy = self.lock_scene_copy();
+        scene_copy.area_lights = gpu_area_lights.to_vec();
+        scene_copy.area_light_power = total_power;
+
+        Ok(())
+    }
+
+    fn upload_area_lights(&self, queue: &wgpu::Queue, gpu_area_lights: &[GpuStorageAreaLight], total_power: f32) {
+        if !gpu_area_lights.is_empty() {
+            queue.write_buffer(&self.area_light_buffer, 0, bytemuck::cast_slice(gpu_area_lights));
+        }
 
+        // Skip over light_count, see GpuUniformLightInfo
+        // The power is sent as raw bits so the count and power can go in a single write
+        let info: [u32; 2] = [gpu_area_lights.len() as u32, total_power.to_bits()];
+        queue.write_buffer(&self.light_info_uniform_buffer, size_of::<u32>() as u64, bytemuck::cast_slice(&info));
+    }
+
+    // Uploads everything in the scene copy, which the engine thread asks for once it's done loading models
+    // The capacity is checked as things are added, so everything fits
+    pub(crate) fn upload_scene(&mut self, queue: &wgpu::Queue) {
+        let scene_copy = self.lock_scene_copy();
+
+        Self::restore(queue, &self.vertex_buffer, &scene_copy.vertices);
+        Self::restore(queue, &self.triangle_data_buffer, &scene_copy.triangles);
+        Self::restore(queue, &self.blas_buffer, &scene_copy.blas_nodes);
+        Self::restore(queue, &self.instance_mesh_buffer, &scene_copy.instances);
+        Self::restore(queue, &self.tlas_buffer, &scene_copy.tlas_nodes);
+        self.upload_area_lights(queue, &scene_copy.area_lights, scene_copy.area_light_power);
+
+        let counts = [scene_copy.vertices.len(), scene_copy.triangles.len(), scene_copy.blas_nodes.len(), scene_copy.instances.len()];
+        drop(scene_copy);
+
+        [self.vertex_count, self.triangle_count, self.blas_count, self.instance_count] = counts.map(|count| count as u64);
+    }
 
+    // Takes over the scene copy of buffers that belonged to a lost device and uploads it
+    // The capacity is the same as when it was first uploaded, so everything fits again
+    pub(crate) fn restore_scene(&mut self, queue: &wgpu::Queue, lost: &GpuBuffers) {
+        self.scene_copy = lost.scene_copy.clone();
+        self.upload_scene(queue);
     }
 
-    pub fn write_vertices(&mut self, queue: &wgpu::Queue, device: &wgpu::Device, gpu_vertices: &[GpuStorageVertex]) {
+    fn restore<T: Pod>(queue: &wgpu::Queue, buffer: &wgpu::Buffer, data: &[T]) {
+        if !data.is_empty() {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(data));
+        }
+    }
+
+    // The copy is only ever appended to or replaced, so one left behind by a panicking thread is still whole
+    fn lock_scene_copy(&self) -> std::sync::MutexGuard<'_, SceneCopy> {
+        self.scene_copy.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn write_camera(&self, queue: &wgpu::Queue, camera: &GpuUniformCamera) {
+        queue.write_buffer(&self.camera_uniform_buffer, 0, bytemuck::bytes_of(camera));
+    }
+
+    pub fn write_background(&self, queue: &wgpu::Queue, background: &GpuUniformBackground) {
+        queue.write_buffer(&self.background_uniform_buffer, 0, bytemuck::bytes_of(background));
+    }
+
+    pub fn write_tone_map(&self, queue: &wgpu::Queue, tone_map: &GpuUniformToneMap) {
+        queue.write_buffer(&self.tone_map_uniform_buffer, 0, bytemuck::bytes_of(tone_map));
+    }
 
+    pub fn write_render_settings(&self, queue: &wgpu::Queue, settings: &GpuUniformRenderSettings) {
+        queue.write_buffer(&self.render_settings_uniform_buffer, 0, bytemuck::bytes_of(settings));
     }
 }
 
@@ -92,20 +416,30 @@ impl GpuBuffers {
 
 // Instance Mesh Buffer
 #[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-struct GpuStorageInstanceMesh {
+#[derive(Clone, Copy, Pod, Zeroable, Debug, PartialEq)]
+pub struct GpuStorageInstanceMesh {
 	// Inverse of the matrix that transforms the origin model to the world model
 	// We precalculate the inverse on the cpu as to not waste gpu resources
     // We also use the glam matrix for faster calculations
-	inverse_transformation_matrix: [f32; 16],
+	pub(crate) inverse_transformation_matrix: [f32; 16],
 
 	// BLAS entry point
 	// See note 1 and 3
 	// Negative value points to a BLAS leaf, positive value points to a branch
-	blas_entry: i32,
+	pub(crate) blas_entry: i32,
 
 	// See note 2
-	_pad: [u32; 3],
+	pub(crate) _pad: [u32; 3],
+}
+
+impl GpuStorageInstanceMesh {
+    pub fn new(transform: glam::Mat4, blas_entry: i32) -> Self {
+        Self {
+            inverse_transformation_matrix: transform.inverse().to_cols_array(),
+            blas_entry,
+            _pad: [0; 3],
+        }
+    }
 }
 
 // Triangle Data Buffer
@@ -122,7 +456,9 @@ pub struct GpuStorageTriangleData {
 	// Texture data
 	pub rgba: [f32; 4],
 
-	// TBD on other data
+	// Emitted radiance, w is unused
+	// Anything with a non zero emission gets collected into the area light buffer
+	pub emission: [f32; 4],
 }
 
 // Vertex Buffer
@@ -180,22 +516,167 @@ impl Default for GpuStorageBvhNode {
 
 
 
+// Light Buffer
+// Kind is one of the KIND_ constants below, and decides which of the fields are read
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug, PartialEq)]
+pub struct GpuStorageLight {
+	pub position: [f32; 3],
+	pub kind: u32,
+
+	// Normalized direction the light points in, used by directional and spot lights
+	pub direction: [f32; 3],
+
+	// Distance the light fades out at, 0 means no cutoff
+	pub range: f32,
+
+	pub color: [f32; 3],
+	pub intensity: f32,
+
+	// Cosines of the inner and outer cone angles of a spot light
+	pub cos_inner: f32,
+	pub cos_outer: f32,
+
+	// See note 2
+	pub _pad: [u32; 2],
+}
+
+impl GpuStorageLight {
+	pub const KIND_POINT: u32 = 0;
+	pub const KIND_DIRECTIONAL: u32 = 1;
+	pub const KIND_SPOT: u32 = 2;
+}
+
+// Area Light Buffer
+// One entry per emissive triangle, stored with its vertices so the shader doesn't have to chase indices
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug, PartialEq)]
+pub struct GpuStorageAreaLight {
+	pub v0: [f32; 3],
+	pub triangle: u32,
+
+	pub v1: [f32; 3],
+	pub area: f32,
+
+	pub v2: [f32; 3],
+
+	// Running sum of the light's power over the total, used to pick lights proportional to their power
+	pub cdf: f32,
+
+	pub emission: [f32; 3],
+
+	// See note 2
+	pub _pad: u32,
+}
+
+// Light Info Uniform Buffer
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Pod, Zeroable, Default)]
+pub struct GpuUniformLightInfo {
+	pub light_count: u32,
+	pub area_light_count: u32,
+
+	// Sum of the power of every area light, so the shader can get the pick probability from the cdf
+	pub area_light_power: f32,
+
+	pub _pad: u32,
+}
+
 // Camera Uniform Buffer
 // Uniforms have to be multiples of 16, so we align 16
 #[repr(C, align(16))]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct GpuUniformCamera {
     pub pos: [f32; 3],
-    _pad: u32,
 
-	// TBD on what will be included here yet, the coordinate system still has to be fleshed out a bit 
+    // Radians, see the math conventions in the README
+    pub yaw: f32,
+    pub pitch: f32,
+
+    _pad: [u32; 3],
 }
 
 impl Default for GpuUniformCamera {
     fn default() -> Self {
         Self {
             pos: [0.0, 0.0, 0.0],
-            _pad: 0,
+
+            // Looking down +z, which is where the camera has always faced
+            yaw: -std::f32::consts::FRAC_PI_2,
+            pitch: 0.0,
+
+            _pad: [0; 3],
         }
     }
-}
\ No newline at end of file
+}
+
+// Background Uniform Buffer
+// Kind is one of the KIND_ constants below, and decides which of the fields are read
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Pod, Zeroable, Default)]
+pub struct GpuUniformBackground {
+    pub kind: u32,
+
+    // Size of the environment map, and the sum of its importance weights
+    pub env_width: u32,
+    pub env_height: u32,
+    pub env_total: f32,
+
+    // Solid color, or the zenith color of a gradient
+    pub color: [f32; 3],
+
+    // Brightness of the sun or the environment map
+    pub intensity: f32,
+
+    pub horizon: [f32; 3],
+
+    // Rotation of the environment map around the y axis, in radians
+    pub env_rotation: f32,
+
+    pub ground: [f32; 3],
+    pub _pad0: u32,
+
+    pub sun_dir: [f32; 3],
+    pub _pad1: u32,
+
+    pub sun_color: [f32; 3],
+    pub _pad2: u32,
+}
+
+impl GpuUniformBackground {
+    pub const KIND_COLOR: u32 = 0;
+    pub const KIND_GRADIENT: u32 = 1;
+    pub const KIND_SKY: u32 = 2;
+    pub const KIND_ENVIRONMENT: u32 = 3;
+}
+
+// Tone Map Uniform Buffer
+// Read by the blit shader rather than the raytracer
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Pod, Zeroable, Default)]
+pub struct GpuUniformToneMap {
+    // See ToneMapping::to_gpu
+    pub kind: u32,
+
+    // In stops, the shader multiplies by 2^exposure
+    pub exposure: f32,
+
+    // 1 when the surface isn't sRGB, so the shader has to do the gamma encoding itself
+    pub encode_srgb: u32,
+
+    pub _pad: u32,
+}
+
+// Render Settings Uniform Buffer
+// Settings for the raytracer that belong to the render thread rather than the scene
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Pod, Zeroable, Default)]
+pub struct GpuUniformRenderSettings {
+    // See RenderMode::to_gpu
+    pub mode: u32,
+
+    // 1 when the aov textures are real and should be written to
+    pub aovs: u32,
+
+    pub _pad: [u32; 2],
+}
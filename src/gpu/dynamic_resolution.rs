@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use crate::core::config::DynamicResolution;
+
+// Frame time gets averaged over roughly 1 / SMOOTHING frames, so one slow frame doesn't shrink the image
+const SMOOTHING: f32 = 0.1;
+
+// Frames to wait after a change before changing again, gives the new size time to show up in the timings
+const COOLDOWN_FRAMES: u32 = 30;
+
+// Changes smaller than this fraction of the current scale are ignored, otherwise we'd remake textures constantly
+const MIN_CHANGE: f32 = 0.05;
+
+// Picks the render scale that keeps frames near the target time, see DynamicResolution
+pub(crate) struct ResolutionController {
+    settings: DynamicResolution,
+    scale: f32,
+
+    // Smoothed frame time in seconds, reset after every change since it was measured at the old size
+    frame_time: Option<f32>,
+    frames_since_change: u32,
+}
+
+impl ResolutionController {
+    pub(crate) fn new(settings: DynamicResolution) -> Self {
+        Self {
+            settings,
+            scale: settings.max_scale,
+            frame_time: None,
+            frames_since_change: 0,
+        }
+    }
+
+    pub(crate) fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    // Feeds in a frame time, returning the new scale when the render size should change
+    pub(crate) fn update(&mut self, frame_time: Duration) -> Option<f32> {
+        let measured = frame_time.as_secs_f32();
+
+        let smoothed = match self.frame_time {
+            Some(smoothed) => smoothed + (measured - smoothed) * SMOOTHING,
+            None => measured,
+        };
+
+        self.frame_time = Some(smoothed);
+        self.frames_since_change += 1;
+
+        if self.frames_since_change < COOLDOWN_FRAMES || smoothed <= 0.0 {
+            return None;
+        }
+
+        // Tracing cost goes with the pixel count, which is the square of the scale
+        let target = self.settings.target_frame_time.as_secs_f32();
+        let ideal = self.scale * (target / smoothed).sqrt();
+        let new_scale = ideal.clamp(self.settings.min_scale, self.settings.max_scale);
+
+        // Small steps onto one of the bounds are still taken, or we'd settle just short of them
+        let at_bound = new_scale == self.settings.min_scale || new_scale == self.settings.max_scale;
+        let small_change = (new_scale - self.scale).abs() < MIN_CHANGE * self.scale;
+
+        if new_scale == self.scale || (small_change && !at_bound) {
+            return None;
+        }
+
+        self.scale = new_scale;
+        self.frame_time = None;
+        self.frames_since_change = 0;
+
+        Some(new_scale)
+    }
+}
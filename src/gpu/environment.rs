@@ -0,0 +1,72 @@
+use crate::engine::background::EnvironmentImage;
+
+// Textures for the environment map, both are only ever read with textureLoad so they don't need to be filterable
+// When the background isn't an environment map we keep 1x1 placeholders bound, since the bindgroup always needs something
+pub struct EnvironmentTextures {
+    pub env_view: wgpu::TextureView,
+    pub cdf_view: wgpu::TextureView,
+}
+
+impl EnvironmentTextures {
+    pub const ENV_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+    pub const CDF_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, image: Option<&EnvironmentImage>) -> Self {
+        let (env_view, cdf_view) = match image {
+            Some(image) => (
+                Self::create_texture(device, queue, "Environment Map Texture", Self::ENV_FORMAT, image.width, image.height, bytemuck::cast_slice(&image.pixels)),
+                Self::create_texture(device, queue, "Environment CDF Texture", Self::CDF_FORMAT, image.cdf_width, image.height + 1, bytemuck::cast_slice(&image.cdf)),
+            ),
+            None => (
+                Self::create_texture(device, queue, "Environment Map Placeholder", Self::ENV_FORMAT, 1, 1, &[0; 16]),
+                Self::create_texture(device, queue, "Environment CDF Placeholder", Self::CDF_FORMAT, 1, 1, &[0; 4]),
+            ),
+        };
+
+        Self { env_view, cdf_view }
+    }
+
+    fn create_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &'static str,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> wgpu::TextureView {
+        let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        // Both formats are a single block per texel, so a row is just width texels
+        let bytes_per_texel = format.block_copy_size(None).unwrap_or(4);
+
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * bytes_per_texel),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+}
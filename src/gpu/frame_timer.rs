@@ -0,0 +1,142 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+// Measures how long the ray tracing pass takes on the gpu with timestamp queries
+// Reading the result back is never waited on, so a measurement shows up a frame or two after the pass ran
+// Only one measurement is in flight at a time, frames that start while one is pending just aren't timed
+pub struct GpuFrameTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+
+    // Nanoseconds per timestamp tick
+    period: f32,
+
+    state: TimerState,
+
+    // Set from the map_async callback, Ok once the readback buffer can be read
+    map_result: Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum TimerState {
+    Idle,
+    Recording,
+    Mapping,
+}
+
+impl GpuFrameTimer {
+    // Begin and end of the pass
+    const QUERY_COUNT: u32 = 2;
+    const BUFFER_SIZE: u64 = Self::QUERY_COUNT as u64 * size_of::<u64>() as u64;
+
+    // None when the device doesn't have timestamp queries, in which case we time on the cpu instead
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Frame Timer Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: Self::QUERY_COUNT,
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Timer Resolve Buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Timer Readback Buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            state: TimerState::Idle,
+            map_result: Arc::default(),
+        })
+    }
+
+    // Timestamp writes for this frame's pass, None while the last measurement is still being read back
+    pub fn timestamp_writes(&mut self) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        if self.state != TimerState::Idle {
+            return None;
+        }
+
+        self.state = TimerState::Recording;
+
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        })
+    }
+
+    // Has to be recorded after the timed pass, in the same encoder
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.state != TimerState::Recording {
+            return;
+        }
+
+        encoder.resolve_query_set(&self.query_set, 0..Self::QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, Self::BUFFER_SIZE);
+    }
+
+    // Starts reading back the timestamps, call once the encoder with the resolve is submitted
+    pub fn after_submit(&mut self) {
+        if self.state != TimerState::Recording {
+            return;
+        }
+
+        self.state = TimerState::Mapping;
+
+        let map_result = self.map_result.clone();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |res| {
+            *map_result.lock().unwrap_or_else(PoisonError::into_inner) = Some(res);
+        });
+    }
+
+    // Returns the last measured pass time if it has finished reading back, without blocking
+    // A failed map goes back to the caller, the timer is left idle but it's up to them whether to keep using it
+    pub fn try_read(&mut self, device: &wgpu::Device) -> Result<Option<Duration>, wgpu::BufferAsyncError> {
+        if self.state != TimerState::Mapping {
+            return Ok(None);
+        }
+
+        // Lets wgpu run the map callback if the gpu got to it, a failed poll just means we try again next frame
+        let _ = device.poll(wgpu::PollType::Poll);
+
+        let map_result = self.map_result.lock().unwrap_or_else(PoisonError::into_inner).take();
+
+        match map_result {
+            None => return Ok(None),
+            Some(Err(e)) => {
+                self.readback_buffer.unmap();
+                self.state = TimerState::Idle;
+
+                return Err(e);
+            },
+            Some(Ok(())) => {},
+        }
+
+        let timestamps: [u64; 2] = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::pod_read_unaligned(&data)
+        };
+
+        self.readback_buffer.unmap();
+        self.state = TimerState::Idle;
+
+        let ticks = timestamps[1].saturating_sub(timestamps[0]);
+        Ok(Some(Duration::from_nanos((ticks as f64 * self.period as f64) as u64)))
+    }
+}
@@ -1,10 +1,16 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Instant;
 
 use crate::core::error::GpuError;
 use super::init_utils::{
     create_sampler,
-    create_screen_texture,
+    offscreen_surface_config,
+    TEXTURE_FORMAT,
     make_device_queue_surface_config,
+    make_headless_device_queue,
 };
 
 use super::shaders::{
@@ -12,72 +18,598 @@ use super::shaders::{
     RenderShader,
 };
 
-use super::buffers::GpuBuffers;
+use super::buffers::{GpuBufferIds, GpuBuffers, GpuUniformRenderSettings, GpuUniformToneMap};
+use super::environment::EnvironmentTextures;
+use super::aovs::{AovIds, AovTextures};
+use super::post::PostChain;
+use super::render_graph::{RenderGraph, RunContext, TextureId};
+use super::readback::read_textures;
+use super::frame_timer::GpuFrameTimer;
+use super::dynamic_resolution::ResolutionController;
+use super::render_commands::RenderCommand;
+use super::tick_interpolation::TickInterpolator;
+use crate::engine::background::PreparedBackground;
+use crate::engine::export::{ExportRequest, FrameCapture};
+use crate::engine::post::PreparedPostEffect;
+use crate::core::config::{LatrConfig, RenderMode, RenderResolution, ToneMapping};
 
 pub struct GpuCore {
-    compute_raytrace_shader: ComputeRaytraceShader,
-    render_shader: RenderShader,
+    // The trace, the post effects and the blit, in that order, see with_device
+    graph: RenderGraph,
+
+    // Where the trace writes, kept around for exporting
+    screen: TextureId,
+    aov_ids: AovIds,
+
+    // Handles to the aov textures in the graph, taken again after every refresh, see refresh_graph
+    aovs: AovTextures,
+
+    tone_map: GpuUniformToneMap,
+    render_mode: RenderMode,
+
+    // Exports wait for the next frame to be drawn, then get read back at the end of render
+    pending_exports: Vec<ExportRequest>,
+
+    // Exports drawn by render_into, which can't be read back until the caller has submitted the frame
+    recorded_exports: Vec<ExportRequest>,
 
     pub(crate) buffers: GpuBuffers,
-    window_dimensions: (u32, u32),
+
+    // The screen texture size comes from these and the window, see RenderResolution
+    num_rays: (u32, u32),
+    render_resolution: RenderResolution,
+
+    // Only there when dynamic resolution is on, the timer also needs the device to have timestamp queries
+    // Without the timer, frames are timed on the cpu from last_frame_start
+    resolution_controller: Option<ResolutionController>,
+    frame_timer: Option<GpuFrameTimer>,
+    last_frame_start: Option<Instant>,
+
+    // Blends the camera and lights between the last two physics ticks, see LatrConfig::interpolate_ticks
+    tick_interpolator: TickInterpolator,
+
+    // A minimized window has a 0 size surface, which can't be configured, so we stop drawing until it comes back
+    minimized: bool,
+
+    // Last background set, kept so the environment textures can be made again after a device loss
+    background: Option<Box<PreparedBackground>>,
+
+    // Set from the device lost callback, the next frame makes a new device, see recover_device
+    device_lost: Arc<AtomicBool>,
+
+    // What the device was made from, None for the window when running headless or on the caller's device
+    window: Option<Arc<winit::window::Window>>,
+    latr_config: LatrConfig,
 
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
 
-    pub surface: wgpu::Surface<'static>,
+    // None when running headless, where frames are only traced for exporting
+    pub surface: Option<wgpu::Surface<'static>>,
     pub config: wgpu::SurfaceConfiguration,
 }
 
 impl GpuCore {
-    pub fn new(window: Arc<winit::window::Window>, ) -> Result<Self, GpuError> {
-        
-        let (device, queue, surface, config) = make_device_queue_surface_config(window.clone())?;
-        let (width, height) = window.inner_size().into();
+    pub fn new(window: Arc<winit::window::Window>, latr_config: &LatrConfig) -> Result<Self, GpuError> {
+        let (device, queue, surface, config) = make_device_queue_surface_config(
+            window.clone(), latr_config.present_mode, &latr_config.adapter, &latr_config.scene_capacity,
+        )?;
+
+        let mut gpu_core = Self::with_device(device, queue, Some(surface), config, latr_config)?;
+        gpu_core.window = Some(window);
+        gpu_core.watch_device_loss();
+
+        Ok(gpu_core)
+    }
+
+    // No window, frames are traced at num_rays and only read back for exports, see LatrEngine::replay
+    pub(crate) fn new_headless(latr_config: &LatrConfig) -> Result<Self, GpuError> {
+        let (device, queue) = make_headless_device_queue(&latr_config.adapter, &latr_config.scene_capacity)?;
+        let config = offscreen_surface_config(wgpu::TextureFormat::Rgba8UnormSrgb, latr_config.num_rays);
+
+        let mut gpu_core = Self::with_device(device, queue, None, config, latr_config)?;
+        gpu_core.watch_device_loss();
+
+        // Anything timed by the wall clock would make frames differ from one run to the next
+        gpu_core.resolution_controller = None;
+        gpu_core.frame_timer = None;
+        gpu_core.tick_interpolator = TickInterpolator::new(false);
+
+        Ok(gpu_core)
+    }
+
+    // Runs on a device someone else made, drawing into their textures with render_into, see LatrRenderer
+    // The device has to have the limits the scene buffers need, which GpuBuffers::new checks
+    pub(crate) fn from_device(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        target_format: wgpu::TextureFormat,
+        target_size: (u32, u32),
+        latr_config: &LatrConfig,
+    ) -> Result<Self, GpuError> {
+        let config = offscreen_surface_config(target_format, target_size);
+
+        let mut gpu_core = Self::with_device(device, queue, None, config, latr_config)?;
+
+        // The timer maps its readback right after submitting, which only we can do when the encoder is ours
+        // Dynamic resolution still works, timed on the cpu instead
+        gpu_core.frame_timer = None;
+
+        Ok(gpu_core)
+    }
+
+    fn with_device(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        surface: Option<wgpu::Surface<'static>>,
+        config: wgpu::SurfaceConfiguration,
+        latr_config: &LatrConfig,
+    ) -> Result<Self, GpuError> {
+        let resolution_controller = latr_config.dynamic_resolution.map(ResolutionController::new);
+        let frame_timer = resolution_controller.as_ref().and_then(|_| GpuFrameTimer::new(&device, &queue));
+
+        let (width, height) = scale_size(
+            latr_config.render_resolution.render_size(latr_config.num_rays, (config.width, config.height)),
+            resolution_controller.as_ref().map_or(1.0, |controller| controller.scale()),
+        );
 
-        // Since we only want a 2d one, we just do width and height with the z axis as 1
-        let texture_size = wgpu::Extent3d { width, height, depth_or_array_layers: 1, };
         let sampler = create_sampler(&device);
+        let buffers = GpuBuffers::new(&device, &latr_config.scene_capacity)?;
+
+        // Every texture the frame goes through lives in the graph, which sizes them all at the render size
+        let mut graph = RenderGraph::new((width, height));
 
         // This is our main screen texture that is written to and read from throughout our program
-        let screen_texture = create_screen_texture(&device, texture_size);
-        let screen_texture_view = screen_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        
-        let buffers = GpuBuffers::new(&device);
-
-        let compute_raytrace_shader = ComputeRaytraceShader::new(
-            &device,
-            &buffers,
-            &screen_texture_view,
+        // ! Compute buffer must output this format and the fragment shader must read this format correctly
+        // COPY_SRC is for reading the frame back when exporting, TEXTURE_BINDING for LatrRenderer::output_texture
+        let screen = graph.add_texture(
+            "Screen Texture",
+            TEXTURE_FORMAT,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            true,
         );
 
-        let render_shader = RenderShader::new(
-            &device,
+        // Placeholders until they're turned on, see set_aovs
+        let aov_ids = AovIds::add_to(&mut graph);
+        let buffer_ids = GpuBufferIds::add_to(&mut graph);
 
-            // We pass these in as owned
-            screen_texture_view,
-            sampler,
+        // Placeholder until a background is set, see set_background
+        let environment = EnvironmentTextures::new(&device, &queue, None);
 
-            &config,
-        );
+        let compute_raytrace_shader = ComputeRaytraceShader::new(&device, &buffers, buffer_ids, environment, screen, aov_ids);
+
+        // When the surface isn't sRGB, the blit has to gamma encode by hand, see choose_surface_format
+        let tone_map = GpuUniformToneMap {
+            kind: ToneMapping::default().to_gpu(),
+            encode_srgb: u32::from(!config.format.is_srgb()),
+            ..Default::default()
+        };
+        buffers.write_tone_map(&queue, &tone_map);
+
+        let post_chain = PostChain::new(&device, &queue, &mut graph, screen);
+
+        // The sampler is passed in as owned
+        let render_shader = RenderShader::new(&device, &buffers, buffer_ids, screen, sampler, &config, latr_config.scale_mode);
+
+        graph.add_node(compute_raytrace_shader);
+        graph.add_node(post_chain);
+        graph.add_node(render_shader);
+        graph.refresh(&device, &queue)?;
+
+        let aovs = AovTextures::from_graph(graph.textures(), &aov_ids, false);
 
         Ok(Self {
-            compute_raytrace_shader,
-            render_shader,
+            graph,
+            screen,
+            aov_ids,
+            aovs,
+            tone_map,
+            render_mode: RenderMode::default(),
+
+            pending_exports: Vec::new(),
+            recorded_exports: Vec::new(),
 
             buffers,
-            window_dimensions: (width, height),
+
+            num_rays: latr_config.num_rays,
+            render_resolution: latr_config.render_resolution,
+            resolution_controller,
+            frame_timer,
+            last_frame_start: None,
+            tick_interpolator: TickInterpolator::new(latr_config.interpolate_ticks),
+            minimized: false,
+            background: None,
+            device_lost: Arc::new(AtomicBool::new(false)),
+            window: None,
+            latr_config: latr_config.clone(),
             
             device, queue,
             surface, config,
         })
     }
 
+    // Driver resets, the gpu being unplugged and running out of memory all end up here
+    // Only done for devices we made, a device from the caller keeps whatever callbacks they gave it
+    fn watch_device_loss(&self) {
+        let lost_flag = self.device_lost.clone();
+
+        self.device.set_device_lost_callback(move |reason, message| {
+            eprintln!("Gpu device lost ({reason:?}): {message}");
+            lost_flag.store(true, Ordering::Release);
+        });
+
+        // Once the device is gone, everything made from it errors until the next frame recovers
+        // Those are expected, anything else is a bug and stays fatal, same as wgpu's own handler
+        let lost_flag = self.device_lost.clone();
+
+        self.device.on_uncaptured_error(Arc::new(move |error| {
+            if !lost_flag.load(Ordering::Acquire) {
+                panic!("wgpu error: {error}");
+            }
+
+            eprintln!("Gpu error on a lost device: {error}");
+        }));
+    }
+
+    // A post effect shader that doesn't compile leaves the chain as it was
+    // Anything that changes the render graph can also fail if the graph no longer fits together
+    pub(crate) fn apply_command(&mut self, command: RenderCommand) -> Result<(), GpuError> {
+        match command {
+            RenderCommand::Background(background) => return self.set_background(background),
+            RenderCommand::ToneMapping(tone_mapping) => self.set_tone_mapping(tone_mapping),
+            RenderCommand::Exposure(exposure) => self.set_exposure(exposure),
+            RenderCommand::RenderMode(render_mode) => return self.set_render_mode(render_mode),
+            RenderCommand::Aovs(enabled) => return self.set_aovs(enabled),
+            RenderCommand::Export(request) => self.pending_exports.push(request),
+            RenderCommand::Tick(tick) => self.tick_interpolator.push(tick),
+            RenderCommand::UploadScene => self.buffers.upload_scene(&self.queue),
+            RenderCommand::PostEffects(effects) => return self.set_post_effects(effects),
+            RenderCommand::PostEffect(index, effect) => return self.set_post_effect(index, *effect),
+            RenderCommand::PostParams(index, values) => self.set_post_params(index, &values),
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn set_post_effects(&mut self, effects: Vec<PreparedPostEffect>) -> Result<(), GpuError> {
+        let post_chain = self.graph.node_mut::<PostChain>().expect("the post chain is always in the render graph");
+        post_chain.set_effects(&self.device, &self.queue, effects)?;
+
+        self.rebind_blit()
+    }
+
+    fn set_post_effect(&mut self, index: usize, effect: PreparedPostEffect) -> Result<(), GpuError> {
+        let post_chain = self.graph.node_mut::<PostChain>().expect("the post chain is always in the render graph");
+        post_chain.set_effect(&self.device, &self.queue, index, effect)?;
+
+        self.rebind_blit()
+    }
+
+    // Only writes a uniform, so there's nothing for the graph to refresh
+    fn set_post_params(&mut self, index: usize, values: &[f32]) {
+        if let Some(post_chain) = self.graph.node_mut::<PostChain>() {
+            post_chain.set_params(&self.queue, index, values);
+        }
+    }
+
+    pub(crate) fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_map.kind = tone_mapping.to_gpu();
+        self.write_tone_map();
+    }
+
+    pub(crate) fn set_exposure(&mut self, exposure: f32) {
+        self.tone_map.exposure = exposure;
+        self.write_tone_map();
+    }
+
+    pub(crate) fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
+    pub(crate) fn set_render_mode(&mut self, render_mode: RenderMode) -> Result<(), GpuError> {
+        self.render_mode = render_mode;
+        self.write_render_settings();
+
+        // Tone mapping and post effects depend on the mode too, see write_tone_map and rebind_blit
+        self.write_tone_map();
+        self.rebind_blit()
+    }
+
+    // Turning aovs off lets the graph shrink them back to placeholders, freeing the full size textures
+    pub(crate) fn set_aovs(&mut self, enabled: bool) -> Result<(), GpuError> {
+        if enabled == self.aovs.enabled() {
+            return Ok(());
+        }
+
+        self.raytrace_mut().aovs_enabled = enabled;
+        self.refresh_graph()?;
+        self.write_render_settings();
+
+        Ok(())
+    }
+
+    fn write_render_settings(&self) {
+        let settings = GpuUniformRenderSettings {
+            mode: self.render_mode.to_gpu(),
+            aovs: u32::from(self.aovs.enabled()),
+            ..Default::default()
+        };
+
+        self.buffers.write_render_settings(&self.queue, &settings);
+    }
+
+    // Debug views need their colors to reach the screen untouched, so they skip exposure and tone mapping
+    // The user's settings are kept around for when they go back to Shaded
+    fn write_tone_map(&self) {
+        let mut tone_map = self.tone_map;
+
+        if self.render_mode != RenderMode::Shaded {
+            tone_map.kind = ToneMapping::None.to_gpu();
+            tone_map.exposure = 0.0;
+        }
+
+        self.buffers.write_tone_map(&self.queue, &tone_map);
+    }
+
+    pub(crate) fn set_background(&mut self, background: Box<PreparedBackground>) -> Result<(), GpuError> {
+        self.buffers.write_background(&self.queue, &background.uniform);
+
+        // Non environment backgrounds swap back to the placeholder textures, freeing the old map
+        let environment = EnvironmentTextures::new(&self.device, &self.queue, background.environment.as_ref());
+        self.raytrace_mut().environment = environment;
+        self.background = Some(background);
+
+        self.refresh_graph()
+    }
+
+    // Reconfigures the surface for the new window size
+    // The screen texture only gets remade if the render resolution follows the window
+    pub(crate) fn resize(&mut self, width: u32, height: u32) -> Result<(), GpuError> {
+        self.minimized = width == 0 || height == 0;
+
+        if self.minimized {
+            return Ok(());
+        }
+
+        self.config.width = width;
+        self.config.height = height;
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+
+        self.update_render_target()
+    }
+
+    // Called when getting a frame says the surface no longer matches the window, like after moving to another monitor
+    // The window size is asked for again, since the Resized event may not have come in yet
+    fn reconfigure_surface(&mut self) -> Result<(), GpuError> {
+        match &self.window {
+            Some(window) => {
+                let size = window.inner_size();
+                self.resize(size.width, size.height)
+            },
+            None => self.resize(self.config.width, self.config.height),
+        }
+    }
+
+    // Makes a new device after the old one was lost, then puts back everything that lived on it
+    // The scene comes from the copy GpuBuffers keeps on the cpu, and settings changed since startup carry over
+    fn recover_device(&mut self) -> Result<(), GpuError> {
+        eprintln!("Recreating the gpu device");
+
+        let mut recovered = match &self.window {
+            Some(window) => Self::new(window.clone(), &self.latr_config)?,
+            None => Self::new_headless(&self.latr_config)?,
+        };
+
+        recovered.buffers.restore_scene(&recovered.queue, &self.buffers);
+
+        // encode_srgb stays as the new surface wants it, see with_device
+        recovered.tone_map.kind = self.tone_map.kind;
+        recovered.tone_map.exposure = self.tone_map.exposure;
+        recovered.set_render_mode(self.render_mode)?;
+        recovered.set_aovs(self.aovs.enabled())?;
+
+        if let Some(background) = self.background.take() {
+            recovered.set_background(background)?;
+        }
+
+        recovered.set_post_effects(self.post_chain().effects())?;
+
+        // Headless cores turn these off, so moving them over keeps it that way
+        recovered.resolution_controller = self.resolution_controller.take();
+        recovered.update_render_target()?;
+
+        recovered.tick_interpolator = std::mem::replace(&mut self.tick_interpolator, TickInterpolator::new(false));
+        recovered.tick_interpolator.mark_dirty();
+        recovered.pending_exports = std::mem::take(&mut self.pending_exports);
+
+        *self = recovered;
+
+        Ok(())
+    }
+
+    // Size the screen texture should be, from the window, the render resolution and the dynamic resolution scale
+    fn render_size(&self) -> (u32, u32) {
+        let base = self.render_resolution.render_size(self.num_rays, (self.config.width, self.config.height));
+        let scale = self.resolution_controller.as_ref().map_or(1.0, |controller| controller.scale());
+
+        scale_size(base, scale)
+    }
+
+    fn update_render_target(&mut self) -> Result<(), GpuError> {
+        let size = self.render_size();
+
+        if size != self.graph.size() {
+            self.graph.resize(size);
+            self.refresh_graph()?;
+        }
+
+        Ok(())
+    }
+
+    // Feeds the latest frame time to the controller, resizing the screen texture if it picks a new scale
+    fn update_dynamic_resolution(&mut self) -> Result<(), GpuError> {
+        let Some(controller) = &mut self.resolution_controller else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+
+        let frame_time = match &mut self.frame_timer {
+            Some(timer) => match timer.try_read(&self.device) {
+                Ok(frame_time) => frame_time,
+
+                // Without a readback the controller would never hear about another frame, so the cpu takes over timing
+                Err(e) => {
+                    eprintln!("Gpu frame timer failed, timing frames on the cpu instead: {e}");
+                    self.frame_timer = None;
+
+                    None
+                },
+            },
+            None => self.last_frame_start.replace(now).map(|last| now - last),
+        };
+
+        if let Some(frame_time) = frame_time
+            && controller.update(frame_time).is_some() {
+            return self.update_render_target();
+        }
+
+        Ok(())
+    }
+
+    // The blit reads whatever the last post effect wrote, or the screen texture when there aren't any
+    // Debug views skip the post effects, same as they skip tone mapping
+    fn rebind_blit(&mut self) -> Result<(), GpuError> {
+        let shaded = self.render_mode == RenderMode::Shaded;
+        let screen = self.screen;
+
+        let post_chain = self.post_chain_mut();
+        post_chain.enabled = shaded;
+        let source = post_chain.output().unwrap_or(screen);
+
+        if let Some(render_shader) = self.graph.node_mut::<RenderShader>() {
+            render_shader.source = source;
+        }
+
+        self.refresh_graph()
+    }
+
+    // Makes anything the nodes now declare and rebinds whatever changed
+    // The nodes here only ever declare valid passes, so a failure would be a bug in one of them, but it still
+    // goes back to the caller rather than taking the render thread down
+    fn refresh_graph(&mut self) -> Result<(), GpuError> {
+        self.graph.refresh(&self.device, &self.queue)?;
+        self.aovs = AovTextures::from_graph(self.graph.textures(), &self.aov_ids, self.aovs_enabled());
+
+        Ok(())
+    }
+
+    fn aovs_enabled(&self) -> bool {
+        self.graph.node::<ComputeRaytraceShader>().is_some_and(|raytrace| raytrace.aovs_enabled)
+    }
+
+    // The graph is built in with_device, so these are always there
+    fn raytrace_mut(&mut self) -> &mut ComputeRaytraceShader {
+        self.graph.node_mut().expect("the trace is always in the render graph")
+    }
+
+    fn post_chain(&self) -> &PostChain {
+        self.graph.node().expect("the post chain is always in the render graph")
+    }
+
+    fn post_chain_mut(&mut self) -> &mut PostChain {
+        self.graph.node_mut().expect("the post chain is always in the render graph")
+    }
+
+    // Reads the frame that was just drawn back to the cpu for every export that's waiting on one
+    // A failed readback goes back to whoever asked, rather than stopping the render thread
+    fn finish_exports(&mut self) {
+        for request in std::mem::take(&mut self.pending_exports) {
+            // The engine may have stopped waiting, in which case nobody needs the frame
+            let _ = request.reply.send(self.capture_frame());
+        }
+    }
+
+    // Drops every export that hasn't been read back, for when nothing else is going to be drawn
+    // The engine sees the reply channel close and stops waiting, see Engine::export_frame
+    pub(crate) fn cancel_exports(&mut self) {
+        self.pending_exports.clear();
+        self.recorded_exports.clear();
+    }
+
+    // Answers the exports waiting on a frame that isn't going to be drawn, rather than leaving the engine blocked on them
+    fn skip_exports(&mut self) {
+        for request in std::mem::take(&mut self.pending_exports) {
+            let _ = request.reply.send(Err(GpuError::ExportFrameSkipped));
+        }
+    }
+
+    fn capture_frame(&self) -> Result<FrameCapture, GpuError> {
+        let screen_texture = self.screen_texture();
+        let mut textures = vec![screen_texture];
+
+        if self.aovs.enabled() {
+            textures.extend([&self.aovs.albedo, &self.aovs.normal_depth, &self.aovs.position_id]);
+        }
+
+        let mut pixels = read_textures(&self.device, &self.queue, &textures)?.into_iter();
+
+        Ok(FrameCapture {
+            width: screen_texture.width(),
+            height: screen_texture.height(),
+            beauty: pixels.next().unwrap_or_default(),
+            albedo: pixels.next(),
+            normal_depth: pixels.next(),
+            position_id: pixels.next(),
+        })
+    }
+
+    // Camera and lights for this frame, somewhere between the last two ticks
+    fn write_tick_state(&mut self) {
+        let Some((camera, lights)) = self.tick_interpolator.frame_state(Instant::now()) else {
+            return;
+        };
+
+        self.buffers.write_camera(&self.queue, &camera);
+
+        // Engine::add_light keeps the count under the buffer size, so this can't fail
+        let _ = self.buffers.write_lights(&self.queue, &lights);
+    }
+
     pub fn render(&mut self) -> Result<(), GpuError> {
-        let output = self.surface.get_current_texture()?;
+        if self.minimized {
+            self.skip_exports();
+            return Ok(());
+        }
+
+        if self.device_lost.load(Ordering::Acquire) {
+            self.recover_device()?;
+        }
+
+        self.update_dynamic_resolution()?;
+        self.write_tick_state();
 
-        let output_texture_view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Headless frames stop after the trace, there's nothing to blit them to
+        let output = match &self.surface {
+            Some(surface) => match surface.get_current_texture() {
+                Ok(output) => Some(output),
+
+                // Nothing gets drawn for skipped frames, so the exports waiting on them fail
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                    self.skip_exports();
+                    return self.reconfigure_surface();
+                },
+                Err(wgpu::SurfaceError::Timeout) => {
+                    self.skip_exports();
+                    return Ok(());
+                },
+                Err(wgpu::SurfaceError::OutOfMemory) => return Err(GpuError::SurfaceOutOfMemory),
+                Err(e) => return Err(e.into()),
+            },
+            None => None,
+        };
 
         // Make the command encoder
         let mut encoder = self
@@ -86,30 +618,96 @@ impl GpuCore {
                 label: Some("Main Encoder"),
             });
 
-        // Run compute raytracer
-        { self.compute_raytrace_shader.run_compute_pass(
-            &mut encoder,
-            &self.buffers,
-            self.window_dimensions.0,
-            self.window_dimensions.1,
-        ); };
-
-        // The compute pass is now recorded in the encoder.
+        let output_texture_view = output.as_ref().map(|output| {
+            output.texture.create_view(&wgpu::TextureViewDescriptor::default())
+        });
 
-        // Now we run the render pass
-        { self.render_shader.run_render_pass(
-            &mut encoder,
-            &output_texture_view
-        ); };
+        self.encode_frame(&mut encoder, output_texture_view.as_ref());
 
         // The render pass is now recorded in the encoder.
 
         // Run the setup encoder
         self.queue.submit(std::iter::once(encoder.finish()));
 
+        if let Some(timer) = &mut self.frame_timer {
+            timer.after_submit();
+        }
+
+        self.finish_exports();
+
         // Tell the window to present this queue
-        output.present();
+        // A suboptimal frame still shows fine, but the surface gets fixed up for the next one
+        if let Some(output) = output {
+            let suboptimal = output.suboptimal;
+            output.present();
+
+            if suboptimal {
+                self.reconfigure_surface()?;
+            }
+        }
 
         Ok(())
     }
+
+    // Same frame as render, recorded into the caller's encoder and drawn over their view, see LatrRenderer::render_into
+    // The view has to be the size and format given to from_device, or whatever resize last set
+    // With no view only the trace is recorded, for callers that take the screen texture from there themselves
+    pub(crate) fn render_into(&mut self, encoder: &mut wgpu::CommandEncoder, view: Option<&wgpu::TextureView>) -> Result<(), GpuError> {
+        // The last frame has been submitted by now, and nothing has traced over it yet
+        for request in std::mem::take(&mut self.recorded_exports) {
+            let _ = request.reply.send(self.capture_frame());
+        }
+
+        if self.minimized {
+            self.skip_exports();
+            return Ok(());
+        }
+
+        self.update_dynamic_resolution()?;
+        self.write_tick_state();
+        self.encode_frame(encoder, view);
+
+        self.recorded_exports = std::mem::take(&mut self.pending_exports);
+
+        Ok(())
+    }
+
+    // Linear hdr color straight out of the trace, in TEXTURE_FORMAT at the render size
+    // Made again whenever the render size changes, so anything bound to it has to be rebuilt then
+    pub(crate) fn screen_texture(&self) -> &wgpu::Texture {
+        self.graph.textures().texture(self.screen)
+    }
+
+    pub(crate) fn screen_texture_view(&self) -> &wgpu::TextureView {
+        self.graph.textures().view(self.screen)
+    }
+
+    // None while they're off, the placeholders aren't worth handing out
+    pub(crate) fn aovs(&self) -> Option<&AovTextures> {
+        self.aovs.enabled().then_some(&self.aovs)
+    }
+
+    // Records every node of the graph, which skips the post effects and the blit when there's no view to draw to,
+    // so exports and trace_into get the frame straight from the trace
+    fn encode_frame(&mut self, encoder: &mut wgpu::CommandEncoder, view: Option<&wgpu::TextureView>) {
+        let timestamp_writes = self.frame_timer.as_mut().and_then(|timer| timer.timestamp_writes());
+
+        self.graph.run(encoder, RunContext {
+            textures: self.graph.textures(),
+            target: view,
+            target_size: (self.config.width, self.config.height),
+            timestamp_writes,
+        });
+
+        if let Some(timer) = &self.frame_timer {
+            timer.resolve(encoder);
+        }
+    }
+}
+
+fn scale_size(size: (u32, u32), scale: f32) -> (u32, u32) {
+    (
+        ((size.0 as f32 * scale).round() as u32).max(1),
+        ((size.1 as f32 * scale).round() as u32).max(1),
+    )
 }
\ No newline at end of file
@@ -1,33 +1,26 @@
 use crate::core::error::GpuError;
+use crate::core::config::{AdapterPreferences, PresentMode, SceneCapacity};
+use super::adapter::choose_adapter;
+use super::buffers::GpuBuffers;
 use std::sync::Arc;
 
-pub fn make_device_queue_surface_config(window_arc: Arc<winit::window::Window>) -> Result<(wgpu::Device, wgpu::Queue, wgpu::Surface<'static>, wgpu::SurfaceConfiguration), GpuError> {
-    let instance = wgpu::Instance::default();
+pub fn make_device_queue_surface_config(
+    window_arc: Arc<winit::window::Window>,
+    present_mode: PresentMode,
+    adapter_preferences: &AdapterPreferences,
+    scene_capacity: &SceneCapacity,
+) -> Result<(wgpu::Device, wgpu::Queue, wgpu::Surface<'static>, wgpu::SurfaceConfiguration), GpuError> {
+    let instance = make_instance(adapter_preferences);
     let surface = instance.create_surface(window_arc.clone())?;
 
-    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::HighPerformance,
-        compatible_surface: Some(&surface),
-        force_fallback_adapter: false,
-    }))?;
+    let adapter = choose_adapter(&instance, adapter_preferences, Some(&surface))?;
 
-    let (device, queue) = pollster::block_on(adapter.request_device( &wgpu::DeviceDescriptor {
-        label: None,
-        required_features: Default::default(),
-        required_limits: Default::default(),
-        experimental_features: Default::default(),
-        memory_hints: Default::default(),
-        trace: Default::default(),
-    },))?;
+    let (device, queue) = request_device(&adapter, scene_capacity)?;
 
     let size = window_arc.inner_size();
     let surface_caps = surface.get_capabilities(&adapter);
-    let surface_format =
-        surface_caps
-        .formats
-        .first()
-        .ok_or(GpuError::NoSupportedFormats)?
-        .clone();
+    let surface_format = choose_surface_format(&surface_caps.formats)?;
+    let present_mode = choose_present_mode(present_mode, &surface_caps.present_modes);
 
     let alpha_mode =
         surface_caps
@@ -41,8 +34,10 @@ pub fn make_device_queue_surface_config(window_arc: Arc<winit::window::Window>)
         format: surface_format,
         width: size.width,
         height: size.height,
-        present_mode: wgpu::PresentMode::Fifo,
-        desired_maximum_frame_latency: 0,
+        present_mode,
+
+        // Two frames in flight, so the cpu can record the next frame while the last one is still drawing
+        desired_maximum_frame_latency: 2,
         alpha_mode: alpha_mode,
         view_formats: vec![],
     };
@@ -52,28 +47,117 @@ pub fn make_device_queue_surface_config(window_arc: Arc<winit::window::Window>)
     Ok((device, queue, surface, config))
 }
 
-// Creates the texture we write to with the compute shader and read from the fragment shader
-// Important constants
-    // Texture format that we're using for the screen texture
-    pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
-
-pub fn create_screen_texture(device: &wgpu::Device, texture_size: wgpu::Extent3d) -> wgpu::Texture {
-    device.create_texture(&wgpu::TextureDescriptor {
-        label: Some("Screen Texture"),
-        size: texture_size,
-        mip_level_count: 1,
-        sample_count: 1,
-        view_formats: &[],
-
-        dimension: wgpu::TextureDimension::D2,
-        // ! Compute buffer must output this format and the fragment shader must read this format correctly
-        format: TEXTURE_FORMAT,
-        
-        // OR operator merges the bitflags to mean both texture binding and storage binding
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING
+// Same as above without a window, for tracing frames that only get exported
+pub fn make_headless_device_queue(
+    adapter_preferences: &AdapterPreferences,
+    scene_capacity: &SceneCapacity,
+) -> Result<(wgpu::Device, wgpu::Queue), GpuError> {
+    let instance = make_instance(adapter_preferences);
+    let adapter = choose_adapter(&instance, adapter_preferences, None)?;
+
+    request_device(&adapter, scene_capacity)
+}
+
+// Only the backends we were asked for get set up, so a broken driver on another one can't get in the way
+pub fn make_instance(adapter_preferences: &AdapterPreferences) -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: adapter_preferences.wgpu_backends(),
+        ..Default::default()
     })
 }
 
+// Stands in for the surface configuration when we don't own a surface, either headless or drawing into someone else's
+// Nothing is ever presented with it, but the blit pipeline still gets built against its format and size
+pub fn offscreen_surface_config(format: wgpu::TextureFormat, size: (u32, u32)) -> wgpu::SurfaceConfiguration {
+    wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format,
+        width: size.0,
+        height: size.1,
+        present_mode: wgpu::PresentMode::Fifo,
+        desired_maximum_frame_latency: 2,
+        alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+        view_formats: vec![],
+    }
+}
+
+fn request_device(adapter: &wgpu::Adapter, scene_capacity: &SceneCapacity) -> Result<(wgpu::Device, wgpu::Queue), GpuError> {
+    let (device, queue) = pollster::block_on(adapter.request_device( &wgpu::DeviceDescriptor {
+        label: None,
+        // Timestamp queries are only used for timing frames, see GpuFrameTimer, so we take them if they're there
+        required_features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+        required_limits: required_limits(adapter, scene_capacity),
+        experimental_features: Default::default(),
+        memory_hints: Default::default(),
+        trace: Default::default(),
+    },))?;
+
+    Ok((device, queue))
+}
+
+// The default limits cap storage buffers at 128MB, which the BLAS and triangle buffers of a big scene blow past
+// When the planned buffers need more, we ask for as much as the adapter has
+// Anything still too big is caught by GpuBuffers::check_limits with the limit named, rather than failing here
+fn required_limits(adapter: &wgpu::Adapter, scene_capacity: &SceneCapacity) -> wgpu::Limits {
+    let mut limits = wgpu::Limits::default();
+
+    let largest = GpuBuffers::planned_sizes(scene_capacity)
+        .iter()
+        .map(|(_, size)| *size)
+        .max()
+        .unwrap_or(0);
+
+    if largest > limits.max_storage_buffer_binding_size as u64 || largest > limits.max_buffer_size {
+        let adapter_limits = adapter.limits();
+
+        limits.max_storage_buffer_binding_size = adapter_limits.max_storage_buffer_binding_size;
+        limits.max_buffer_size = adapter_limits.max_buffer_size;
+    }
+
+    limits
+}
+
+// Picks the format we present in
+// We always want an sRGB format, since then the hardware does the gamma encoding for us when the blit writes
+// If the surface has no sRGB formats, we take a plain 8 bit one and the blit shader encodes it by hand instead
+// Whoever uses this should check format.is_srgb() to know which of the two happened
+pub fn choose_surface_format(formats: &[wgpu::TextureFormat]) -> Result<wgpu::TextureFormat, GpuError> {
+    const PREFERRED: [wgpu::TextureFormat; 2] = [
+        wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    ];
+
+    const FALLBACK: [wgpu::TextureFormat; 2] = [
+        wgpu::TextureFormat::Bgra8Unorm,
+        wgpu::TextureFormat::Rgba8Unorm,
+    ];
+
+    PREFERRED.iter()
+        .find(|format| formats.contains(format))
+        .or_else(|| formats.iter().find(|format| format.is_srgb()))
+        .or_else(|| FALLBACK.iter().find(|format| formats.contains(format)))
+        .or_else(|| formats.first())
+        .copied()
+        .ok_or(GpuError::NoSupportedFormats)
+}
+
+// Takes the first mode in the preference order that the surface supports
+// Fifo is required to be supported everywhere, so it's the last resort
+pub fn choose_present_mode(requested: PresentMode, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    requested.preference_order()
+        .iter()
+        .find(|mode| supported.contains(mode))
+        .copied()
+        .unwrap_or(wgpu::PresentMode::Fifo)
+}
+
+// The texture we write to with the compute shader and read from the fragment shader, see GpuCore::with_device
+// Important constants
+    // Texture format that we're using for the screen texture
+    // This holds linear hdr color, which gets tone mapped down to the surface during the blit
+    // Rgba16Float is one of the few float formats that can be both a storage texture and filtered
+    pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
 // Creates the sampler that is used for scaling up the screen when changing resolutions
 // Note: Creates black bars when scaling up resolution
 // Important constants
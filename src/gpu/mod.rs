@@ -1,6 +1,16 @@
 pub(crate) mod gpu_core;
-mod init_utils;
+pub(crate) mod init_utils;
 mod pipelines;
 mod bindgroups;
 mod shaders;
-pub(crate) mod buffers;
\ No newline at end of file
+pub(crate) mod buffers;
+mod environment;
+pub(crate) mod render_commands;
+pub(crate) mod aovs;
+pub(crate) mod readback;
+mod frame_timer;
+pub(crate) mod dynamic_resolution;
+pub(crate) mod tick_interpolation;
+pub(crate) mod adapter;
+pub(crate) mod post;
+pub(crate) mod render_graph;
\ No newline at end of file
@@ -0,0 +1,386 @@
+use crate::core::error::GpuError;
+use crate::engine::post::{CubeLut, PreparedPostEffect};
+use super::init_utils::TEXTURE_FORMAT;
+use super::render_graph::{BindContext, NodeIo, RenderGraph, RenderNode, RunContext, TextureId};
+
+// End of imports
+
+pub const POST_PRELUDE_WGSL: &str = include_str!("../shaders/post/prelude.wgsl");
+
+const LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+// Input, source and output of a stage, see the prelude for what each one is
+type StageTargets = (TextureId, TextureId, TextureId);
+
+// One effect from the chain, with a pipeline for each of its stages
+struct PostEffectPass {
+    effect: PreparedPostEffect,
+    pipelines: Vec<wgpu::ComputePipeline>,
+    params_buffer: wgpu::Buffer,
+    lut_view: Option<wgpu::TextureView>,
+}
+
+// The post effects that run between the trace and the blit, see PostEffect
+// Stages ping pong between a few textures in the render graph, which only get declared while there are effects,
+// so the graph keeps them as 1x1 placeholders the rest of the time
+pub(crate) struct PostChain {
+    bindgroup_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    sampler: wgpu::Sampler,
+    frame_buffer: wgpu::Buffer,
+
+    // Bound for every effect that isn't color grading, since the bindgroup always needs something
+    placeholder_lut: wgpu::TextureView,
+
+    passes: Vec<PostEffectPass>,
+
+    // Where the trace left the frame, and the textures the stages write to
+    input: TextureId,
+    pool: [TextureId; 3],
+
+    // Debug views skip the chain, same as they skip tone mapping
+    pub(crate) enabled: bool,
+
+    // One for every stage of every pass, in the order they run
+    bindgroups: Vec<wgpu::BindGroup>,
+}
+
+impl PostChain {
+    pub(crate) fn new(device: &wgpu::Device, queue: &wgpu::Queue, graph: &mut RenderGraph, input: TextureId) -> Self {
+        let bindgroup_layout = create_post_bindgroup_layout(device);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            bind_group_layouts: &[&bindgroup_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Linear so chromatic aberration and custom passes can sample between pixels
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let frame_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post Frame Uniform Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let placeholder_lut = create_lut_view(device, queue, &CubeLut { size: 1, texels: vec![[0.0; 4]] });
+
+        // Same format as the screen texture, so the blit reads them the same way
+        // Stages sample what the last one wrote, which stays inside this node, so that usage isn't declared
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING;
+        let pool = ["Post Texture A", "Post Texture B", "Post Texture C"]
+            .map(|name| graph.add_texture(name, TEXTURE_FORMAT, usage, false));
+
+        Self {
+            bindgroup_layout,
+            pipeline_layout,
+            sampler,
+            frame_buffer,
+            placeholder_lut,
+            passes: Vec::new(),
+            input,
+            pool,
+            enabled: true,
+            bindgroups: Vec::new(),
+        }
+    }
+
+    // Builds every effect before swapping any in, so a shader that doesn't compile leaves the old chain running
+    // The graph needs a refresh after, for the new bind groups
+    pub(crate) fn set_effects(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        effects: Vec<PreparedPostEffect>,
+    ) -> Result<(), GpuError> {
+        let passes = effects
+            .into_iter()
+            .map(|effect| self.build_pass(device, queue, effect))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.passes = passes;
+
+        Ok(())
+    }
+
+    // Only rebuilds the pipelines when the shaders or the LUT changed, new numbers for the same effect just get written
+    pub(crate) fn set_effect(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        index: usize,
+        effect: PreparedPostEffect,
+    ) -> Result<(), GpuError> {
+        let Some(pass) = self.passes.get_mut(index) else {
+            return Ok(());
+        };
+
+        if pass.effect.same_passes(&effect) {
+            queue.write_buffer(&pass.params_buffer, 0, bytemuck::cast_slice(&effect.params));
+            pass.effect = effect;
+
+            return Ok(());
+        }
+
+        self.passes[index] = self.build_pass(device, queue, effect)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn set_params(&mut self, queue: &wgpu::Queue, index: usize, values: &[f32]) {
+        let Some(pass) = self.passes.get_mut(index) else {
+            return;
+        };
+
+        // Engine::set_post_params already checked the count
+        if pass.effect.set_params(values).is_ok() {
+            queue.write_buffer(&pass.params_buffer, 0, bytemuck::cast_slice(&pass.effect.params));
+        }
+    }
+
+    // What the chain was built from, for building it again on a new device
+    pub(crate) fn effects(&self) -> Vec<PreparedPostEffect> {
+        self.passes.iter().map(|pass| pass.effect.clone()).collect()
+    }
+
+    // What the blit should read, None for the screen texture
+    pub(crate) fn output(&self) -> Option<TextureId> {
+        self.stage_targets().last().map(|(_, _, output)| *output)
+    }
+
+    // Works out which texture every stage reads and writes, in the order they run
+    fn stage_targets(&self) -> Vec<StageTargets> {
+        let mut targets = Vec::new();
+
+        if !self.enabled {
+            return targets;
+        }
+
+        let mut current = self.input;
+
+        for pass in &self.passes {
+            let source = current;
+
+            for _ in &pass.pipelines {
+                // A single stage only needs to avoid its input, a later stage also has to keep the effect's source around
+                let output = self.pool
+                    .into_iter()
+                    .find(|target| *target != current && *target != source)
+                    .unwrap_or(self.pool[0]);
+
+                targets.push((current, source, output));
+                current = output;
+            }
+        }
+
+        targets
+    }
+
+    // Compiles inside an error scope, so a broken custom shader comes back as an error instead of hitting the
+    // uncaptured error handler
+    fn build_pass(&self, device: &wgpu::Device, queue: &wgpu::Queue, effect: PreparedPostEffect) -> Result<PostEffectPass, GpuError> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let pipelines = effect.stages.iter().map(|stage| {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&effect.name),
+                source: wgpu::ShaderSource::Wgsl(format!("{POST_PRELUDE_WGSL}\n{stage}").into()),
+            });
+
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&effect.name),
+                layout: Some(&self.pipeline_layout),
+                module: &shader,
+                entry_point: Some("main"), // Defined by the prelude
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        }).collect();
+
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(GpuError::PostEffect(effect.name, error.to_string()));
+        }
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post Params Uniform Buffer"),
+            size: std::mem::size_of_val(&effect.params) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&params_buffer, 0, bytemuck::cast_slice(&effect.params));
+
+        let lut_view = effect.lut.as_ref().map(|lut| create_lut_view(device, queue, lut));
+
+        Ok(PostEffectPass { effect, pipelines, params_buffer, lut_view })
+    }
+}
+
+impl RenderNode for PostChain {
+    fn name(&self) -> &'static str {
+        "Post Process Pass"
+    }
+
+    // Only the input comes from an earlier node, the pool textures are written and read back inside the chain
+    fn io(&self) -> NodeIo {
+        let targets = self.stage_targets();
+
+        let mut writes: Vec<TextureId> = targets.iter().map(|(_, _, output)| *output).collect();
+        writes.sort_by_key(|id| self.pool.iter().position(|pool_id| pool_id == id));
+        writes.dedup();
+
+        let reads = if targets.is_empty() { Vec::new() } else { vec![self.input] };
+
+        NodeIo { reads, writes, ..Default::default() }
+    }
+
+    fn rebind(&mut self, ctx: &BindContext) {
+        let input = ctx.textures.texture(self.input);
+        let (width, height) = (input.width() as f32, input.height() as f32);
+
+        let frame = [width, height, 1.0 / width, 1.0 / height];
+        ctx.queue.write_buffer(&self.frame_buffer, 0, bytemuck::cast_slice(&frame));
+
+        let stages = self.passes.iter().flat_map(|pass| pass.pipelines.iter().map(move |_| pass));
+
+        self.bindgroups = stages.zip(self.stage_targets()).map(|(pass, (current, source, output))| {
+            let lut_view = pass.lut_view.as_ref().unwrap_or(&self.placeholder_lut);
+
+            ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Bindgroup"),
+                layout: &self.bindgroup_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(ctx.textures.view(current)) },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(ctx.textures.view(source)) },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(ctx.textures.view(output)) },
+                    wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                    wgpu::BindGroupEntry { binding: 4, resource: self.frame_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 5, resource: pass.params_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(lut_view) },
+                ],
+            })
+        }).collect();
+    }
+
+    // Every stage gets its own dispatch, wgpu puts the barriers between them
+    fn run(&self, encoder: &mut wgpu::CommandEncoder, ctx: &mut RunContext) {
+        if self.bindgroups.is_empty() {
+            return;
+        }
+
+        let input = ctx.textures.texture(self.input);
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(self.name()),
+            timestamp_writes: None,
+        });
+
+        let pipelines = self.passes.iter().flat_map(|pass| &pass.pipelines);
+
+        for (pipeline, bindgroup) in pipelines.zip(&self.bindgroups) {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, bindgroup, &[]);
+            compute_pass.dispatch_workgroups(input.width().div_ceil(8), input.height().div_ceil(8), 1);
+        }
+    }
+}
+
+fn create_post_bindgroup_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let texture_entry = |binding, filterable, view_dimension| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable },
+            view_dimension,
+            multisampled: false,
+        },
+        count: None,
+    };
+
+    let uniform_entry = |binding, size: usize| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(size as u64),
+        },
+        count: None,
+    };
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Post Bindgroup Layout"),
+        entries: &[
+            // Input and source
+            texture_entry(0, true, wgpu::TextureViewDimension::D2),
+            texture_entry(1, true, wgpu::TextureViewDimension::D2),
+
+            // Output
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: TEXTURE_FORMAT,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            },
+
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+
+            // Frame size and params
+            uniform_entry(4, std::mem::size_of::<[f32; 4]>()),
+            uniform_entry(5, std::mem::size_of::<[f32; 16]>()),
+
+            // LUT, read with textureLoad since 32 bit floats can't be filtered
+            texture_entry(6, false, wgpu::TextureViewDimension::D3),
+        ],
+    })
+}
+
+fn create_lut_view(device: &wgpu::Device, queue: &wgpu::Queue, lut: &CubeLut) -> wgpu::TextureView {
+    let size = wgpu::Extent3d { width: lut.size, height: lut.size, depth_or_array_layers: lut.size };
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Post LUT Texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format: LUT_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    let bytes_per_texel = LUT_FORMAT.block_copy_size(None).unwrap_or(16);
+
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        bytemuck::cast_slice(&lut.texels),
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(lut.size * bytes_per_texel),
+            rows_per_image: Some(lut.size),
+        },
+        size,
+    );
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
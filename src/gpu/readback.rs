@@ -0,0 +1,111 @@
+use std::sync::mpsc;
+
+use exr::prelude::f16;
+
+use crate::core::error::GpuError;
+
+// Copies textures back to the cpu as plain rgba floats, rows from top to bottom
+// Only the float formats we render into are handled, Rgba16Float and Rgba32Float
+// This blocks until the gpu has caught up, so it's for exporting frames and not for anything per frame
+pub fn read_textures(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    textures: &[&wgpu::Texture],
+) -> Result<Vec<Vec<[f32; 4]>>, GpuError> {
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+
+    let staging: Vec<(wgpu::Buffer, u32)> = textures.iter()
+        .map(|texture| {
+            // Buffer copies need every row to start on a 256 byte boundary, so rows get padded out
+            let bytes_per_row = texture.width() * bytes_per_texel(texture.format())?;
+            let padded_bytes_per_row = bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Readback Buffer"),
+                size: padded_bytes_per_row as u64 * texture.height() as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            encoder.copy_texture_to_buffer(
+                texture.as_image_copy(),
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_bytes_per_row),
+                        rows_per_image: Some(texture.height()),
+                    },
+                },
+                texture.size(),
+            );
+
+            Ok((buffer, padded_bytes_per_row))
+        })
+        .collect::<Result<_, GpuError>>()?;
+
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let (sender, receiver) = mpsc::channel();
+
+    for (buffer, _) in &staging {
+        let sender = sender.clone();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |res| {
+            let _ = sender.send(res);
+        });
+    }
+
+    device.poll(wgpu::PollType::wait_indefinitely())?;
+
+    for _ in &staging {
+        receiver.recv().map_err(|_| GpuError::ReadbackDropped)??;
+    }
+
+    let pixels = textures.iter()
+        .zip(&staging)
+        .map(|(texture, (buffer, padded_bytes_per_row))| {
+            let data = buffer.slice(..).get_mapped_range();
+            let pixels = unpack_rows(&data, texture.format(), texture.width(), *padded_bytes_per_row);
+            drop(data);
+
+            buffer.unmap();
+            pixels
+        })
+        .collect();
+
+    Ok(pixels)
+}
+
+fn bytes_per_texel(format: wgpu::TextureFormat) -> Result<u32, GpuError> {
+    match format {
+        wgpu::TextureFormat::Rgba16Float => Ok(8),
+        wgpu::TextureFormat::Rgba32Float => Ok(16),
+        _ => Err(GpuError::UnsupportedReadbackFormat(format)),
+    }
+}
+
+fn unpack_rows(data: &[u8], format: wgpu::TextureFormat, width: u32, padded_bytes_per_row: u32) -> Vec<[f32; 4]> {
+    data.chunks_exact(padded_bytes_per_row as usize)
+        .flat_map(|row| {
+            let row_texels: Vec<[f32; 4]> = match format {
+                wgpu::TextureFormat::Rgba16Float => row[..width as usize * 8]
+                    .chunks_exact(8)
+                    .map(|texel| {
+                        let half: [u16; 4] = bytemuck::pod_read_unaligned(texel);
+                        half.map(|bits| f16::from_bits(bits).to_f32())
+                    })
+                    .collect(),
+
+                // Only the two formats from bytes_per_texel get this far
+                _ => row[..width as usize * 16]
+                    .chunks_exact(16)
+                    .map(bytemuck::pod_read_unaligned)
+                    .collect(),
+            };
+
+            row_texels
+        })
+        .collect()
+}
@@ -0,0 +1,23 @@
+use crate::core::config::{RenderMode, ToneMapping};
+use crate::engine::background::PreparedBackground;
+use crate::engine::export::ExportRequest;
+use crate::engine::post::PreparedPostEffect;
+use crate::gpu::tick_interpolation::TickState;
+
+// Messages sent from the engine thread over to the render thread
+// Anything that needs the render thread's own gpu objects (textures, bindgroups) goes through here
+// Scene data is only added to the cpu copy on the engine thread, and UploadScene has the render thread upload it
+// The camera and lights come through Tick so the render thread can blend between ticks
+pub(crate) enum RenderCommand {
+    Background(Box<PreparedBackground>),
+    ToneMapping(ToneMapping),
+    Exposure(f32),
+    RenderMode(RenderMode),
+    Aovs(bool),
+    PostEffects(Vec<PreparedPostEffect>),
+    PostEffect(usize, Box<PreparedPostEffect>),
+    PostParams(usize, Vec<f32>),
+    Export(ExportRequest),
+    Tick(TickState),
+    UploadScene,
+}
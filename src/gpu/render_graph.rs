@@ -0,0 +1,311 @@
+use std::any::Any;
+
+use crate::core::error::GpuError;
+
+// End of imports
+
+// Handle to a texture the graph owns, from RenderGraph::add_texture
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct TextureId(usize);
+
+// Handle to a buffer the graph tracks, from RenderGraph::add_buffer
+// The buffers themselves live in GpuBuffers, since the engine thread fills them and they don't follow the render size
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct BufferId(usize);
+
+// What a node touches this frame, the graph works everything else out from these
+// Reads are sampled or loaded, writes are storage writes, and target is the surface or the caller's view
+// Buffers only one node ever binds, like a post effect's params, don't need declaring
+#[derive(Default, Debug)]
+pub(crate) struct NodeIo {
+    pub(crate) reads: Vec<TextureId>,
+    pub(crate) writes: Vec<TextureId>,
+    pub(crate) buffer_reads: Vec<BufferId>,
+    pub(crate) buffer_writes: Vec<BufferId>,
+    pub(crate) target: bool,
+}
+
+// Handed to RenderNode::rebind, whenever a texture was remade or the node was changed through node_mut
+pub(crate) struct BindContext<'a> {
+    pub(crate) device: &'a wgpu::Device,
+    pub(crate) queue: &'a wgpu::Queue,
+    pub(crate) textures: &'a GraphTextures,
+}
+
+pub(crate) struct RunContext<'a> {
+    pub(crate) textures: &'a GraphTextures,
+
+    // None when running headless or for LatrRenderer::trace_into, nodes that draw to it are skipped then
+    pub(crate) target: Option<&'a wgpu::TextureView>,
+    pub(crate) target_size: (u32, u32),
+
+    // For the pass the frame timer measures, only the trace takes it
+    pub(crate) timestamp_writes: Option<wgpu::ComputePassTimestampWrites<'a>>,
+}
+
+// One step of the frame, like the trace, a post effect or the blit
+// Each node records its own compute or render pass, and wgpu puts barriers between passes from the usages the
+// graph gave the textures and buffers, so declaring what gets read and written is all a node has to do
+pub(crate) trait RenderNode: Any {
+    fn name(&self) -> &'static str;
+    fn io(&self) -> NodeIo;
+
+    // Bind groups have to be made again here, the textures behind the ids may be new
+    fn rebind(&mut self, ctx: &BindContext);
+
+    fn run(&self, encoder: &mut wgpu::CommandEncoder, ctx: &mut RunContext);
+}
+
+struct GraphTexture {
+    name: &'static str,
+    format: wgpu::TextureFormat,
+
+    // Usages the texture always needs, like COPY_SRC for exporting, the declarations add the rest
+    base_usage: wgpu::TextureUsages,
+
+    // Kept textures are read from outside the graph, so whatever writes them always runs
+    keep: bool,
+
+    texture: Option<wgpu::Texture>,
+    view: Option<wgpu::TextureView>,
+}
+
+struct GraphBuffer {
+    name: &'static str,
+
+    // Written from the cpu with queue writes, so nodes can read it without an earlier node writing it
+    uploaded: bool,
+
+    // Same as for textures, whatever writes it always runs
+    keep: bool,
+}
+
+// The textures, split out so nodes can read them while the graph hands out the nodes
+pub(crate) struct GraphTextures {
+    textures: Vec<GraphTexture>,
+}
+
+impl GraphTextures {
+    // Only valid after RenderGraph::refresh, which creates everything that was added
+    pub(crate) fn texture(&self, id: TextureId) -> &wgpu::Texture {
+        self.textures[id.0].texture.as_ref().expect("render graph texture used before refresh")
+    }
+
+    pub(crate) fn view(&self, id: TextureId) -> &wgpu::TextureView {
+        self.textures[id.0].view.as_ref().expect("render graph texture used before refresh")
+    }
+}
+
+// Runs the nodes in the order they were added, creating and sizing every texture from what the nodes declare
+// Textures some node uses are the render size, ones nobody uses this frame shrink to 1x1 placeholders,
+// which keeps them bindable for nodes that always bind them, like the trace does with the aovs
+pub(crate) struct RenderGraph {
+    textures: GraphTextures,
+    buffers: Vec<GraphBuffer>,
+    nodes: Vec<Box<dyn RenderNode>>,
+
+    // Nodes changed through node_mut, which get rebound on the next refresh
+    dirty: Vec<bool>,
+    size: (u32, u32),
+}
+
+impl RenderGraph {
+    pub(crate) fn new(size: (u32, u32)) -> Self {
+        Self {
+            textures: GraphTextures { textures: Vec::new() },
+            buffers: Vec::new(),
+            nodes: Vec::new(),
+            dirty: Vec::new(),
+            size,
+        }
+    }
+
+    // Nothing is created until refresh
+    pub(crate) fn add_texture(
+        &mut self,
+        name: &'static str,
+        format: wgpu::TextureFormat,
+        base_usage: wgpu::TextureUsages,
+        keep: bool,
+    ) -> TextureId {
+        self.textures.textures.push(GraphTexture { name, format, base_usage, keep, texture: None, view: None });
+
+        TextureId(self.textures.textures.len() - 1)
+    }
+
+    // Only tracked for validate and needed_nodes, the buffer is made and bound by whoever owns it
+    pub(crate) fn add_buffer(&mut self, name: &'static str, uploaded: bool, keep: bool) -> BufferId {
+        self.buffers.push(GraphBuffer { name, uploaded, keep });
+
+        BufferId(self.buffers.len() - 1)
+    }
+
+    pub(crate) fn add_node<N: RenderNode>(&mut self, node: N) {
+        self.nodes.push(Box::new(node));
+        self.dirty.push(true);
+    }
+
+    pub(crate) fn textures(&self) -> &GraphTextures {
+        &self.textures
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub(crate) fn node<N: RenderNode>(&self) -> Option<&N> {
+        self.nodes.iter().find_map(|node| (node.as_ref() as &dyn Any).downcast_ref::<N>())
+    }
+
+    // Marks the node for rebinding, call refresh once done with it
+    pub(crate) fn node_mut<N: RenderNode>(&mut self) -> Option<&mut N> {
+        let index = self.nodes.iter().position(|node| (node.as_ref() as &dyn Any).is::<N>())?;
+        self.dirty[index] = true;
+
+        (self.nodes[index].as_mut() as &mut dyn Any).downcast_mut::<N>()
+    }
+
+    // Takes effect on the next refresh
+    pub(crate) fn resize(&mut self, size: (u32, u32)) {
+        self.size = size;
+    }
+
+    // Checks the declarations, remakes any texture whose size or usage no longer fits them, then rebinds
+    // Every node gets rebound when a texture was remade, since bind groups can hold textures they don't declare
+    pub(crate) fn refresh(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<(), GpuError> {
+        self.validate()?;
+
+        let ios: Vec<NodeIo> = self.nodes.iter().map(|node| node.io()).collect();
+
+        let mut remade = false;
+
+        for (index, graph_texture) in self.textures.textures.iter_mut().enumerate() {
+            let id = TextureId(index);
+            let read = ios.iter().any(|io| io.reads.contains(&id));
+            let written = ios.iter().any(|io| io.writes.contains(&id));
+
+            let mut usage = graph_texture.base_usage;
+            usage.set(wgpu::TextureUsages::TEXTURE_BINDING, read || usage.contains(wgpu::TextureUsages::TEXTURE_BINDING));
+            usage.set(wgpu::TextureUsages::STORAGE_BINDING, written || usage.contains(wgpu::TextureUsages::STORAGE_BINDING));
+
+            let (width, height) = if read || written { self.size } else { (1, 1) };
+
+            let fits = graph_texture.texture.as_ref().is_some_and(|texture| {
+                (texture.width(), texture.height()) == (width, height) && texture.usage().contains(usage)
+            });
+
+            if fits {
+                continue;
+            }
+
+            // Usage only grows, so a texture that's sometimes read keeps TEXTURE_BINDING when it isn't
+            let usage = graph_texture.texture.as_ref().map_or(usage, |texture| usage | texture.usage());
+
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(graph_texture.name),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: graph_texture.format,
+                usage,
+                view_formats: &[],
+            });
+
+            graph_texture.view = Some(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+            graph_texture.texture = Some(texture);
+            remade = true;
+        }
+
+        let ctx = BindContext { device, queue, textures: &self.textures };
+
+        for (node, dirty) in self.nodes.iter_mut().zip(&mut self.dirty) {
+            if remade || *dirty {
+                node.rebind(&ctx);
+                *dirty = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Nothing can read a texture before some earlier node wrote it this frame, or write one it's reading
+    // Buffers go by the same rules, except uploaded ones already hold something when the frame starts
+    pub(crate) fn validate(&self) -> Result<(), GpuError> {
+        let mut written = Vec::new();
+        let mut buffers_written = Vec::new();
+
+        for node in &self.nodes {
+            let io = node.io();
+
+            for id in &io.reads {
+                let name = self.textures.textures[id.0].name;
+
+                if io.writes.contains(id) {
+                    return Err(GpuError::RenderGraph(format!("{} reads and writes {name} in the same pass", node.name())));
+                }
+
+                if !written.contains(id) {
+                    return Err(GpuError::RenderGraph(format!("{} reads {name} before anything writes it", node.name())));
+                }
+            }
+
+            for id in &io.buffer_reads {
+                let buffer = &self.buffers[id.0];
+
+                if io.buffer_writes.contains(id) {
+                    return Err(GpuError::RenderGraph(format!("{} reads and writes {} in the same pass", node.name(), buffer.name)));
+                }
+
+                if !buffer.uploaded && !buffers_written.contains(id) {
+                    return Err(GpuError::RenderGraph(format!("{} reads {} before anything writes it", node.name(), buffer.name)));
+                }
+            }
+
+            written.extend(io.writes.iter().copied());
+            buffers_written.extend(io.buffer_writes.iter().copied());
+        }
+
+        Ok(())
+    }
+
+    // Which nodes have to run, skipping any whose work nobody would see, which is anything that only feeds a
+    // target that isn't there
+    pub(crate) fn needed_nodes(&self, has_target: bool) -> Vec<bool> {
+        let ios: Vec<NodeIo> = self.nodes.iter().map(|node| node.io()).collect();
+
+        // Walking backwards, a node is needed if it draws to the target or writes something a later needed node reads
+        let mut needed = vec![false; self.nodes.len()];
+        let mut wanted: Vec<TextureId> = (0..self.textures.textures.len())
+            .map(TextureId)
+            .filter(|id| self.textures.textures[id.0].keep)
+            .collect();
+        let mut wanted_buffers: Vec<BufferId> = (0..self.buffers.len())
+            .map(BufferId)
+            .filter(|id| self.buffers[id.0].keep)
+            .collect();
+
+        for (index, io) in ios.iter().enumerate().rev() {
+            let feeds_wanted = io.writes.iter().any(|id| wanted.contains(id))
+                || io.buffer_writes.iter().any(|id| wanted_buffers.contains(id));
+
+            if (io.target && has_target) || feeds_wanted {
+                needed[index] = true;
+                wanted.extend(io.reads.iter().copied());
+                wanted_buffers.extend(io.buffer_reads.iter().copied());
+            }
+        }
+
+        needed
+    }
+
+    pub(crate) fn run(&self, encoder: &mut wgpu::CommandEncoder, mut ctx: RunContext) {
+        let needed = self.needed_nodes(ctx.target.is_some());
+
+        for (node, needed) in self.nodes.iter().zip(needed) {
+            if needed {
+                node.run(encoder, &mut ctx);
+            }
+        }
+    }
+}
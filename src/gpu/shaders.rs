@@ -10,92 +10,172 @@ use super::bindgroups::{
     create_render_bindgroup,
 };
 
-use super::buffers::GpuBuffers;
+use super::buffers::{GpuBufferIds, GpuBuffers};
+use super::environment::EnvironmentTextures;
+use super::aovs::{AovIds, AovTextures};
+use super::render_graph::{BindContext, NodeIo, RenderNode, RunContext, TextureId};
+use crate::core::config::ScaleMode;
 
+// The first node of the render graph, traces into the screen texture and the aovs
 pub struct ComputeRaytraceShader {
     pub pipeline: wgpu::ComputePipeline,
-    pub bindgroup: wgpu::BindGroup,
     pub bindgroup_layout: wgpu::BindGroupLayout,
+
+    // Made on the first rebind, once the graph has created the textures
+    bindgroup: Option<wgpu::BindGroup>,
+
+    buffers: GpuBuffers,
+    buffer_ids: GpuBufferIds,
+
+    // Placeholders until a background is set, see GpuCore::set_background
+    pub(crate) environment: EnvironmentTextures,
+
+    screen_texture: TextureId,
+    aovs: AovIds,
+
+    // The aovs are always bound, but only declared while they're on, so the graph shrinks them to 1x1 when off
+    pub(crate) aovs_enabled: bool,
 }
 
 impl ComputeRaytraceShader {
     pub fn new(
         device: &wgpu::Device,
         buffers: &GpuBuffers,
-        screen_texture_view: &wgpu::TextureView,
+        buffer_ids: GpuBufferIds,
+        environment: EnvironmentTextures,
+        screen_texture: TextureId,
+        aovs: AovIds,
     ) -> Self {
         let bindgroup_layout = create_raytrace_bindgroup_layout(&device);
-
-        let bindgroup = create_raytrace_bindgroup(
-            &device,
-            &buffers,
-            &screen_texture_view,
-            &bindgroup_layout,
-        );
-
         let pipeline = create_raytrace_compute_pipeline(&device, &bindgroup_layout);
 
         Self {
             pipeline,
-            bindgroup,
             bindgroup_layout,
+            bindgroup: None,
+            buffers: buffers.clone(),
+            buffer_ids,
+            environment,
+            screen_texture,
+            aovs,
+            aovs_enabled: false,
         }
     }
+}
+
+impl RenderNode for ComputeRaytraceShader {
+    fn name(&self) -> &'static str {
+        "Ray Tracing Pass"
+    }
+
+    fn io(&self) -> NodeIo {
+        let mut writes = vec![self.screen_texture];
+
+        if self.aovs_enabled {
+            writes.extend(self.aovs.ids());
+        }
+
+        NodeIo { writes, buffer_reads: self.buffer_ids.trace_reads(), ..Default::default() }
+    }
+
+    fn rebind(&mut self, ctx: &BindContext) {
+        let aovs = AovTextures::from_graph(ctx.textures, &self.aovs, self.aovs_enabled);
+
+        self.bindgroup = Some(create_raytrace_bindgroup(
+            ctx.device,
+            &self.buffers,
+            ctx.textures.view(self.screen_texture),
+            &self.environment,
+            &aovs,
+            &self.bindgroup_layout,
+        ));
+    }
+
+    fn run(&self, encoder: &mut wgpu::CommandEncoder, ctx: &mut RunContext) {
+        let screen_texture = ctx.textures.texture(self.screen_texture);
 
-    pub fn run_compute_pass(
-        &self,
-        encoder: &mut wgpu::CommandEncoder,
-        buffers: &GpuBuffers,
-        width: u32,
-        height: u32
-    ) {
         let mut compute_pass =
             encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Ray Tracing Pass"),
-                timestamp_writes: None,
+                label: Some(self.name()),
+                timestamp_writes: ctx.timestamp_writes.take(),
             });
 
         compute_pass.set_pipeline(&self.pipeline);
 
-        compute_pass.set_bind_group(0, &self.bindgroup, &[]);
+        compute_pass.set_bind_group(0, self.bindgroup.as_ref(), &[]);
 
-        let workgroup_x = (width + 7) / 8;
-        let workgroup_y = (height + 7) / 8;
+        let workgroup_x = screen_texture.width().div_ceil(8);
+        let workgroup_y = screen_texture.height().div_ceil(8);
         compute_pass.dispatch_workgroups(workgroup_x, workgroup_y, 1);
     }
 }
 
+// The last node of the render graph, tone maps whatever it reads onto the target
 pub struct RenderShader {
     pub pipeline: wgpu::RenderPipeline,
-    pub bindgroup: wgpu::BindGroup,
-
-    pub screen_texture_view: wgpu::TextureView,
+    pub bindgroup_layout: wgpu::BindGroupLayout,
     pub sampler: wgpu::Sampler,
+
+    bindgroup: Option<wgpu::BindGroup>,
+    buffers: GpuBuffers,
+    buffer_ids: GpuBufferIds,
+
+    // The screen texture, or the output of the last post effect, see GpuCore::rebind_blit
+    pub(crate) source: TextureId,
+    pub(crate) scale_mode: ScaleMode,
 }
 
 impl RenderShader {
     pub fn new(
-        device: &wgpu::Device, 
-        screen_texture_view: wgpu::TextureView, 
-        sampler: wgpu::Sampler, 
-        surface_config: &wgpu::SurfaceConfiguration
+        device: &wgpu::Device,
+        buffers: &GpuBuffers,
+        buffer_ids: GpuBufferIds,
+        source: TextureId,
+        sampler: wgpu::Sampler,
+        surface_config: &wgpu::SurfaceConfiguration,
+        scale_mode: ScaleMode,
     ) -> Self {
         let render_bindgroup_layout = create_render_bindgroup_layout(&device);
-        let render_bindgroup = create_render_bindgroup(&device, &render_bindgroup_layout, &screen_texture_view, &sampler);
-
         let render_pipeline = create_render_pipeline(&device, &surface_config, &render_bindgroup_layout);
 
         Self {
-            bindgroup: render_bindgroup,
+            bindgroup: None,
+            bindgroup_layout: render_bindgroup_layout,
             pipeline: render_pipeline,
-            screen_texture_view,
             sampler,
+            buffers: buffers.clone(),
+            buffer_ids,
+            source,
+            scale_mode,
         }
     }
+}
+
+impl RenderNode for RenderShader {
+    fn name(&self) -> &'static str {
+        "Screen Blit Pass"
+    }
+
+    fn io(&self) -> NodeIo {
+        NodeIo { reads: vec![self.source], buffer_reads: vec![self.buffer_ids.tone_map], target: true, ..Default::default() }
+    }
+
+    fn rebind(&mut self, ctx: &BindContext) {
+        let view = ctx.textures.view(self.source);
+        self.bindgroup = Some(create_render_bindgroup(ctx.device, &self.bindgroup_layout, view, &self.sampler, &self.buffers));
+    }
+
+    // The image is stretched over the part of the target from ScaleMode::viewport
+    fn run(&self, encoder: &mut wgpu::CommandEncoder, ctx: &mut RunContext) {
+        let Some(view) = ctx.target else {
+            return;
+        };
+
+        let source = ctx.textures.texture(self.source);
+        let viewport = self.scale_mode.viewport((source.width(), source.height()), ctx.target_size);
 
-    pub fn run_render_pass(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Screen Blit Pass"),
+            label: Some(self.name()),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                 view: &view, // Reference to the view that we need to draw on that we made earlier
                 depth_slice: None,
@@ -113,10 +193,14 @@ impl RenderShader {
         // Set the correct pipeline process
         render_pass.set_pipeline(&self.pipeline);
 
+        // Anything outside the viewport keeps the black clear color, which gives the letterbox bars
+        let [x, y, width, height] = viewport;
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+
         // Pass in the texture and sampler
-        render_pass.set_bind_group(0, &self.bindgroup, &[]);
+        render_pass.set_bind_group(0, self.bindgroup.as_ref(), &[]);
 
         // This draws 2 triangles (6 vertices) to cover the screen.
         render_pass.draw(0..4, 0..1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,115 @@
+use std::time::Instant;
+
+use glam::Vec3;
+
+use crate::gpu::buffers::{GpuStorageLight, GpuUniformCamera};
+
+// End of imports
+
+// What the engine thread hands over at the end of every pass of the physics loop
+pub(crate) struct TickState {
+    pub(crate) camera: GpuUniformCamera,
+
+    // Only sent when the lights changed, None keeps the last ones
+    pub(crate) lights: Option<Vec<GpuStorageLight>>,
+
+    pub(crate) published: Instant,
+}
+
+// Keeps the last two tick states on the render thread, so frames drawn between ticks can blend from one to the other
+// This shows everything one tick late, in exchange for smooth movement no matter how slow the tick rate is
+pub(crate) struct TickInterpolator {
+    enabled: bool,
+
+    previous: Option<Snapshot>,
+    current: Option<Snapshot>,
+
+    // Set when a new tick comes in or the last frame was still blending
+    // The buffers get written once more after blending stops, so things end up exactly on the current tick
+    dirty: bool,
+}
+
+struct Snapshot {
+    camera: GpuUniformCamera,
+    lights: Vec<GpuStorageLight>,
+    published: Instant,
+}
+
+impl TickInterpolator {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self { enabled, previous: None, current: None, dirty: false }
+    }
+
+    pub(crate) fn push(&mut self, tick: TickState) {
+        // Ticks that didn't touch the lights still need them around to blend from
+        let lights = match (tick.lights, &self.current) {
+            (Some(lights), _) => lights,
+            (None, Some(current)) => current.lights.clone(),
+            (None, None) => Vec::new(),
+        };
+
+        self.previous = self.current.take();
+        self.current = Some(Snapshot { camera: tick.camera, lights, published: tick.published });
+        self.dirty = true;
+    }
+
+    // Makes the next frame write the camera and lights even if nothing changed, for buffers that were just remade
+    pub(crate) fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    // How far between the previous and current tick a frame drawn now should be, from 0 to 1
+    // Ticks keep coming at roughly the same rate, so the time since the last one is measured against the gap before it
+    pub(crate) fn blend(&self, now: Instant) -> f32 {
+        let (Some(previous), Some(current)) = (&self.previous, &self.current) else {
+            return 1.0;
+        };
+
+        let interval = current.published.saturating_duration_since(previous.published).as_secs_f32();
+
+        if interval <= 0.0 {
+            return 1.0;
+        }
+
+        (now.saturating_duration_since(current.published).as_secs_f32() / interval).clamp(0.0, 1.0)
+    }
+
+    // Camera and lights to draw with right now, None when there's nothing new to write
+    pub(crate) fn frame_state(&mut self, now: Instant) -> Option<(GpuUniformCamera, Vec<GpuStorageLight>)> {
+        let blend = self.blend(now);
+
+        // Once we've caught up to the current tick, everything is where it'll stay until the next one
+        let moving = self.enabled && blend < 1.0;
+        if !std::mem::replace(&mut self.dirty, moving) && !moving {
+            return None;
+        }
+
+        let current = self.current.as_ref()?;
+
+        let Some(previous) = self.previous.as_ref().filter(|_| moving) else {
+            return Some((current.camera, current.lights.clone()));
+        };
+
+        // Yaw isn't wrapped anywhere, so a plain lerp follows the way the camera actually turned
+        let mut camera = current.camera;
+        camera.pos = Vec3::from_array(previous.camera.pos).lerp(Vec3::from_array(current.camera.pos), blend).to_array();
+        camera.yaw = lerp(previous.camera.yaw, current.camera.yaw, blend);
+        camera.pitch = lerp(previous.camera.pitch, current.camera.pitch, blend);
+
+        // Lights are matched up by index, so they only blend when none were added or removed in between
+        let mut lights = current.lights.clone();
+        if previous.lights.len() == lights.len() {
+            for (light, before) in lights.iter_mut().zip(&previous.lights) {
+                if light.kind == before.kind {
+                    light.position = Vec3::from_array(before.position).lerp(Vec3::from_array(light.position), blend).to_array();
+                }
+            }
+        }
+
+        Some((camera, lights))
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
@@ -6,7 +6,40 @@ mod core;
 #[cfg(test)]
 mod private_tests;
 
-pub use core::config::{LatrConfig, RunMode};
-pub use core::error::LatrError;
+pub use core::config::{
+    AdapterPreferences,
+    Backend,
+    CameraPose,
+    DynamicResolution,
+    LatrConfig,
+    PowerPreference,
+    PresentMode,
+    RenderMode,
+    RenderResolution,
+    RunMode,
+    ScaleMode,
+    SceneCapacity,
+    ToneMapping,
+};
+pub use core::error::{ConfigError, GpuError, LatrError};
 pub use core::latr_core::LatrEngine;
-pub use crate::engine::engine_core::{Engine, PhysicsLoop};
\ No newline at end of file
+pub use core::builder::LatrEngineBuilder;
+pub use core::renderer::LatrRenderer;
+pub use crate::gpu::adapter::AdapterDetails;
+pub use crate::gpu::aovs::AovTextures;
+pub use crate::engine::engine_core::{Engine, PhysicsLoop};
+pub use crate::engine::lights::{Light, LightId};
+pub use crate::engine::background::Background;
+pub use crate::engine::post::{PostEffect, PostPass};
+pub use crate::engine::export::ExportFormat;
+pub use crate::engine::input::{InputEvent, InputState};
+pub use crate::engine::camera::{CameraController, FlyCamera, FlyKeys, OrbitCamera, OrbitTarget};
+pub use crate::engine::timestep::TickStats;
+pub use crate::engine::recording::{FrameExport, Recording};
+
+// Re-exported so users can name keys and buttons without depending on winit themselves
+pub use winit::event::MouseButton;
+pub use winit::keyboard::KeyCode;
+
+// Same for the adapter info and limits in AdapterDetails
+pub use wgpu;
\ No newline at end of file
@@ -0,0 +1,46 @@
+use crate::engine::background::*;
+
+#[test]
+fn environment_cdf_follows_brightness() {
+    // 2x2 map where only the bottom right texel is lit
+    let pixels = vec![
+        [0.0, 0.0, 0.0, 1.0], [0.0, 0.0, 0.0, 1.0],
+        [0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0],
+    ];
+
+    let image = EnvironmentImage::from_pixels(2, 2, pixels);
+    let w = image.cdf_width as usize;
+
+    assert_eq!(image.cdf.len(), w * 3);
+    assert!(image.total > 0.0);
+
+    // The black top row can't be weighted, so it falls back to an even spread
+    assert_eq!(&image.cdf[0..2], &[0.5, 1.0]);
+
+    // All of the bottom row's weight sits on its second texel
+    assert_eq!(&image.cdf[w..w + 2], &[0.0, 1.0]);
+
+    // And the marginal row always picks the bottom row
+    assert_eq!(&image.cdf[2 * w..2 * w + 2], &[0.0, 1.0]);
+}
+
+#[test]
+fn missing_environment_map_is_an_error() {
+    let background = Background::environment_map("./src/private_tests/test_files/does_not_exist.hdr");
+    assert!(background.prepare(8192).is_err());
+}
+
+#[test]
+fn oversized_environment_map_is_shrunk_to_fit() {
+    // 6x3 map going into a 4 wide texture, the cdf needs 3 / 2 + 1 rows so the factor is 2
+    let pixels = (0..18).map(|i| [i as f32, 0.0, 0.0, 1.0]).collect();
+
+    let (width, height, pixels) = downsample_to_fit(6, 3, pixels, 4);
+
+    assert_eq!((width, height), (3, 2));
+    assert_eq!(pixels.len(), 6);
+
+    // Top left block averages texels 0, 1, 6 and 7, the bottom row only has one row of texels to average
+    assert_eq!(pixels[0][0], 3.5);
+    assert_eq!(pixels[3][0], 12.5);
+}
@@ -42,4 +42,51 @@ fn single_tri() -> Result<(), EngineError> {
     assert_eq!(1, flattened.len());
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn tlas_points_at_every_instance() -> Result<(), EngineError> {
+    let path = PathBuf::from(ROOT).join("single_meshes/single_tri.tri");
+    let raw_tris = parse_tri_file(&path)?;
+
+    // The same model twice, the second moved out of the way of the first
+    let first = raw_tris[0].get_aabb();
+    let second = AABB { min: first.min + Vec3::splat(10.0), max: first.max + Vec3::splat(10.0) };
+
+    let tlas = build_tlas(vec![first]);
+    assert_eq!(tlas.len(), 1);
+    assert_eq!(tlas[0].indices, [-1, 0, 0, 0]);
+    assert_eq!([tlas[0].min_x[0], tlas[0].max_x[0]], [first.min.x, first.max.x]);
+
+    let tlas = build_tlas(vec![first, second]);
+    let mut leaves: Vec<i32> = tlas.iter().flat_map(|node| node.indices).filter(|idx| *idx < 0).collect();
+    leaves.sort();
+    assert_eq!(leaves, [!1, !0]);
+
+    // Nothing loaded still leaves an entry node for trace_scene to start at
+    assert_eq!(build_tlas(Vec::new()), [GpuStorageBvhNode::default()]);
+
+    Ok(())
+}
+
+#[test]
+fn blas_reaches_every_triangle() {
+    // A row of triangles, enough that the build has to split more than once
+    let triangles: Vec<RawTriangle> = (0..40)
+        .map(|i| {
+            let x = i as f32 * 2.0;
+            [Vec3::new(x, 0.0, 0.0), Vec3::new(x + 1.0, 0.0, 0.0), Vec3::new(x, 1.0, 0.0)]
+        })
+        .collect();
+
+    let blas = BvhNode::build(triangles).flatten_to_blas();
+
+    let mut leaves: Vec<i32> = blas.iter().flat_map(|node| node.indices).filter(|idx| *idx < 0).map(|idx| !idx).collect();
+    leaves.sort();
+    assert_eq!(leaves, (0..40).collect::<Vec<i32>>());
+
+    // Branches only ever point forwards, past the node they're in
+    for (i, node) in blas.iter().enumerate() {
+        assert!(node.indices.iter().all(|idx| *idx <= 0 || *idx as usize > i + 1));
+    }
+}
@@ -0,0 +1,50 @@
+use std::f32::consts::FRAC_PI_2;
+
+use glam::Vec3;
+use winit::keyboard::KeyCode;
+
+use crate::engine::camera::*;
+use crate::engine::input::{InputEvent, InputState};
+use crate::gpu::buffers::GpuUniformCamera;
+
+#[test]
+fn forward_matches_readme_conventions() {
+    assert!(camera_forward(0.0, 0.0).abs_diff_eq(Vec3::X, 1e-6));
+    assert!(camera_forward(FRAC_PI_2, 0.0).abs_diff_eq(Vec3::NEG_Z, 1e-6));
+    assert!(camera_forward(0.0, FRAC_PI_2).abs_diff_eq(Vec3::Y, 1e-6));
+
+    // The default camera keeps looking down +z, with its right towards -x
+    let camera = GpuUniformCamera::default();
+    assert!(camera_forward(camera.yaw, camera.pitch).abs_diff_eq(Vec3::Z, 1e-6));
+    assert!(camera_right(camera.yaw).abs_diff_eq(Vec3::NEG_X, 1e-6));
+}
+
+#[test]
+fn fly_camera_moves_where_it_looks() {
+    let mut input = InputState::default();
+    input.apply(&InputEvent::Key { key: KeyCode::KeyW, pressed: true, repeat: false });
+
+    let mut camera = GpuUniformCamera::default();
+    let mut controller = CameraController::fly();
+
+    // Default speed is 5 units a second
+    controller.update(&mut camera, &input, None, 0.5);
+    assert!(Vec3::from_array(camera.pos).abs_diff_eq(Vec3::new(0.0, 0.0, 2.5), 1e-5));
+}
+
+#[test]
+fn orbit_camera_looks_at_target() {
+    let input = InputState::default();
+    let target = [1.0, 2.0, 3.0];
+
+    let mut camera = GpuUniformCamera::default();
+    let mut controller = CameraController::orbit(OrbitTarget::Point(target));
+
+    controller.update(&mut camera, &input, None, 0.1);
+
+    let pos = Vec3::from_array(camera.pos);
+    let to_target = Vec3::from_array(target) - pos;
+
+    assert!(to_target.normalize().abs_diff_eq(camera_forward(camera.yaw, camera.pitch), 1e-5));
+    assert!((to_target.length() - 5.0).abs() < 1e-4);
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use crate::core::config::{AdapterPreferences, Backend, DynamicResolution, LatrConfig, PowerPreference, PresentMode, RenderResolution};
+use crate::core::config_file::ConfigFile;
+use crate::core::error::ConfigError;
+use crate::LatrEngine;
+
+#[test]
+fn default_config_is_valid() {
+    assert!(LatrConfig::default().validate().is_ok());
+}
+
+#[test]
+fn bad_configs_are_caught_up_front() {
+    let builder = LatrEngine::builder();
+
+    let zero_size = builder.clone().resolution(0, 360);
+    assert!(matches!(zero_size.config().validate(), Err(ConfigError::ZeroResolution((0, 360)))));
+
+    let bad_scale = builder.clone().render_scale(f32::NAN);
+    assert!(matches!(bad_scale.config().validate(), Err(ConfigError::InvalidRenderScale(_))));
+
+    let flipped_scales = builder.clone().dynamic_resolution(DynamicResolution { min_scale: 1.0, max_scale: 0.5, ..Default::default() });
+    assert!(matches!(flipped_scales.config().validate(), Err(ConfigError::InvalidDynamicScales(1.0, 0.5))));
+
+    let missing_models = builder.models("does/not/exist.toml");
+    assert!(matches!(missing_models.config().validate(), Err(ConfigError::ModelFileNotFound(_))));
+}
+
+#[test]
+fn builder_fills_in_the_config() {
+    let builder = LatrEngine::builder()
+        .resolution(1280, 720)
+        .render_scale(0.5)
+        .camera_position([1.0, 2.0, 3.0])
+        .max_catch_up_ticks(3);
+
+    let config = builder.config();
+
+    assert_eq!(config.resolution, (1280, 720));
+    assert_eq!(config.render_resolution, RenderResolution::WindowScale(0.5));
+    assert_eq!(config.camera.pos, [1.0, 2.0, 3.0]);
+    assert_eq!(config.max_catch_up_ticks, 3);
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn config_file_sets_what_it_lists() {
+    let file: ConfigFile = toml::from_str(r#"
+        resolution = [1920, 1080]
+        render_scale = "fixed"
+        present_mode = "mailbox"
+        model_config = "models/ModelConfig.toml"
+
+        [adapter]
+        power_preference = "low_power"
+    "#).unwrap();
+
+    let mut config = LatrConfig::default();
+    file.apply(&mut config, Path::new("configs"));
+
+    assert_eq!(config.resolution, (1920, 1080));
+    assert_eq!(config.render_resolution, RenderResolution::Fixed);
+    assert_eq!(config.present_mode, PresentMode::Mailbox);
+    assert_eq!(config.model_file.as_deref(), Some(Path::new("configs/models/ModelConfig.toml")));
+    assert_eq!(config.adapter.power_preference, PowerPreference::LowPower);
+
+    // Everything left out keeps its default
+    assert_eq!(config.fps_cap, LatrConfig::default().fps_cap);
+}
+
+#[test]
+fn env_vars_override_the_config() {
+    let vars = [
+        ("LATR_RESOLUTION", "1280x720"),
+        ("LATR_FPS_CAP", "144"),
+        ("LATR_PRESENT_MODE", "immediate"),
+        ("LATR_RENDER_SCALE", "0.5"),
+        ("PATH", "/usr/bin"),
+    ];
+
+    let file = ConfigFile::from_env(vars.map(|(name, value)| (name.to_string(), value.to_string()))).unwrap();
+
+    let mut config = LatrConfig::default();
+    file.apply(&mut config, Path::new("."));
+
+    assert_eq!(config.resolution, (1280, 720));
+    assert_eq!(config.fps_cap, 144);
+    assert_eq!(config.present_mode, PresentMode::Immediate);
+    assert_eq!(config.render_resolution, RenderResolution::WindowScale(0.5));
+
+    let typo = ConfigFile::from_env([("LATR_FPS_CAPP".to_string(), "60".to_string())]);
+    assert!(matches!(typo, Err(ConfigError::UnknownEnvVar(_))));
+
+    let bad_value = ConfigFile::from_env([("LATR_FPS_CAP".to_string(), "fast".to_string())]);
+    assert!(matches!(bad_value, Err(ConfigError::InvalidEnvVar(..))));
+}
+
+#[test]
+fn adapter_preferences_filter_and_rank() {
+    let file = ConfigFile::from_env([
+        ("LATR_ADAPTER_BACKENDS".to_string(), "vulkan, gl".to_string()),
+        ("LATR_ADAPTER_NAME".to_string(), "llvmpipe".to_string()),
+    ]).unwrap();
+
+    let mut config = LatrConfig::default();
+    file.apply(&mut config, Path::new("."));
+
+    let prefs = &config.adapter;
+    assert_eq!(prefs.backends, vec![Backend::Vulkan, Backend::Gl]);
+    assert_eq!(prefs.wgpu_backends(), wgpu::Backends::VULKAN | wgpu::Backends::GL);
+
+    let adapter = |name: &str, device_type| wgpu::AdapterInfo {
+        name: name.to_string(),
+        vendor: 0,
+        device: 0,
+        device_type,
+        driver: String::new(),
+        driver_info: String::new(),
+        backend: wgpu::Backend::Vulkan,
+    };
+
+    assert!(prefs.matches(&adapter("llvmpipe (LLVM 15.0.7, 256 bits)", wgpu::DeviceType::Cpu)));
+    assert!(!prefs.matches(&adapter("NVIDIA GeForce RTX 3070", wgpu::DeviceType::DiscreteGpu)));
+
+    let fallback = AdapterPreferences { force_fallback: true, ..Default::default() };
+    assert!(!fallback.matches(&adapter("NVIDIA GeForce RTX 3070", wgpu::DeviceType::DiscreteGpu)));
+
+    let low_power = PowerPreference::LowPower;
+    assert!(low_power.rank(wgpu::DeviceType::IntegratedGpu) < low_power.rank(wgpu::DeviceType::DiscreteGpu));
+    assert!(low_power.rank(wgpu::DeviceType::DiscreteGpu) < low_power.rank(wgpu::DeviceType::Cpu));
+}
\ No newline at end of file
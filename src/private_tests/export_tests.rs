@@ -0,0 +1,45 @@
+use crate::engine::export::*;
+
+fn test_capture() -> FrameCapture {
+    // 2x1 frame, the left pixel hits instance 3 and the right one misses
+    FrameCapture {
+        width: 2,
+        height: 1,
+        beauty: vec![[4.0, 2.0, 1.0, 1.0], [0.5, 0.5, 0.5, 1.0]],
+        albedo: Some(vec![[0.8, 0.1, 0.1, 1.0], [0.0; 4]]),
+        normal_depth: Some(vec![[0.0, 1.0, 0.0, 2.5], [0.0, 0.0, 0.0, 1e30]]),
+        position_id: Some(vec![[1.0, 0.0, 2.5, 3.0], [0.0, 0.0, 0.0, -1.0]]),
+    }
+}
+
+#[test]
+fn aovs_split_into_named_layers() {
+    let layers = test_capture().layers();
+    let names: Vec<&str> = layers.iter().map(|(name, _)| *name).collect();
+
+    assert_eq!(names, ["beauty", "albedo", "normal", "depth", "position", "instance_id"]);
+
+    let (_, depth) = &layers[3];
+    assert_eq!(depth[0].1, [2.5, 1e30]);
+
+    let (_, instance_id) = &layers[5];
+    assert_eq!(instance_id[0].1, [3.0, -1.0]);
+}
+
+#[test]
+fn multi_layer_exr_round_trips() {
+    let path = std::env::temp_dir().join("latr_multi_layer_export_test.exr");
+    test_capture().write(&path, ExportFormat::MultiLayerExr).unwrap();
+
+    let image = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(image.layer_data.len(), 6);
+
+    let beauty = &image.layer_data[0];
+    assert_eq!(beauty.attributes.layer_name.as_ref().unwrap().to_string(), "beauty");
+
+    // Channels get sorted by name, so red comes last after alpha, blue and green
+    let red: Vec<f32> = beauty.channel_data.list[3].sample_data.values_as_f32().collect();
+    assert_eq!(red, [4.0, 0.5]);
+}
@@ -0,0 +1,45 @@
+use crate::engine::input::*;
+use winit::keyboard::KeyCode;
+
+#[test]
+fn keys_stay_down_until_released() {
+    let mut input = InputState::default();
+
+    input.apply(&InputEvent::Key { key: KeyCode::KeyW, pressed: true, repeat: false });
+    input.apply(&InputEvent::Key { key: KeyCode::KeyW, pressed: true, repeat: true });
+    assert!(input.is_key_down(KeyCode::KeyW));
+
+    input.apply(&InputEvent::Key { key: KeyCode::KeyW, pressed: false, repeat: false });
+    assert!(!input.is_key_down(KeyCode::KeyW));
+
+    // Losing focus lets go of everything, since we'd never hear about the release
+    input.apply(&InputEvent::Key { key: KeyCode::Space, pressed: true, repeat: false });
+    input.apply(&InputEvent::Focused(false));
+    assert!(!input.is_key_down(KeyCode::Space));
+}
+
+#[test]
+fn mouse_delta_covers_one_tick() {
+    let mut input = InputState::default();
+
+    input.apply(&InputEvent::MouseMotion { dx: 3.0, dy: -1.0 });
+    input.apply(&InputEvent::MouseMotion { dx: 2.0, dy: 4.0 });
+    assert_eq!(input.mouse_delta(), (5.0, 3.0));
+
+    input.begin_tick();
+    assert_eq!(input.mouse_delta(), (0.0, 0.0));
+}
+
+#[test]
+fn mouse_delta_waits_for_a_tick() {
+    let mut input = InputState::default();
+
+    // Two passes with no tick in between, the camera only sees the latest one but the tick gets both
+    input.begin_pass();
+    input.apply(&InputEvent::MouseMotion { dx: 3.0, dy: 0.0 });
+    input.begin_pass();
+    input.apply(&InputEvent::MouseMotion { dx: 2.0, dy: 0.0 });
+
+    assert_eq!(input.pass_mouse_delta(), (2.0, 0.0));
+    assert_eq!(input.mouse_delta(), (5.0, 0.0));
+}
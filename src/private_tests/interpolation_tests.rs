@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use crate::gpu::buffers::GpuUniformCamera;
+use crate::gpu::tick_interpolation::{TickInterpolator, TickState};
+
+fn tick_at(x: f32, published: Instant) -> TickState {
+    let mut camera = GpuUniformCamera::default();
+    camera.pos = [x, 0.0, 0.0];
+
+    TickState { camera, lights: None, published }
+}
+
+#[test]
+fn frames_blend_between_the_last_two_ticks() {
+    let start = Instant::now();
+    let second = Duration::from_secs(1);
+
+    let mut interpolator = TickInterpolator::new(true);
+    interpolator.push(tick_at(0.0, start));
+    interpolator.push(tick_at(10.0, start + second));
+
+    // A quarter of the way through the next tick shows the camera a quarter of the way there
+    let (camera, _) = interpolator.frame_state(start + second + second / 4).unwrap();
+    assert_eq!(camera.pos[0], 2.5);
+
+    // Past the gap, it stays on the current tick, and stops rewriting once it's there
+    let (camera, _) = interpolator.frame_state(start + second * 3).unwrap();
+    assert_eq!(camera.pos[0], 10.0);
+    assert!(interpolator.frame_state(start + second * 4).is_none());
+}
+
+#[test]
+fn turned_off_snaps_to_the_latest_tick() {
+    let start = Instant::now();
+
+    let mut interpolator = TickInterpolator::new(false);
+    interpolator.push(tick_at(0.0, start));
+    interpolator.push(tick_at(10.0, start + Duration::from_secs(1)));
+
+    let (camera, _) = interpolator.frame_state(start + Duration::from_millis(1250)).unwrap();
+    assert_eq!(camera.pos[0], 10.0);
+    assert!(interpolator.frame_state(start + Duration::from_millis(1500)).is_none());
+}
@@ -0,0 +1,68 @@
+use crate::engine::lights::*;
+use crate::gpu::buffers::*;
+
+fn triangle(vertices: [u32; 3], emission: [f32; 3]) -> GpuStorageTriangleData {
+    GpuStorageTriangleData {
+        vertices,
+        _pad: 0,
+        rgba: [1.0, 1.0, 1.0, 1.0],
+        emission: [emission[0], emission[1], emission[2], 0.0],
+    }
+}
+
+#[test]
+fn light_ids_survive_removal() {
+    let mut lights = LightSet::default();
+
+    let a = lights.add(Light::point([0.0, 1.0, 0.0], [1.0, 1.0, 1.0], 1.0));
+    let b = lights.add(Light::directional([0.0, -1.0, 0.0], [1.0, 1.0, 1.0], 2.0));
+
+    assert!(lights.remove(a).is_some());
+    assert!(lights.remove(a).is_none());
+    assert_eq!(lights.len(), 1);
+
+    // b still points at the directional light after a is gone
+    assert_eq!(lights.get_mut(b).copied(), Some(Light::directional([0.0, -1.0, 0.0], [1.0, 1.0, 1.0], 2.0)));
+
+    // The freed slot is reused
+    let c = lights.add(Light::point([5.0, 0.0, 0.0], [1.0, 0.0, 0.0], 1.0));
+    assert_eq!(c, a);
+
+    let gpu_lights = lights.to_gpu();
+    assert_eq!(gpu_lights.len(), 2);
+    assert_eq!(gpu_lights[0].kind, GpuStorageLight::KIND_POINT);
+    assert_eq!(gpu_lights[1].kind, GpuStorageLight::KIND_DIRECTIONAL);
+}
+
+#[test]
+fn emissive_triangles_become_area_lights() {
+    let vertices = [
+        GpuStorageVertex::from_arr([0.0, 0.0, 0.0]),
+        GpuStorageVertex::from_arr([1.0, 0.0, 0.0]),
+        GpuStorageVertex::from_arr([0.0, 1.0, 0.0]),
+        GpuStorageVertex::from_arr([0.0, 0.0, 0.0]),
+        GpuStorageVertex::from_arr([2.0, 0.0, 0.0]),
+        GpuStorageVertex::from_arr([0.0, 2.0, 0.0]),
+    ];
+
+    // Vertex indices are global, so they start at the vertex base
+    let triangles = [
+        triangle([10, 11, 12], [1.0, 1.0, 1.0]),
+        triangle([10, 11, 12], [0.0, 0.0, 0.0]),
+        triangle([13, 14, 15], [1.0, 1.0, 1.0]),
+    ];
+
+    let mut area_lights = collect_area_lights(&triangles, &vertices, 10, 100);
+
+    assert_eq!(area_lights.len(), 2);
+    assert_eq!(area_lights[0].triangle, 100);
+    assert_eq!(area_lights[1].triangle, 102);
+    assert_eq!(area_lights[0].area, 0.5);
+    assert_eq!(area_lights[1].area, 2.0);
+
+    // White light has a luminance of 1, so the power is just the area
+    let total = finish_area_lights(&mut area_lights);
+    assert!((total - 2.5).abs() < 1e-5);
+    assert!((area_lights[0].cdf - 0.2).abs() < 1e-5);
+    assert_eq!(area_lights[1].cdf, 1.0);
+}
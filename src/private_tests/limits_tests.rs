@@ -0,0 +1,30 @@
+use crate::core::config::{LatrConfig, SceneCapacity};
+use crate::core::error::{ConfigError, GpuError};
+use crate::gpu::buffers::GpuBuffers;
+
+#[test]
+fn default_capacity_fits_default_limits() {
+    assert!(GpuBuffers::check_limits(&wgpu::Limits::default(), &SceneCapacity::default()).is_ok());
+}
+
+#[test]
+fn oversized_scene_names_the_limit() {
+    // 2 million triangles puts the BLAS well past the default 128MB binding size
+    let capacity = SceneCapacity::for_triangles(2_000_000);
+
+    let res = GpuBuffers::check_limits(&wgpu::Limits::default(), &capacity);
+    assert!(matches!(res, Err(GpuError::BufferLimitExceeded("BLAS", "max_storage_buffer_binding_size", ..))));
+
+    // An adapter that can bind a whole buffer still has its buffer size to get past
+    let limits = wgpu::Limits { max_storage_buffer_binding_size: u32::MAX, ..Default::default() };
+
+    let res = GpuBuffers::check_limits(&limits, &capacity);
+    assert!(matches!(res, Err(GpuError::BufferLimitExceeded("BLAS", "max_buffer_size", ..))));
+
+    // Big enough that the byte sizes don't fit in a u64
+    let res = GpuBuffers::check_limits(&wgpu::Limits::default(), &SceneCapacity::for_triangles(u64::MAX / 4));
+    assert!(matches!(res, Err(GpuError::BufferLimitExceeded("triangle data", _, u64::MAX, _))));
+
+    let config = LatrConfig { scene_capacity: SceneCapacity { vertices: 0, ..Default::default() }, ..Default::default() };
+    assert!(matches!(config.validate(), Err(ConfigError::ZeroSceneCapacity("vertices"))));
+}
@@ -1 +1,15 @@
-mod bvh_tests;
\ No newline at end of file
+mod bvh_tests;
+mod light_tests;
+mod background_tests;
+mod export_tests;
+mod resize_tests;
+mod pacing_tests;
+mod input_tests;
+mod camera_tests;
+mod timestep_tests;
+mod interpolation_tests;
+mod recording_tests;
+mod config_tests;
+mod limits_tests;
+mod post_tests;
+mod render_graph_tests;
\ No newline at end of file
@@ -0,0 +1,45 @@
+use std::time::{Duration, Instant};
+
+use crate::core::config::PresentMode;
+use crate::core::frame_pacer::FramePacer;
+use crate::gpu::init_utils::choose_present_mode;
+
+#[test]
+fn capped_frames_wait_their_turn() {
+    let start = Instant::now();
+    let mut pacer = FramePacer::new(50, start);
+
+    assert!(pacer.frame_due(start));
+    pacer.frame_started(start);
+
+    // 50 fps is one frame every 20ms
+    assert!(!pacer.frame_due(start + Duration::from_millis(10)));
+    assert!(pacer.frame_due(start + Duration::from_millis(20)));
+    assert_eq!(pacer.next_frame(), Some(start + Duration::from_millis(20)));
+
+    // A long hitch starts the schedule over instead of bursting frames to catch up
+    let late = start + Duration::from_secs(1);
+    pacer.frame_started(late);
+    assert_eq!(pacer.next_frame(), Some(late + Duration::from_millis(20)));
+}
+
+#[test]
+fn zero_fps_cap_is_uncapped() {
+    let start = Instant::now();
+    let mut pacer = FramePacer::new(0, start);
+
+    pacer.frame_started(start);
+
+    assert!(pacer.frame_due(start));
+    assert_eq!(pacer.next_frame(), None);
+}
+
+#[test]
+fn present_mode_falls_back_to_supported() {
+    let fifo_only = [wgpu::PresentMode::Fifo];
+    let with_mailbox = [wgpu::PresentMode::Fifo, wgpu::PresentMode::Mailbox];
+
+    assert_eq!(choose_present_mode(PresentMode::Immediate, &fifo_only), wgpu::PresentMode::Fifo);
+    assert_eq!(choose_present_mode(PresentMode::Immediate, &with_mailbox), wgpu::PresentMode::Mailbox);
+    assert_eq!(choose_present_mode(PresentMode::Mailbox, &with_mailbox), wgpu::PresentMode::Mailbox);
+}
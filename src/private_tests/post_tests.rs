@@ -0,0 +1,63 @@
+use crate::core::error::EngineError;
+use crate::engine::post::{CubeLut, PostEffect, PostPass};
+
+struct Tint(usize);
+
+impl PostPass for Tint {
+    fn name(&self) -> &str {
+        "tint"
+    }
+
+    fn wgsl(&self) -> String {
+        "fn post(pixel: vec2<i32>, uv: vec2<f32>) -> vec4<f32> { return load_input(pixel) * param(0u); }".to_string()
+    }
+
+    fn params(&self) -> Vec<f32> {
+        vec![0.5; self.0]
+    }
+}
+
+#[test]
+fn cube_lut_parses_and_checks_its_size() {
+    let contents = "# comment\nTITLE \"identity\"\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\n\
+        0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+
+    let lut = CubeLut::parse(contents, 256).unwrap();
+    assert_eq!(lut.size, 2);
+    assert_eq!(lut.texels[1], [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(lut.texels[6], [0.0, 1.0, 1.0, 1.0]);
+
+    // One entry short
+    let short = contents.trim_end().rsplit_once('\n').unwrap().0;
+    assert!(CubeLut::parse(short, 256).is_err());
+
+    assert!(CubeLut::parse("LUT_1D_SIZE 2\n0 0 0\n1 1 1\n", 256).is_err());
+    assert!(CubeLut::parse("LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n", 256).is_err());
+
+    // Bigger than the device can make, or so big the entry count overflows
+    assert!(CubeLut::parse(contents, 1).is_err());
+    assert!(CubeLut::parse("LUT_3D_SIZE 4294967295\n", u32::MAX).is_err());
+}
+
+#[test]
+fn params_fill_from_the_front() {
+    let mut prepared = PostEffect::Vignette { strength: 0.75, radius: 0.25 }.prepare(256).unwrap();
+    assert_eq!(prepared.params[..3], [0.75, 0.25, 0.0]);
+
+    // New numbers for the same effect don't need new pipelines
+    let stronger = PostEffect::Vignette { strength: 1.0, radius: 0.25 }.prepare(256).unwrap();
+    assert!(prepared.same_passes(&stronger));
+    assert!(!prepared.same_passes(&PostEffect::sharpen().prepare(256).unwrap()));
+
+    // Loading the same LUT again still counts as the same passes
+    let grade = PostEffect::color_grade("./src/private_tests/test_files/identity.cube");
+    assert!(grade.prepare(256).unwrap().same_passes(&grade.prepare(256).unwrap()));
+
+    prepared.set_params(&[0.1]).unwrap();
+    assert_eq!(prepared.params[..2], [0.1, 0.25]);
+
+    assert_eq!(PostEffect::custom(Tint(1)).prepare(256).unwrap().params[0], 0.5);
+
+    let res = PostEffect::custom(Tint(PostEffect::MAX_PARAMS + 1)).prepare(256);
+    assert!(matches!(res, Err(EngineError::TooManyPostParams(name, 17)) if name == "tint"));
+}
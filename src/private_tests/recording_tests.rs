@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+use crate::engine::input::InputEvent;
+use crate::engine::recording::{RecordedPass, Recorder, Recording};
+
+#[test]
+fn recorded_passes_load_back_the_same() {
+    let path = std::env::temp_dir().join("latr_recording_test.toml");
+
+    let passes = vec![
+        RecordedPass {
+            real_dt_nanos: 16_666_667,
+            ticks: 1,
+            events: vec![
+                InputEvent::Key { key: KeyCode::KeyW, pressed: true, repeat: false },
+                InputEvent::MouseButton { button: MouseButton::Right, pressed: true },
+                InputEvent::MouseMotion { dx: 0.1, dy: -3.75 },
+                InputEvent::Focused(true),
+            ],
+        },
+        RecordedPass { real_dt_nanos: 16_666_667, ticks: 2, events: Vec::new() },
+    ];
+
+    {
+        let mut recorder = Recorder::create(&path, Duration::from_nanos(16_666_667)).unwrap();
+
+        for pass in &passes {
+            recorder.record(pass).unwrap();
+        }
+    }
+
+    let recording = Recording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(recording, Recording { tick_nanos: 16_666_667, passes });
+    assert_eq!(recording.tick_count(), 3);
+}
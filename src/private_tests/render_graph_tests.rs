@@ -0,0 +1,101 @@
+use crate::core::error::GpuError;
+use crate::gpu::render_graph::{BindContext, BufferId, NodeIo, RenderGraph, RenderNode, RunContext, TextureId};
+
+// Only declares, nothing here gets run on a device
+struct Node {
+    reads: Vec<TextureId>,
+    writes: Vec<TextureId>,
+    target: bool,
+}
+
+impl RenderNode for Node {
+    fn name(&self) -> &'static str {
+        "test node"
+    }
+
+    fn io(&self) -> NodeIo {
+        NodeIo { reads: self.reads.clone(), writes: self.writes.clone(), target: self.target, ..Default::default() }
+    }
+
+    fn rebind(&mut self, _ctx: &BindContext) {}
+
+    fn run(&self, _encoder: &mut wgpu::CommandEncoder, _ctx: &mut RunContext) {}
+}
+
+struct BufferNode {
+    reads: Vec<BufferId>,
+    writes: Vec<BufferId>,
+}
+
+impl RenderNode for BufferNode {
+    fn name(&self) -> &'static str {
+        "test buffer node"
+    }
+
+    fn io(&self) -> NodeIo {
+        NodeIo { buffer_reads: self.reads.clone(), buffer_writes: self.writes.clone(), ..Default::default() }
+    }
+
+    fn rebind(&mut self, _ctx: &BindContext) {}
+
+    fn run(&self, _encoder: &mut wgpu::CommandEncoder, _ctx: &mut RunContext) {}
+}
+
+fn add_texture(graph: &mut RenderGraph, keep: bool) -> TextureId {
+    graph.add_texture("test texture", wgpu::TextureFormat::Rgba16Float, wgpu::TextureUsages::empty(), keep)
+}
+
+#[test]
+fn reads_need_an_earlier_write() {
+    let mut graph = RenderGraph::new((4, 4));
+    let a = add_texture(&mut graph, false);
+    let b = add_texture(&mut graph, false);
+
+    graph.add_node(Node { reads: vec![a], writes: vec![b], target: false });
+    assert!(matches!(graph.validate(), Err(GpuError::RenderGraph(_))));
+
+    let mut graph = RenderGraph::new((4, 4));
+    let a = add_texture(&mut graph, false);
+
+    graph.add_node(Node { reads: vec![], writes: vec![a], target: false });
+    graph.add_node(Node { reads: vec![a], writes: vec![a], target: false });
+    assert!(matches!(graph.validate(), Err(GpuError::RenderGraph(_))));
+}
+
+#[test]
+fn nodes_nobody_sees_are_skipped() {
+    let mut graph = RenderGraph::new((4, 4));
+    let screen = add_texture(&mut graph, true);
+    let post = add_texture(&mut graph, false);
+
+    // Trace, post effect, blit
+    graph.add_node(Node { reads: vec![], writes: vec![screen], target: false });
+    graph.add_node(Node { reads: vec![screen], writes: vec![post], target: false });
+    graph.add_node(Node { reads: vec![post], writes: vec![], target: true });
+    assert!(graph.validate().is_ok());
+
+    assert_eq!(graph.needed_nodes(true), [true, true, true]);
+
+    // Without a target only the kept screen texture matters
+    assert_eq!(graph.needed_nodes(false), [true, false, false]);
+}
+
+#[test]
+fn buffers_are_ordered_like_textures() {
+    let mut graph = RenderGraph::new((4, 4));
+    let uploaded = graph.add_buffer("uploaded buffer", true, false);
+    let written = graph.add_buffer("written buffer", false, false);
+
+    graph.add_node(BufferNode { reads: vec![uploaded, written], writes: vec![] });
+    assert!(matches!(graph.validate(), Err(GpuError::RenderGraph(_))));
+
+    let mut graph = RenderGraph::new((4, 4));
+    let uploaded = graph.add_buffer("uploaded buffer", true, false);
+    let kept = graph.add_buffer("kept buffer", false, true);
+
+    graph.add_node(BufferNode { reads: vec![uploaded], writes: vec![kept] });
+    assert!(graph.validate().is_ok());
+
+    // Kept buffers keep their writers alive even without a target
+    assert_eq!(graph.needed_nodes(false), [true]);
+}
@@ -0,0 +1,47 @@
+use crate::core::config::*;
+
+#[test]
+fn render_size_follows_resolution_mode() {
+    let num_rays = (320, 180);
+
+    assert_eq!(RenderResolution::Fixed.render_size(num_rays, (1920, 1080)), (320, 180));
+    assert_eq!(RenderResolution::WindowScale(0.5).render_size(num_rays, (1920, 1080)), (960, 540));
+
+    // Tiny windows still get a texture that exists
+    assert_eq!(RenderResolution::WindowScale(0.1).render_size(num_rays, (3, 3)), (1, 1));
+}
+
+#[test]
+fn letterbox_keeps_aspect_ratio() {
+    // 16:9 image in a square window gets bars above and below
+    let [x, y, w, h] = ScaleMode::Letterbox.viewport((160, 90), (800, 800));
+    assert_eq!([x, y, w, h], [0.0, 175.0, 800.0, 450.0]);
+
+    // Stretching always covers the whole window
+    assert_eq!(ScaleMode::Stretch.viewport((160, 90), (800, 800)), [0.0, 0.0, 800.0, 800.0]);
+}
+
+#[test]
+fn dynamic_resolution_drops_scale_when_slow() {
+    use crate::gpu::dynamic_resolution::ResolutionController;
+    use std::time::Duration;
+
+    let settings = DynamicResolution {
+        target_frame_time: Duration::from_millis(10),
+        min_scale: 0.25,
+        max_scale: 1.0,
+    };
+
+    let mut controller = ResolutionController::new(settings);
+
+    // Frames taking 4x the target want a quarter of the pixels, which is half the scale
+    let changes: Vec<f32> = (0..100)
+        .filter_map(|_| controller.update(Duration::from_millis(40)))
+        .collect();
+
+    // The timings don't improve here, so it keeps going until it hits the minimum
+    assert_eq!(changes, [0.5, 0.25]);
+
+    // Once frames are on target nothing changes
+    assert!((0..100).all(|_| controller.update(Duration::from_millis(10)).is_none()));
+}
@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use crate::engine::timestep::FixedTimestep;
+
+const TICK: Duration = Duration::from_millis(10);
+
+#[test]
+fn accumulator_hands_out_whole_ticks() {
+    let start = Instant::now();
+    let mut timestep = FixedTimestep::new(TICK, 5, start);
+
+    // 25ms is two ticks, with the leftover 5ms carried into the next pass
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(25)), 2);
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(30)), 1);
+
+    // Falling a second behind only catches up 5 ticks, the rest are dropped
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(1030)), 5);
+    assert_eq!(timestep.stats().dropped_ticks, 95);
+}
+
+#[test]
+fn time_scale_changes_how_often_ticks_run() {
+    let start = Instant::now();
+    let mut timestep = FixedTimestep::new(TICK, 5, start);
+
+    timestep.set_time_scale(0.5);
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(20)), 1);
+
+    timestep.set_time_scale(2.0);
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(40)), 4);
+}
+
+#[test]
+fn paused_only_runs_stepped_ticks() {
+    let start = Instant::now();
+    let mut timestep = FixedTimestep::new(TICK, 5, start);
+
+    timestep.set_paused(true);
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(100)), 0);
+
+    timestep.step_once();
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(200)), 1);
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(300)), 0);
+
+    // Time spent paused doesn't turn into catch up ticks
+    timestep.set_paused(false);
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(305)), 0);
+    assert_eq!(timestep.ticks_due(start + Duration::from_millis(315)), 1);
+}
+
+#[test]
+fn tiny_time_scale_still_wakes_every_tick() {
+    let start = Instant::now();
+    let mut timestep = FixedTimestep::new(TICK, 5, start);
+
+    // A tick's worth of real time at this scale doesn't fit in a Duration
+    timestep.set_time_scale(f32::MIN_POSITIVE);
+    assert_eq!(timestep.time_until_next(start), TICK);
+}
@@ -27,14 +27,122 @@ fn vs_main(@builtin(vertex_index) idx: u32) -> VertexOutput {
 }
 
 // !-- Fragment Shader Code --! //
+
+// Has to match ToneMapping::to_gpu in config.rs
+const TONEMAP_NONE: u32 = 0u;
+const TONEMAP_REINHARD: u32 = 1u;
+const TONEMAP_ACES: u32 = 2u;
+const TONEMAP_AGX: u32 = 3u;
+
+struct ToneMap {
+    kind: u32,
+    exposure: f32,
+    encode_srgb: u32,
+    _pad: u32,
+}
+
 @group(0) @binding(0) var input_texture: texture_2d<f32>;
 @group(0) @binding(1) var my_sampler: sampler;
+@group(0) @binding(2) var<uniform> tone_map: ToneMap;
 
 @fragment
 fn fs_main(@location(0) in_uv: vec2<f32>) -> @location(0) vec4<f32> {
     // Function to sample the texture using the provided sampler at the UV coordinate
     // Just goes through and takes the pixel color and samples it from the texture
-    let final_color = textureSample(input_texture, my_sampler, in_uv);
+    let hdr_color = textureSample(input_texture, my_sampler, in_uv).rgb * exp2(tone_map.exposure);
+
+    var final_color = apply_tone_map(hdr_color);
+
+    // sRGB surfaces encode on write, anything else we have to do ourselves
+    if (tone_map.encode_srgb != 0u) {
+        final_color = linear_to_srgb(final_color);
+    }
+
+    return vec4<f32>(final_color, 1.0);
+}
+
+fn apply_tone_map(color: vec3<f32>) -> vec3<f32> {
+    switch (tone_map.kind) {
+        case TONEMAP_REINHARD: {
+            return color / (1.0 + color);
+        }
+        case TONEMAP_ACES: {
+            return aces_fitted(color);
+        }
+        case TONEMAP_AGX: {
+            return agx(color);
+        }
+        default: {
+            return clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+    }
+}
+
+// Stephen Hill's fit of the ACES reference transform, going through the RRT and ODT in one curve
+fn aces_fitted(color: vec3<f32>) -> vec3<f32> {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    // WGSL matrices are column major, so these are the transposes of how they're usually written
+    let aces_input = mat3x3<f32>(
+        vec3<f32>(0.59719, 0.07600, 0.02840),
+        vec3<f32>(0.35458, 0.90834, 0.13383),
+        vec3<f32>(0.04823, 0.01566, 0.83777),
+    );
+
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    let aces_output = mat3x3<f32>(
+        vec3<f32>(1.60475, -0.10208, -0.00327),
+        vec3<f32>(-0.53108, 1.10813, -0.07276),
+        vec3<f32>(-0.07367, -0.00605, 1.07602),
+    );
+
+    let v = aces_input * color;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+
+    return clamp(aces_output * (a / b), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Minimal AgX, from Benjamin Wrensch's fit of the Blender implementation
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let agx_inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+
+    let agx_outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+
+    // Log2 encoding over the range the curve was fit for
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var v = agx_inset * color;
+    v = clamp(log2(max(v, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    v = (v - min_ev) / (max_ev - min_ev);
+
+    // 6th order polynomial fit of the sigmoid contrast curve
+    let v2 = v * v;
+    let v4 = v2 * v2;
+    v = 15.5 * v4 * v2
+        - 40.14 * v4 * v
+        + 31.96 * v4
+        - 6.868 * v2 * v
+        + 0.4298 * v2
+        + 0.1191 * v
+        - 0.00232;
+
+    // The curve comes out gamma encoded, so we bring it back to linear for the rest of the pipeline
+    v = agx_outset * v;
+    return pow(max(v, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
 
-    return final_color;
+    return select(high, low, color <= vec3<f32>(0.0031308));
 }
\ No newline at end of file
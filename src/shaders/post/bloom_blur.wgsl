@@ -0,0 +1,28 @@
+// First half of bloom, keeps what's over the threshold and blurs it sideways
+// param 0 = threshold, param 2 = radius in pixels
+
+const MAX_RADIUS: i32 = 64;
+
+fn bright(color: vec3<f32>, threshold: f32) -> vec3<f32> {
+    let luma = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+
+    // Scaled rather than cut off, so there's no hard edge where a surface crosses the threshold
+    return color * max(luma - threshold, 0.0) / max(luma, 1e-4);
+}
+
+fn post(pixel: vec2<i32>, uv: vec2<f32>) -> vec4<f32> {
+    let threshold = param(0u);
+    let radius = clamp(i32(ceil(param(2u))), 1, MAX_RADIUS);
+    let sigma = f32(radius) / 3.0;
+
+    var sum = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+
+    for (var i = -radius; i <= radius; i++) {
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        sum += bright(load_input(pixel + vec2<i32>(i, 0)).rgb, threshold) * weight;
+        weight_sum += weight;
+    }
+
+    return vec4<f32>(sum / weight_sum, 1.0);
+}
@@ -0,0 +1,23 @@
+// Second half of bloom, blurs the bright parts downwards and adds them over the image the effect started with
+// param 1 = intensity, param 2 = radius in pixels
+
+const MAX_RADIUS: i32 = 64;
+
+fn post(pixel: vec2<i32>, uv: vec2<f32>) -> vec4<f32> {
+    let intensity = param(1u);
+    let radius = clamp(i32(ceil(param(2u))), 1, MAX_RADIUS);
+    let sigma = f32(radius) / 3.0;
+
+    var sum = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+
+    for (var i = -radius; i <= radius; i++) {
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        sum += load_input(pixel + vec2<i32>(0, i)).rgb * weight;
+        weight_sum += weight;
+    }
+
+    let source = load_source(pixel);
+
+    return vec4<f32>(source.rgb + sum / weight_sum * intensity, source.a);
+}
@@ -0,0 +1,12 @@
+// Pulls red outwards and blue inwards, more the further from the center
+// param 0 = strength, how far red and blue shift at the corners as a fraction of the image
+
+fn post(pixel: vec2<i32>, uv: vec2<f32>) -> vec4<f32> {
+    let offset = (uv - 0.5) * param(0u);
+    let color = load_input(pixel);
+
+    let red = sample_input(uv + offset).r;
+    let blue = sample_input(uv - offset).b;
+
+    return vec4<f32>(red, color.g, blue, color.a);
+}
@@ -0,0 +1,51 @@
+// Runs the image through a 3d LUT, which maps sRGB encoded colors from 0 to 1 to graded ones
+// The image is still hdr here, so colors brighter than 1 are graded at the brightness of 1 and scaled back up after
+// param 0 = strength, from 0 for the original image to 1 for fully graded
+
+fn post(pixel: vec2<i32>, uv: vec2<f32>) -> vec4<f32> {
+    let strength = param(0u);
+    let color = load_input(pixel);
+
+    let scale = max(max(color.r, max(color.g, color.b)), 1.0);
+    let encoded = linear_to_srgb(clamp(color.rgb / scale, vec3<f32>(0.0), vec3<f32>(1.0)));
+    let graded = srgb_to_linear(lookup(encoded, i32(textureDimensions(lut_texture).x))) * scale;
+
+    return vec4<f32>(mix(color.rgb, graded, strength), color.a);
+}
+
+// Trilinear by hand, the LUT is 32 bit float which can't go through a filtering sampler
+fn lookup(color: vec3<f32>, size: i32) -> vec3<f32> {
+    let coord = color * f32(size - 1);
+    let base = min(vec3<i32>(floor(coord)), vec3<i32>(size - 2));
+    let f = coord - vec3<f32>(base);
+
+    let c000 = textureLoad(lut_texture, base, 0).rgb;
+    let c100 = textureLoad(lut_texture, base + vec3<i32>(1, 0, 0), 0).rgb;
+    let c010 = textureLoad(lut_texture, base + vec3<i32>(0, 1, 0), 0).rgb;
+    let c110 = textureLoad(lut_texture, base + vec3<i32>(1, 1, 0), 0).rgb;
+    let c001 = textureLoad(lut_texture, base + vec3<i32>(0, 0, 1), 0).rgb;
+    let c101 = textureLoad(lut_texture, base + vec3<i32>(1, 0, 1), 0).rgb;
+    let c011 = textureLoad(lut_texture, base + vec3<i32>(0, 1, 1), 0).rgb;
+    let c111 = textureLoad(lut_texture, base + vec3<i32>(1, 1, 1), 0).rgb;
+
+    let x00 = mix(c000, c100, f.x);
+    let x10 = mix(c010, c110, f.x);
+    let x01 = mix(c001, c101, f.x);
+    let x11 = mix(c011, c111, f.x);
+
+    return mix(mix(x00, x10, f.y), mix(x01, x11, f.y), f.z);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((max(color, vec3<f32>(0.0)) + 0.055) / 1.055, vec3<f32>(2.4));
+
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
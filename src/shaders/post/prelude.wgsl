@@ -0,0 +1,50 @@
+// Put in front of every post effect, the effect's own code comes after this and defines post
+// Each pass reads input_texture and writes whatever post returns, see PostPass in post.rs
+
+struct PostFrame {
+    size: vec2<f32>,
+    texel: vec2<f32>,
+}
+
+// What the last pass wrote, or the traced image for the first one
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+
+// What the effect started from, the same as input_texture except in the later stages of bloom
+@group(0) @binding(1) var source_texture: texture_2d<f32>;
+
+@group(0) @binding(2) var output_texture: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3) var linear_sampler: sampler;
+@group(0) @binding(4) var<uniform> frame: PostFrame;
+@group(0) @binding(5) var<uniform> params: array<vec4<f32>, 4>;
+
+// Only color grading has a real one, everything else gets a 1x1x1 placeholder
+@group(0) @binding(6) var lut_texture: texture_3d<f32>;
+
+fn param(index: u32) -> f32 {
+    return params[index / 4u][index % 4u];
+}
+
+// Bilinear, and clamped at the edges
+fn sample_input(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(input_texture, linear_sampler, uv, 0.0);
+}
+
+fn load_input(pixel: vec2<i32>) -> vec4<f32> {
+    return textureLoad(input_texture, clamp(pixel, vec2<i32>(0), vec2<i32>(frame.size) - 1), 0);
+}
+
+fn load_source(pixel: vec2<i32>) -> vec4<f32> {
+    return textureLoad(source_texture, clamp(pixel, vec2<i32>(0), vec2<i32>(frame.size) - 1), 0);
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (f32(id.x) >= frame.size.x || f32(id.y) >= frame.size.y) {
+        return;
+    }
+
+    let pixel = vec2<i32>(id.xy);
+    let uv = (vec2<f32>(id.xy) + 0.5) * frame.texel;
+
+    textureStore(output_texture, pixel, post(pixel, uv));
+}
@@ -0,0 +1,17 @@
+// Unsharp mask over the four direct neighbours
+// param 0 = strength
+
+fn post(pixel: vec2<i32>, uv: vec2<f32>) -> vec4<f32> {
+    let strength = param(0u);
+    let center = load_input(pixel);
+
+    let neighbours = load_input(pixel + vec2<i32>(1, 0)).rgb
+        + load_input(pixel - vec2<i32>(1, 0)).rgb
+        + load_input(pixel + vec2<i32>(0, 1)).rgb
+        + load_input(pixel - vec2<i32>(0, 1)).rgb;
+
+    let sharpened = center.rgb * (1.0 + 4.0 * strength) - neighbours * strength;
+
+    // Negative light would turn into odd colors after tone mapping
+    return vec4<f32>(max(sharpened, vec3<f32>(0.0)), center.a);
+}
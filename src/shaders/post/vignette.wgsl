@@ -0,0 +1,14 @@
+// Darkens towards the corners
+// param 0 = strength, param 1 = distance from the center where it starts, 0 at the center and 1 at the corners
+
+fn post(pixel: vec2<i32>, uv: vec2<f32>) -> vec4<f32> {
+    let strength = param(0u);
+    let radius = param(1u);
+
+    let dist = length(uv - 0.5) / length(vec2<f32>(0.5));
+    let darken = strength * smoothstep(radius, 1.0, dist);
+
+    let color = load_input(pixel);
+
+    return vec4<f32>(color.rgb * (1.0 - darken), color.a);
+}
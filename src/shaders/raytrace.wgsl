@@ -12,6 +12,7 @@ struct InstanceMeshWrapper {
 struct TriangleData {
     vertices: vec3<u32>,
     rgba: vec4<f32>,
+    emission: vec4<f32>,
 }
 
 struct TriangleDataWrapper {
@@ -52,6 +53,74 @@ struct BvhNodeWrapper {
 
 struct Camera {
     pos: vec3f,
+    yaw: f32,
+    pitch: f32,
+}
+
+// See GpuStorageLight in buffers.rs
+const LIGHT_POINT: u32 = 0u;
+const LIGHT_DIRECTIONAL: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
+struct Light {
+    position: vec3f,
+    kind: u32,
+    direction: vec3f,
+    range: f32,
+    color: vec3f,
+    intensity: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+    _pad: vec2<u32>,
+}
+
+struct LightWrapper {
+    data: array<Light>,
+}
+
+struct AreaLight {
+    v0: vec3f,
+    triangle: u32,
+    v1: vec3f,
+    area: f32,
+    v2: vec3f,
+    cdf: f32,
+    emission: vec3f,
+    _pad: u32,
+}
+
+struct AreaLightWrapper {
+    data: array<AreaLight>,
+}
+
+struct LightInfo {
+    light_count: u32,
+    area_light_count: u32,
+    area_light_power: f32,
+    _pad: u32,
+}
+
+// See GpuUniformBackground in buffers.rs
+const BACKGROUND_COLOR: u32 = 0u;
+const BACKGROUND_GRADIENT: u32 = 1u;
+const BACKGROUND_SKY: u32 = 2u;
+const BACKGROUND_ENVIRONMENT: u32 = 3u;
+
+struct Background {
+    kind: u32,
+    env_width: u32,
+    env_height: u32,
+    env_total: f32,
+    color: vec3f,
+    intensity: f32,
+    horizon: vec3f,
+    env_rotation: f32,
+    ground: vec3f,
+    _pad0: u32,
+    sun_dir: vec3f,
+    _pad1: u32,
+    sun_color: vec3f,
+    _pad2: u32,
 }
 
 struct Ray {
@@ -59,6 +128,59 @@ struct Ray {
     direction: vec3f,
 }
 
+// t is the distance along the ray, u and v are the barycentrics from intersect_tri
+// steps and box_tests count the work traversal did, for the heatmap debug views
+struct Hit {
+    t: f32,
+    u: f32,
+    v: f32,
+    triangle: u32,
+    instance: u32,
+    hit: bool,
+    steps: u32,
+    box_tests: u32,
+}
+
+// See RenderMode::to_gpu in config.rs
+const MODE_SHADED: u32 = 0u;
+const MODE_NORMALS: u32 = 1u;
+const MODE_DEPTH: u32 = 2u;
+const MODE_BARYCENTRICS: u32 = 3u;
+const MODE_TRIANGLE_ID: u32 = 4u;
+const MODE_INSTANCE_ID: u32 = 5u;
+const MODE_TRAVERSAL_STEPS: u32 = 6u;
+const MODE_BOX_TESTS: u32 = 7u;
+
+struct RenderSettings {
+    mode: u32,
+    aovs: u32,
+    _pad: vec2<u32>,
+}
+
+// Counts that show up as full red in the heatmaps
+const HEATMAP_MAX_STEPS: f32 = 128.0;
+const HEATMAP_MAX_BOX_TESTS: f32 = 512.0;
+
+// Distance that shows up as half brightness in the depth view
+const DEPTH_HALF_DISTANCE: f32 = 10.0;
+
+const PI: f32 = 3.14159265359;
+
+// Far enough to count as infinity, without running into inf math on some drivers
+const T_MAX: f32 = 1e30;
+
+// Offset for rays leaving a surface, stops them from hitting the surface they started on
+const RAY_EPSILON: f32 = 0.001;
+
+// Cosine of the angular radius of the sun disk, about a quarter of a degree like the real sun
+const SUN_COS_ANGLE: f32 = 0.99999;
+
+// Solid angle of the sun disk, 2 * PI * (1 - SUN_COS_ANGLE)
+const SUN_SOLID_ANGLE: f32 = 0.0000628;
+
+// Depth of the traversal stacks, a bvh4 this deep holds far more triangles than the buffers can
+const STACK_SIZE: u32 = 32u;
+
 fn default_ray() -> Ray {
     let ray = Ray(
         vec3<f32>(0.0, 0.0, 0.0,),
@@ -69,7 +191,7 @@ fn default_ray() -> Ray {
 }
 
 @group(0) @binding(0)
-var output_texture: texture_storage_2d<rgba8unorm, write>;
+var output_texture: texture_storage_2d<rgba16float, write>;
 
 @group(0) @binding(1)
 var<uniform> camera_uniform: Camera;
@@ -89,6 +211,39 @@ var<storage, read> tlas_storage: BvhNodeWrapper;
 @group(0) @binding(6)
 var<storage, read> blas_storage: BvhNodeWrapper;
 
+@group(0) @binding(7)
+var<storage, read> light_storage: LightWrapper;
+
+@group(0) @binding(8)
+var<storage, read> area_light_storage: AreaLightWrapper;
+
+@group(0) @binding(9)
+var<uniform> light_info: LightInfo;
+
+@group(0) @binding(10)
+var<uniform> background: Background;
+
+@group(0) @binding(11)
+var env_texture: texture_2d<f32>;
+
+// Rows 0..env_height are per row cdfs, row env_height picks the row, see EnvironmentImage in background.rs
+@group(0) @binding(12)
+var env_cdf: texture_2d<f32>;
+
+@group(0) @binding(13)
+var<uniform> render_settings: RenderSettings;
+
+// Aovs, only written when render_settings.aovs is set, otherwise these are 1x1 placeholders
+// See AovTextures in aovs.rs for the packing
+@group(0) @binding(14)
+var aov_albedo: texture_storage_2d<rgba16float, write>;
+
+@group(0) @binding(15)
+var aov_normal_depth: texture_storage_2d<rgba32float, write>;
+
+@group(0) @binding(16)
+var aov_position_id: texture_storage_2d<rgba32float, write>;
+
 @compute
 @workgroup_size(8, 8, 1) // Workgroup size is just temporary for now, but 8x8 seems like a good standard
 fn main(
@@ -97,40 +252,629 @@ fn main(
     let texture_dims = textureDimensions(output_texture); // We fetch the size of the texture to compare to our workgroup
     let texture_coord = vec2<u32>(global_id.xy);
 
+    // Workgroups are rounded up, so the edges have threads that are off the texture
+    if (texture_coord.x >= texture_dims.x || texture_coord.y >= texture_dims.y) {
+        return;
+    }
+
     let math_coord = vec2<f32>(global_id.xy);
 
-    var pixel_color = vec3<f32>(1.0, 1.0, 1.0);
+    var rng_state = hash_u32(texture_coord.x + texture_coord.y * texture_dims.x);
 
-    let ray: Ray = get_ray_from_screen_coord(math_coord);
+    let ray: Ray = get_ray_from_screen_coord(math_coord, vec2<f32>(texture_dims));
+    let hit = trace_closest(ray);
 
-    // Just sets all pixels to red to test that this works
-    pixel_color = vec3<f32>(0.0, 0.0, 1.0);
+    var pixel_color: vec3<f32>;
+
+    if (render_settings.mode == MODE_SHADED) {
+        pixel_color = shade(ray, hit, &rng_state);
+    } else {
+        pixel_color = debug_color(ray, hit);
+    }
 
     textureStore(
         output_texture,
         texture_coord,
         vec4<f32>(pixel_color, 1.0),
     );
-    
+
+    if (render_settings.aovs != 0u) {
+        write_aovs(texture_coord, ray, hit);
+    }
+}
+
+// Misses get no albedo or normal, a depth of T_MAX and an instance of -1
+fn write_aovs(coord: vec2<u32>, ray: Ray, hit: Hit) {
+    if (!hit.hit) {
+        textureStore(aov_albedo, coord, vec4<f32>(0.0));
+        textureStore(aov_normal_depth, coord, vec4<f32>(0.0, 0.0, 0.0, T_MAX));
+        textureStore(aov_position_id, coord, vec4<f32>(0.0, 0.0, 0.0, -1.0));
+        return;
+    }
+
+    let albedo = triangle_storage.data[hit.triangle].rgba.rgb;
+    let normal = get_hit_normal(ray, hit);
+    let pos = ray.origin + ray.direction * hit.t;
+
+    textureStore(aov_albedo, coord, vec4<f32>(albedo, 1.0));
+    textureStore(aov_normal_depth, coord, vec4<f32>(normal, hit.t));
+    textureStore(aov_position_id, coord, vec4<f32>(pos, f32(hit.instance)));
 }
 
-fn get_ray_from_screen_coord(screen_coord: vec2<f32>) -> Ray {
+fn get_ray_from_screen_coord(screen_coord: vec2<f32>, screen_dims: vec2<f32>) -> Ray {
     var ray: Ray = default_ray();
     ray.origin = camera_uniform.pos;
 
-    // This should be the coordinate of the 2d coordinate but in 3d space relative to the camera
-    // As said, this is relative, so its value is actually the same as the momentum
-    var screen_3d_coord = vec3<f32>(0.0, 0.0, 0.0);
+    // We map the center of the pixel to -1..1, flipping y since the texture counts downwards
+    // x is scaled by the aspect ratio so pixels stay square
+    let uv = (screen_coord + 0.5) / screen_dims * 2.0 - 1.0;
+    let aspect = screen_dims.x / screen_dims.y;
+
+    // Same as camera_forward and camera_right in camera.rs, see the math conventions in the README
+    // The screen sits 1 unit in front of the camera, which gives a 90 degree tall view
+    let forward = vec3<f32>(
+        cos(camera_uniform.pitch) * cos(camera_uniform.yaw),
+        sin(camera_uniform.pitch),
+        -cos(camera_uniform.pitch) * sin(camera_uniform.yaw),
+    );
+    let right = normalize(cross(forward, vec3<f32>(0.0, 1.0, 0.0)));
+    let up = cross(right, forward);
 
-    // We just have 1 for now to symbolize the camera facing forward
-    // This means that our camera's coordinates should be negative to look forward at some object at 0, 0, 0
-    screen_3d_coord = vec3<f32>(screen_coord.x, screen_coord.y, 1);
+    let screen_3d_coord = forward + right * uv.x * aspect - up * uv.y;
 
-    ray.direction = screen_3d_coord;
+    ray.direction = normalize(screen_3d_coord);
 
     return ray;
 }
 
+// !-- Traversal --! //
+
+// Slab test against all 4 boxes of a bvh4 node at once
+// Returns the entry distance of each box, or -1 for boxes the ray misses
+fn intersect_node(node: BvhNode, origin: vec3f, inv_dir: vec3f, t_max: f32) -> vec4<f32> {
+    let t1_x = (node.min_x - origin.x) * inv_dir.x;
+    let t2_x = (node.max_x - origin.x) * inv_dir.x;
+    let t1_y = (node.min_y - origin.y) * inv_dir.y;
+    let t2_y = (node.max_y - origin.y) * inv_dir.y;
+    let t1_z = (node.min_z - origin.z) * inv_dir.z;
+    let t2_z = (node.max_z - origin.z) * inv_dir.z;
+
+    let t_enter = max(max(min(t1_x, t2_x), min(t1_y, t2_y)), max(min(t1_z, t2_z), vec4<f32>(0.0)));
+    let t_exit = min(min(max(t1_x, t2_x), max(t1_y, t2_y)), min(max(t1_z, t2_z), vec4<f32>(t_max)));
+
+    return select(vec4<f32>(-1.0), t_enter, t_enter <= t_exit);
+}
+
+// Number of filled slots in a node, which is how many boxes intersect_node really tested
+fn count_slots(node: BvhNode) -> u32 {
+    return dot(vec4<u32>(node.indices != vec4<i32>(0)), vec4<u32>(1u));
+}
+
+fn safe_inverse(dir: vec3f) -> vec3f {
+    // Axis aligned rays would divide by 0, so we nudge them to a tiny value instead
+    let nudged = select(dir, vec3<f32>(1e-8), abs(dir) < vec3<f32>(1e-8));
+    return 1.0 / nudged;
+}
+
+fn get_triangle(idx: u32) -> mat3x3<f32> {
+    let vertices = triangle_storage.data[idx].vertices;
+    let v0 = vertex_storage.data[vertices.x];
+    let v1 = vertex_storage.data[vertices.y];
+    let v2 = vertex_storage.data[vertices.z];
+
+    return mat3x3<f32>(
+        vec3<f32>(v0.x, v0.y, v0.z),
+        vec3<f32>(v1.x, v1.y, v1.z),
+        vec3<f32>(v2.x, v2.y, v2.z),
+    );
+}
+
+// Walks the BLAS of a single instance, with the ray already moved into the mesh's local space
+// Since the direction isn't renormalized after the transform, t still matches world space
+// See note 1 and 3 of the buffer docs for the index format
+fn traverse_blas(ray: Ray, entry: i32, instance: u32, hit: ptr<function, Hit>, any_hit: bool) {
+    let inv_dir = safe_inverse(ray.direction);
+
+    var stack: array<i32, STACK_SIZE>;
+    var stack_len = 1u;
+    stack[0] = entry;
+
+    while (stack_len > 0u) {
+        stack_len -= 1u;
+        let idx = stack[stack_len];
+
+        // Leaf, test the triangle
+        if (idx < 0) {
+            let tri_idx = u32(~idx);
+            let uvt = intersect_tri(ray.origin, ray.direction, get_triangle(tri_idx));
+
+            if (uvt.z > RAY_EPSILON && uvt.z < (*hit).t) {
+                (*hit).t = uvt.z;
+                (*hit).u = uvt.x;
+                (*hit).v = uvt.y;
+                (*hit).triangle = tri_idx;
+                (*hit).instance = instance;
+                (*hit).hit = true;
+
+                if (any_hit) {
+                    return;
+                }
+            }
+
+            continue;
+        }
+
+        let node = blas_storage.nodes[idx - 1];
+        let t_enter = intersect_node(node, ray.origin, inv_dir, (*hit).t);
+
+        (*hit).steps += 1u;
+        (*hit).box_tests += count_slots(node);
+
+        for (var slot = 0; slot < 4; slot++) {
+            // 0 is an empty slot
+            if (node.indices[slot] != 0 && t_enter[slot] >= 0.0 && stack_len < STACK_SIZE) {
+                stack[stack_len] = node.indices[slot];
+                stack_len += 1u;
+            }
+        }
+    }
+}
+
+// Walks the TLAS, handing each instance it reaches off to traverse_blas
+// any_hit returns as soon as anything is hit, which is all a shadow ray needs
+fn trace_scene(ray: Ray, t_max: f32, any_hit: bool) -> Hit {
+    var hit = Hit(t_max, 0.0, 0.0, 0u, 0u, false, 0u, 0u);
+    let inv_dir = safe_inverse(ray.direction);
+
+    var stack: array<i32, STACK_SIZE>;
+    var stack_len = 1u;
+
+    // Node 0 is the entry node of the scene, see buffer docs
+    stack[0] = 1;
+
+    while (stack_len > 0u) {
+        stack_len -= 1u;
+        let idx = stack[stack_len];
+
+        // Instance, move the ray into its local space and walk its BLAS
+        if (idx < 0) {
+            let instance_idx = u32(~idx);
+            let instance = instance_storage.data[instance_idx];
+
+            let local_ray = Ray(
+                (instance.inverse_transformation_matrix * vec4<f32>(ray.origin, 1.0)).xyz,
+                (instance.inverse_transformation_matrix * vec4<f32>(ray.direction, 0.0)).xyz,
+            );
+
+            traverse_blas(local_ray, instance.blas_entry, instance_idx, &hit, any_hit);
+
+            if (any_hit && hit.hit) {
+                return hit;
+            }
+
+            continue;
+        }
+
+        let node = tlas_storage.nodes[idx - 1];
+        let t_enter = intersect_node(node, ray.origin, inv_dir, hit.t);
+
+        hit.steps += 1u;
+        hit.box_tests += count_slots(node);
+
+        for (var slot = 0; slot < 4; slot++) {
+            if (node.indices[slot] != 0 && t_enter[slot] >= 0.0 && stack_len < STACK_SIZE) {
+                stack[stack_len] = node.indices[slot];
+                stack_len += 1u;
+            }
+        }
+    }
+
+    return hit;
+}
+
+fn trace_closest(ray: Ray) -> Hit {
+    return trace_scene(ray, T_MAX, false);
+}
+
+// True if nothing blocks the ray before max_dist
+fn is_visible(origin: vec3f, dir: vec3f, max_dist: f32) -> bool {
+    let shadow_ray = Ray(origin, dir);
+    return !trace_scene(shadow_ray, max_dist, true).hit;
+}
+
+// !-- Shading --! //
+
+// World space normal of the hit triangle, flipped to face back along the ray
+fn get_hit_normal(ray: Ray, hit: Hit) -> vec3f {
+    let tri = get_triangle(hit.triangle);
+    let local_normal = cross(tri[1] - tri[0], tri[2] - tri[0]);
+
+    // Normals transform by the inverse transpose, and we already have the inverse
+    let inverse_matrix = instance_storage.data[hit.instance].inverse_transformation_matrix;
+    let world_normal = normalize((transpose(inverse_matrix) * vec4<f32>(local_normal, 0.0)).xyz);
+
+    return select(world_normal, -world_normal, dot(world_normal, ray.direction) > 0.0);
+}
+
+// Falloff for lights with a range, smoothly reaching 0 at the range instead of cutting off
+fn range_falloff(dist: f32, range: f32) -> f32 {
+    if (range <= 0.0) {
+        return 1.0;
+    }
+
+    let ratio = dist / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window;
+}
+
+// Incoming light at a point from a single punctual light, shadow included
+fn eval_light(light: Light, pos: vec3f, normal: vec3f) -> vec3f {
+    var to_light: vec3f;
+    var dist = T_MAX;
+    var attenuation = 1.0;
+
+    if (light.kind == LIGHT_DIRECTIONAL) {
+        to_light = -light.direction;
+    } else {
+        let offset = light.position - pos;
+        dist = length(offset);
+        to_light = offset / dist;
+        attenuation = range_falloff(dist, light.range) / (dist * dist);
+
+        if (light.kind == LIGHT_SPOT) {
+            let cos_angle = dot(-to_light, light.direction);
+            attenuation *= smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+        }
+    }
+
+    let n_dot_l = dot(normal, to_light);
+
+    // Skip the shadow ray when the light can't reach us anyways
+    if (n_dot_l <= 0.0 || attenuation <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+
+    if (!is_visible(pos + normal * RAY_EPSILON, to_light, dist - RAY_EPSILON)) {
+        return vec3<f32>(0.0);
+    }
+
+    return light.color * light.intensity * attenuation * n_dot_l;
+}
+
+// Next event estimation, picks one area light proportional to its power and samples a point on it
+fn sample_area_lights(pos: vec3f, normal: vec3f, rng_state: ptr<function, u32>) -> vec3f {
+    let count = light_info.area_light_count;
+
+    if (count == 0u || light_info.area_light_power <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+
+    // Binary search the cdf for the first light past our random value
+    let pick = rand_f32(rng_state);
+    var low = 0u;
+    var high = count - 1u;
+
+    while (low < high) {
+        let mid = (low + high) / 2u;
+
+        if (area_light_storage.data[mid].cdf < pick) {
+            low = mid + 1u;
+        } else {
+            high = mid;
+        }
+    }
+
+    let light = area_light_storage.data[low];
+    let pick_pdf = luminance(light.emission) * light.area / light_info.area_light_power;
+
+    // Uniform point on the triangle, folding the square over onto the triangle
+    var b = vec2<f32>(rand_f32(rng_state), rand_f32(rng_state));
+    if (b.x + b.y > 1.0) {
+        b = 1.0 - b;
+    }
+
+    let light_pos = light.v0 + b.x * (light.v1 - light.v0) + b.y * (light.v2 - light.v0);
+    let light_normal = normalize(cross(light.v1 - light.v0, light.v2 - light.v0));
+
+    let offset = light_pos - pos;
+    let dist = length(offset);
+    let to_light = offset / dist;
+
+    let cos_surface = dot(normal, to_light);
+
+    // Emissive triangles light both of their sides
+    let cos_light = abs(dot(light_normal, -to_light));
+
+    if (cos_surface <= 0.0 || cos_light <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+
+    if (!is_visible(pos + normal * RAY_EPSILON, to_light, dist - RAY_EPSILON * 2.0)) {
+        return vec3<f32>(0.0);
+    }
+
+    // Converting the area pdf of the sample over to solid angle gives dist^2 / cos_light
+    let pdf = pick_pdf / light.area;
+    return light.emission * cos_surface * cos_light / (dist * dist * pdf);
+}
+
+// !-- Background --! //
+
+// Radiance coming from the background in a direction
+// include_sun is false when lighting surfaces, since the sun is lit separately as if it were a directional light
+fn eval_background(dir: vec3f, include_sun: bool) -> vec3f {
+    switch (background.kind) {
+        case BACKGROUND_GRADIENT: {
+            if (dir.y < 0.0) {
+                return background.ground;
+            }
+
+            return mix(background.horizon, background.color, sqrt(dir.y));
+        }
+        case BACKGROUND_SKY: {
+            return eval_sky(dir, include_sun);
+        }
+        case BACKGROUND_ENVIRONMENT: {
+            return textureLoad(env_texture, env_uv_to_texel(dir_to_env_uv(dir)), 0).rgb * background.intensity;
+        }
+        default: {
+            return background.color;
+        }
+    }
+}
+
+fn eval_sky(dir: vec3f, include_sun: bool) -> vec3f {
+    let sun_dir = background.sun_dir;
+
+    // Deeper blue straight up, paler near the horizon
+    let zenith = vec3<f32>(0.25, 0.45, 0.85);
+    let horizon = vec3<f32>(0.75, 0.85, 0.95);
+    var sky = mix(horizon, zenith, pow(max(dir.y, 0.0), 0.4));
+
+    // The whole sky darkens as the sun sets
+    sky *= mix(0.05, 1.0, smoothstep(-0.1, 0.3, sun_dir.y));
+
+    if (dir.y < 0.0) {
+        return sky * 0.3;
+    }
+
+    // Glow around the sun, tinted by the sun color
+    let cos_sun = dot(dir, sun_dir);
+    sky += background.sun_color * pow(max(cos_sun, 0.0), 64.0) * 0.5;
+
+    // Intensity is the irradiance of the sun, so the disk's radiance is spread over its solid angle
+    if (include_sun && cos_sun > SUN_COS_ANGLE) {
+        sky += background.sun_color * background.intensity / SUN_SOLID_ANGLE;
+    }
+
+    return sky;
+}
+
+// Equirectangular mapping, u goes around the y axis and v goes from straight up to straight down
+fn dir_to_env_uv(dir: vec3f) -> vec2f {
+    let phi = atan2(dir.z, dir.x) + background.env_rotation;
+    let u = fract(phi / (2.0 * PI) + 0.5);
+    let v = acos(clamp(dir.y, -1.0, 1.0)) / PI;
+
+    return vec2<f32>(u, v);
+}
+
+fn env_uv_to_dir(uv: vec2f) -> vec3f {
+    let phi = (uv.x - 0.5) * 2.0 * PI - background.env_rotation;
+    let theta = uv.y * PI;
+
+    return vec3<f32>(sin(theta) * cos(phi), cos(theta), sin(theta) * sin(phi));
+}
+
+fn env_uv_to_texel(uv: vec2f) -> vec2<u32> {
+    let size = vec2<u32>(background.env_width, background.env_height);
+    return min(vec2<u32>(uv * vec2<f32>(size)), size - 1u);
+}
+
+// Binary search a row of the cdf texture for the first entry past value
+fn search_env_cdf(row: u32, count: u32, value: f32) -> u32 {
+    var low = 0u;
+    var high = count - 1u;
+
+    while (low < high) {
+        let mid = (low + high) / 2u;
+
+        if (textureLoad(env_cdf, vec2<u32>(mid, row), 0).r < value) {
+            low = mid + 1u;
+        } else {
+            high = mid;
+        }
+    }
+
+    return low;
+}
+
+// Picks a direction proportional to the brightness of the environment map
+// Returns the direction in xyz and its solid angle pdf in w
+fn sample_environment(rng_state: ptr<function, u32>) -> vec4f {
+    let width = background.env_width;
+    let height = background.env_height;
+
+    let row = search_env_cdf(height, height, rand_f32(rng_state));
+    let col = search_env_cdf(row, width, rand_f32(rng_state));
+
+    let jitter = vec2<f32>(rand_f32(rng_state), rand_f32(rng_state));
+    let uv = (vec2<f32>(f32(col), f32(row)) + jitter) / vec2<f32>(f32(width), f32(height));
+
+    let dir = env_uv_to_dir(uv);
+    return vec4<f32>(dir, environment_pdf(vec2<u32>(col, row), uv.y));
+}
+
+// The cdf weights each texel by its luminance and the sin of its row's center
+// Going from texel space over to directions divides by 2 * PI^2 * sin(theta)
+fn environment_pdf(texel: vec2<u32>, v: f32) -> f32 {
+    let sin_theta = sin(v * PI);
+    let row_sin_theta = sin((f32(texel.y) + 0.5) / f32(background.env_height) * PI);
+
+    if (sin_theta <= 0.0 || background.env_total <= 0.0) {
+        return 0.0;
+    }
+
+    let texel_count = f32(background.env_width * background.env_height);
+    let weight = luminance(textureLoad(env_texture, texel, 0).rgb) * row_sin_theta;
+
+    return weight * texel_count / (background.env_total * 2.0 * PI * PI * sin_theta);
+}
+
+// Cosine weighted direction around the normal, the pdf is cos(theta) / PI
+fn sample_cosine_hemisphere(normal: vec3f, rng_state: ptr<function, u32>) -> vec3f {
+    let r1 = rand_f32(rng_state);
+    let r2 = rand_f32(rng_state);
+
+    let phi = 2.0 * PI * r1;
+    let radius = sqrt(r2);
+    let local = vec3<f32>(radius * cos(phi), radius * sin(phi), sqrt(max(1.0 - r2, 0.0)));
+
+    // Any tangent works, we just need one that isn't parallel to the normal
+    let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(normal.x) > 0.9);
+    let tangent = normalize(cross(helper, normal));
+    let bitangent = cross(normal, tangent);
+
+    return normalize(tangent * local.x + bitangent * local.y + normal * local.z);
+}
+
+// One sample of the light coming in from the background, already divided by its pdf
+fn sample_background_light(pos: vec3f, normal: vec3f, rng_state: ptr<function, u32>) -> vec3f {
+    let origin = pos + normal * RAY_EPSILON;
+    var incoming = vec3<f32>(0.0);
+
+    if (background.kind == BACKGROUND_ENVIRONMENT && background.env_total > 0.0) {
+        let sample = sample_environment(rng_state);
+        let cos_theta = dot(normal, sample.xyz);
+
+        if (cos_theta > 0.0 && sample.w > 0.0 && is_visible(origin, sample.xyz, T_MAX)) {
+            incoming += eval_background(sample.xyz, false) * cos_theta / sample.w;
+        }
+    } else {
+        // cos / pdf cancels down to PI
+        let dir = sample_cosine_hemisphere(normal, rng_state);
+
+        if (is_visible(origin, dir, T_MAX)) {
+            incoming += eval_background(dir, false) * PI;
+        }
+    }
+
+    // The sun is too small to ever be found by chance, so it gets lit like a directional light
+    if (background.kind == BACKGROUND_SKY) {
+        let n_dot_l = dot(normal, background.sun_dir);
+
+        if (n_dot_l > 0.0 && is_visible(origin, background.sun_dir, T_MAX)) {
+            incoming += background.sun_color * background.intensity * n_dot_l;
+        }
+    }
+
+    return incoming;
+}
+
+fn shade(ray: Ray, hit: Hit, rng_state: ptr<function, u32>) -> vec3f {
+    if (!hit.hit) {
+        return eval_background(ray.direction, true);
+    }
+
+    let tri_data = triangle_storage.data[hit.triangle];
+    let albedo = tri_data.rgba.rgb;
+
+    let pos = ray.origin + ray.direction * hit.t;
+    let normal = get_hit_normal(ray, hit);
+
+    var incoming = vec3<f32>(0.0);
+
+    for (var i = 0u; i < light_info.light_count; i++) {
+        incoming += eval_light(light_storage.data[i], pos, normal);
+    }
+
+    incoming += sample_area_lights(pos, normal, rng_state);
+    incoming += sample_background_light(pos, normal, rng_state);
+
+    // Lambertian surfaces for now
+    return tri_data.emission.rgb + albedo / PI * incoming;
+}
+
+fn luminance(rgb: vec3f) -> f32 {
+    return dot(rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// !-- Debug Views --! //
+
+fn debug_color(ray: Ray, hit: Hit) -> vec3f {
+    // The heatmaps show the cost of misses too, so they're handled before the miss check
+    switch (render_settings.mode) {
+        case MODE_TRAVERSAL_STEPS: {
+            return heatmap(f32(hit.steps) / HEATMAP_MAX_STEPS);
+        }
+        case MODE_BOX_TESTS: {
+            return heatmap(f32(hit.box_tests) / HEATMAP_MAX_BOX_TESTS);
+        }
+        default: {}
+    }
+
+    if (!hit.hit) {
+        return vec3<f32>(0.0);
+    }
+
+    switch (render_settings.mode) {
+        case MODE_NORMALS: {
+            return get_hit_normal(ray, hit) * 0.5 + 0.5;
+        }
+        case MODE_DEPTH: {
+            return vec3<f32>(DEPTH_HALF_DISTANCE / (DEPTH_HALF_DISTANCE + hit.t));
+        }
+        case MODE_BARYCENTRICS: {
+            return vec3<f32>(1.0 - hit.u - hit.v, hit.u, hit.v);
+        }
+        case MODE_TRIANGLE_ID: {
+            return id_to_color(hit.triangle);
+        }
+        case MODE_INSTANCE_ID: {
+            return id_to_color(hit.instance);
+        }
+        default: {
+            return vec3<f32>(0.0);
+        }
+    }
+}
+
+// Blue through green and yellow up to red, anything past 1 stays red
+fn heatmap(value: f32) -> vec3f {
+    let x = clamp(value, 0.0, 1.0);
+
+    let r = smoothstep(0.5, 0.75, x);
+    let g = smoothstep(0.0, 0.25, x) * (1.0 - smoothstep(0.75, 1.0, x));
+    let b = 1.0 - smoothstep(0.25, 0.5, x);
+
+    return vec3<f32>(r, g, b);
+}
+
+// Neighboring ids get very different colors, so edges between triangles stand out
+fn id_to_color(id: u32) -> vec3f {
+    let hash = hash_u32(id + 1u);
+
+    return vec3<f32>(
+        f32(hash & 0xffu),
+        f32((hash >> 8u) & 0xffu),
+        f32((hash >> 16u) & 0xffu),
+    ) / 255.0;
+}
+
+// !-- Random Numbers --! //
+
+// PCG hash, good enough for picking lights and sample points
+fn hash_u32(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn rand_f32(rng_state: ptr<function, u32>) -> f32 {
+    *rng_state = hash_u32(*rng_state);
+    return f32(*rng_state) / 4294967295.0;
+}
+
 fn point_distance(p1: vec2<f32>, p2: vec2<f32>) -> f32 {
     return sqrt(
         (p1.x - p2.x) * (p1.x - p2.x) +